@@ -0,0 +1,199 @@
+use std::{io, process::Stdio};
+
+use anyhow::{Context, Result};
+use async_compression::tokio::bufread::{GzipEncoder, ZstdEncoder};
+use axum::body::Bytes;
+use futures::{Stream, StreamExt, TryStreamExt};
+use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use crate::image::Image;
+
+/// Compression of an exported image archive. `docker load` detects it on
+/// import, so importing takes any of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub const ALL: [Compression; 3] = [Self::None, Self::Gzip, Self::Zstd];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.to_str() == s)
+    }
+
+    /// File extension, e.g. `tar.gz`
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::None => "tar",
+            Self::Gzip => "tar.gz",
+            Self::Zstd => "tar.zst",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::None => "application/x-tar",
+            Self::Gzip => "application/gzip",
+            Self::Zstd => "application/zstd",
+        }
+    }
+}
+
+/// Only the images docker lists can be exported, which also keeps anything
+/// that looks like an option out of `docker save`
+pub fn validate_export(references: &[String], images: &[Image]) -> Result<()> {
+    if references.is_empty() {
+        return Err(anyhow::anyhow!("no images selected"));
+    }
+
+    for reference in references {
+        let known = images
+            .iter()
+            .any(|image| image.id == *reference || image.reference().as_ref() == Some(reference));
+        if !known {
+            return Err(anyhow::anyhow!("unknown image {}", reference));
+        }
+    }
+
+    Ok(())
+}
+
+/// Download name of the archive, the image for a single one
+pub fn file_name(references: &[String], compression: Compression) -> String {
+    let name = match references {
+        [reference] => reference
+            .trim_start_matches("sha256:")
+            .replace(['/', ':', '@'], "_"),
+        _ => "images".to_string(),
+    };
+    format!("{}.{}", name, compression.extension())
+}
+
+/// `docker save` of the images as it is written, compressed on the fly.
+/// Images saved by `repository:tag` keep their tag when loaded, the ones
+/// saved by id come back untagged. The stream fails when `docker save` does,
+/// which cuts the download short.
+pub fn export(
+    references: Vec<String>,
+    compression: Compression,
+) -> Result<impl Stream<Item = io::Result<Bytes>>> {
+    let mut child = tokio::process::Command::new("docker")
+        .arg("save")
+        .args(&references)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // stop saving when the download is cancelled
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let stdout = BufReader::new(child.stdout.take().context("docker save: no stdout")?);
+    let reader: Box<dyn AsyncRead + Send + Unpin> = match compression {
+        Compression::None => Box::new(stdout),
+        Compression::Gzip => Box::new(GzipEncoder::new(stdout)),
+        Compression::Zstd => Box::new(ZstdEncoder::new(stdout)),
+    };
+
+    Ok(async_stream::stream! {
+        let mut chunks = ReaderStream::new(reader);
+        while let Some(chunk) = chunks.next().await {
+            yield chunk;
+        }
+
+        let mut stderr = String::new();
+        if let Some(mut pipe) = child.stderr.take() {
+            pipe.read_to_string(&mut stderr).await.ok();
+        }
+        match child.wait().await {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                tracing::warn!("docker save {}: {} {}", references.join(" "), status, stderr.trim());
+                yield Err(io::Error::other(format!("docker save failed: {}", stderr.trim())));
+            }
+            Err(e) => yield Err(e),
+        }
+    })
+}
+
+/// Stream an uploaded archive into `docker load` as it arrives, and return
+/// what docker printed, e.g. `Loaded image: alpine:latest`
+pub async fn import<S, E>(body: S) -> Result<String>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let mut child = tokio::process::Command::new("docker")
+        .arg("load")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().context("docker load: no stdin")?;
+    let mut reader = StreamReader::new(body.map_err(io::Error::other));
+    let copy = async {
+        let copied = tokio::io::copy(&mut reader, &mut stdin).await;
+        // the end of the archive
+        drop(stdin);
+        copied
+    };
+
+    let (copied, output) = tokio::join!(copy, child.wait_with_output());
+    let output = output?;
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+
+    // docker's error says more than the broken pipe it causes
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("docker load failed: {}", stderr));
+    }
+    let copied = copied.context("failed to read the upload")?;
+    tracing::info!("docker load: {} bytes, {}", copied, stdout);
+
+    Ok(stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_name() {
+        assert_eq!(
+            super::file_name(&["ghcr.io/org/app:1.2".to_string()], Compression::Zstd),
+            "ghcr.io_org_app_1.2.tar.zst"
+        );
+        assert_eq!(
+            super::file_name(&["sha256:abc".to_string()], Compression::None),
+            "abc.tar"
+        );
+        assert_eq!(
+            super::file_name(&["a:1".to_string(), "b:2".to_string()], Compression::Gzip),
+            "images.tar.gz"
+        );
+    }
+
+    #[test]
+    fn validate_export() {
+        let image: Image = serde_json::from_str(r#"{"Containers":"N/A","CreatedAt":"2024-02-13 20:26:49 +0100 CET","CreatedSince":"","Digest":"<none>","ID":"sha256:111","Repository":"alpine","SharedSize":"N/A","Size":"7MB","Tag":"latest","UniqueSize":"N/A","VirtualSize":"7MB"}"#).unwrap();
+        let images = [image];
+
+        assert!(super::validate_export(&["alpine:latest".to_string()], &images).is_ok());
+        assert!(super::validate_export(&["sha256:111".to_string()], &images).is_ok());
+        assert!(super::validate_export(&["--output=/etc/x".to_string()], &images).is_err());
+        assert!(super::validate_export(&[], &images).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::Parser;
 
 /// mgdocker - A simple web interface for managing docker containers and images
@@ -10,4 +12,7 @@ pub struct Args {
     /// Host that the server will run on
     #[arg(long, default_value = "localhost")]
     pub host: String,
+    /// Path to a toml config file
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
 }
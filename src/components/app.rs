@@ -6,12 +6,32 @@ use crate::model::AppPage;
 pub fn AppComponent(app_page: AppPage) -> impl IntoView {
     let ap = app_page.clone();
     let index_link = view! {
-        <a href="/" class={move || if ap == AppPage::Index {"current"} else {""}}>Containers</a>
+        <a href="/" class={move || if matches!(ap, AppPage::Index(_) | AppPage::Container(_) | AppPage::Logs(_) | AppPage::Metrics(_) | AppPage::Terminal(_)) {"current"} else {""}}>Containers</a>
     };
 
     let ap = app_page.clone();
     let images_link = view! {
-        <a href="/images" class={move || if ap == AppPage::Images {"current"} else {""}}>Images</a>
+        <a href="/images" class={move || if matches!(ap, AppPage::Images(_) | AppPage::Image(_) | AppPage::ImageLayers(_)) {"current"} else {""}}>Images</a>
+    };
+
+    let ap = app_page.clone();
+    let volumes_link = view! {
+        <a href="/volumes" class={move || if matches!(ap, AppPage::Volumes | AppPage::Volume(_)) {"current"} else {""}}>Volumes</a>
+    };
+
+    let ap = app_page.clone();
+    let disk_link = view! {
+        <a href="/disk" class={move || if matches!(ap, AppPage::Disk) {"current"} else {""}}>Disk</a>
+    };
+
+    let ap = app_page.clone();
+    let prune_link = view! {
+        <a href="/prune" class={move || if matches!(ap, AppPage::Prune) {"current"} else {""}}>Prune</a>
+    };
+
+    let ap = app_page.clone();
+    let retention_link = view! {
+        <a href="/retention" class={move || if matches!(ap, AppPage::Retention) {"current"} else {""}}>Retention</a>
     };
 
     view! {
@@ -20,14 +40,51 @@ pub fn AppComponent(app_page: AppPage) -> impl IntoView {
             <nav>
                 {index_link}
                 {images_link}
+                {volumes_link}
+                {disk_link}
+                {prune_link}
+                {retention_link}
             </nav>
         </header>
         {match app_page {
-            AppPage::Index => view! {
-                <div style="word-break:break-word" hx-get="/components/containers" hx-trigger="load"></div>
+            AppPage::Index(filter) => view! {
+                <div style="word-break:break-word" hx-get=format!("/components/containers{}", filter.query()) hx-trigger="load"></div>
+            },
+            AppPage::Images(filter) => view! {
+                <div style="word-break:break-word" hx-get=format!("/components/images{}", filter.query()) hx-trigger="load"></div>
+            },
+            AppPage::Logs(source) => view! {
+                <div hx-get=format!("/components{}", source.path()) hx-trigger="load"></div>
+            },
+            AppPage::Container(id) => view! {
+                <div style="word-break:break-word" hx-get=format!("/components/containers/{}", id) hx-trigger="load"></div>
+            },
+            AppPage::Image(id) => view! {
+                <div style="word-break:break-word" hx-get=format!("/components/images/{}", id) hx-trigger="load"></div>
+            },
+            AppPage::ImageLayers(id) => view! {
+                <div style="word-break:break-word" hx-get=format!("/components/images/{}/layers", id) hx-trigger="load"></div>
+            },
+            AppPage::Metrics(source) => view! {
+                <div hx-get=format!("/components/metrics/{}/{}", source.kind(), source.name()) hx-trigger="load"></div>
+            },
+            AppPage::Volumes => view! {
+                <div style="word-break:break-word" hx-get="/components/volumes" hx-trigger="load"></div>
+            },
+            AppPage::Volume(name) => view! {
+                <div style="word-break:break-word" hx-get=format!("/components/volumes/{}", name) hx-trigger="load"></div>
+            },
+            AppPage::Disk => view! {
+                <div style="word-break:break-word" hx-get="/components/disk" hx-trigger="load"></div>
+            },
+            AppPage::Prune => view! {
+                <div style="word-break:break-word" hx-get="/components/prune" hx-trigger="load"></div>
+            },
+            AppPage::Retention => view! {
+                <div style="word-break:break-word" hx-get="/components/retention" hx-trigger="load"></div>
             },
-            AppPage::Images => view! {
-                <div style="word-break:break-word" hx-get="/components/images" hx-trigger="load"></div>
+            AppPage::Terminal(name) => view! {
+                <div hx-get=format!("/components/containers/{}/terminal", name) hx-trigger="load"></div>
             },
         }}
     }
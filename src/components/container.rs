@@ -1,76 +1,560 @@
 use leptos::*;
 
-use crate::{container::Container, model::SseTask};
+use crate::{
+    components::shared::{summary::SummaryComponent, time::RelativeTimeComponent},
+    container::{Container, KILL_SIGNALS},
+    fields::Port,
+    inspect::{ContainerInspect, PortBinding, Top},
+    logs::LogSource,
+    model::SseTask,
+    util::{dom_id, format_bytes},
+};
+
+/// Link to a published tcp port. Ports published on every interface
+/// are opened on the host the page was loaded from.
+#[component]
+pub fn PortComponent(port: Port) -> impl IntoView {
+    let text = port.to_string();
+    let host_port = match (port.protocol.as_str(), port.host_port) {
+        ("tcp", Some(host_port)) => host_port,
+        _ => return view! { <code>{text}</code> }.into_view(),
+    };
+
+    let (host, onclick) = match port.host_ip.as_deref() {
+        Some(ip) if !port.is_wildcard() && ip.contains(':') => (format!("[{}]", ip), None),
+        Some(ip) if !port.is_wildcard() => (ip.to_string(), None),
+        _ => (
+            "localhost".to_string(),
+            Some("this.hostname = location.hostname"),
+        ),
+    };
+
+    view! {
+        <a href=format!("http://{}:{}", host, host_port) onclick=onclick target="_blank">
+            <code>{text}</code>
+        </a>
+    }
+    .into_view()
+}
 
 #[component]
 pub fn ContainerComponent(c: Container) -> impl IntoView {
-    let pull_url = format!("/components/shared/sse/{}/{}", c.names, SseTask::Pull);
-    let update_url = format!("/components/shared/sse/{}/{}", c.names, SseTask::Update);
-    let config_url = format!("/components/shared/sse/{}/{}", c.names, SseTask::GetConfig);
     view! {
         <details>
             <summary>
-                {c.names}
+                {c.names.clone()}
+                " "
+                <ContainerStatusComponent c=c.clone() />
+                " "
+                <span sse-swap=format!("stats-{}", c.names)></span>
             </summary>
-            <div style="display:flex;gap:0.5rem">
-                <button
-                    hx-get=pull_url
-                    hx-swap="innerHTML"
-                    hx-target="next #container_task_container"
-                    title="docker compose pull"
-                    hx-indicator="next .loader"
-                >
-                    "Pull"
-                </button>
-                <button
-                    hx-get=update_url
-                    hx-swap="innerHTML"
-                    hx-target="next #container_task_container"
-                    hx-indicator="next .loader"
-                    title="docker compose down && docker compose up -d"
-                >
-                    "Update"
-                </button>
-                <button
-                    hx-get=config_url
-                    hx-swap="innerHTML"
-                    hx-target="next #container_task_container"
-                    hx-indicator="next .loader"
-                >
-                    "View Config"
-                </button>
-            </div>
-            <div class="loader htmx-indicator">"Loading..."</div>
-            <div id="container_task_container"></div>
+            <ContainerActionsComponent c=c.clone() />
+            <ContainerInfoComponent c=c />
+        </details>
+    }
+}
+
+/// Status and health badge, swapped in on its own by the live updates
+#[component]
+pub fn ContainerStatusComponent(c: Container, #[prop(optional)] oob: bool) -> impl IntoView {
+    let health = c.health().map(|health| {
+        let class = format!("health health-{}", health.to_str());
+        view! { <small class=class>{health.to_str()}</small> }
+    });
+
+    view! {
+        <span id=dom_id(&["container-status", &c.names]) hx-swap-oob=oob.then_some("true")>
+            <small>{c.status.clone()}</small>
+            " "
+            {health}
+        </span>
+    }
+}
+
+#[component]
+pub fn ContainerInfoComponent(c: Container, #[prop(optional)] oob: bool) -> impl IntoView {
+    let ports = c
+        .ports
+        .iter()
+        .map(|port| view! { <PortComponent port=port.clone() /> " " })
+        .collect::<Vec<_>>();
+
+    let size = c
+        .size
+        .map(|size| size.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let labels = c
+        .labels
+        .iter()
+        .map(|(key, value)| view! { <li><code>{key.clone()} "=" {value.clone()}</code></li> })
+        .collect::<Vec<_>>();
+
+    view! {
+        <div id=dom_id(&["container-info", &c.names]) hx-swap-oob=oob.then_some("true")>
             <div><b>"id: "</b>{c.id}</div>
             <div><b>"image: "</b> {c.image}</div>
+            <div><b>"command: "</b> {c.command}</div>
             <div><b>"status: "</b> {c.status}</div>
             <div><b>"state: "</b> {c.state}</div>
-            <div><b>"ports: "</b> {c.ports}</div>
-            <div><b>"created at: "</b> {c.created_at}</div>
-            <div><b>"running for: "</b> {c.running_for}</div>
-            <div><b>"size: "</b> {c.size}</div>
-            <div><b>"mounts: "</b> {c.mounts}</div>
-            <div><b>"networks: "</b> {c.networks}</div>
-            <div><b>"local volumes: "</b> {c.local_volumes}</div>
-            <div><b>"labels: "</b> {c.labels}</div>
-        </details>
+            <div><b>"ports: "</b> {ports}</div>
+            <div><b>"created: "</b> <RelativeTimeComponent time=c.created_at /></div>
+            <div><b>"size: "</b> {size}</div>
+            <div><b>"mounts: "</b> {c.mounts.join(", ")}</div>
+            <div><b>"networks: "</b> {c.networks.join(", ")}</div>
+            <div><b>"local volumes: "</b> {c.local_volumes.unwrap_or_default()}</div>
+            <div><b>"labels: "</b> <ul class="labels">{labels}</ul></div>
+        </div>
+    }
+}
+
+/// Lifecycle buttons for a single container and the output of the task they run
+#[component]
+pub fn ContainerActionsComponent(c: Container) -> impl IntoView {
+    view! {
+        <ContainerButtonsComponent c=c />
+        <div class="loader htmx-indicator">"Loading..."</div>
+        <div id="container_task_container"></div>
     }
 }
 
+/// The buttons are disabled when the action doesn't apply to the
+/// container's current state
 #[component]
-pub fn ContainerListComponent(containers: Vec<Container>) -> impl IntoView {
-    let (containers, _) = create_signal::<Vec<Container>>(containers);
+pub fn ContainerButtonsComponent(c: Container, #[prop(optional)] oob: bool) -> impl IntoView {
+    let button = |task: SseTask, label: &'static str, title: &'static str, confirm: bool| {
+        let url = format!("/components/shared/sse/{}/{}", c.names, task);
+        let confirm = confirm.then(|| format!("{} {}?", label, c.names));
+        view! {
+            <button
+                hx-get=url
+                hx-swap="innerHTML"
+                hx-target="next #container_task_container"
+                hx-indicator="next .loader"
+                hx-confirm=confirm
+                hx-include=(task == SseTask::ContainerKill).then_some("closest .kill")
+                title=title
+                disabled=!c.can(&task)
+            >
+                {label}
+            </button>
+        }
+    };
+
+    let signals = KILL_SIGNALS
+        .iter()
+        .map(|signal| view! { <option value=*signal>{*signal}</option> })
+        .collect::<Vec<_>>();
+
+    let recreate = c.is_standalone().then(|| {
+        button(
+            SseTask::ContainerRecreate,
+            "Recreate",
+            "pull the image and re-create the container with the same configuration",
+            true,
+        )
+    });
 
     view! {
-        <For
-            each=move || containers.get()
-            key=|c| c.id.clone()
-            children=move |c: Container| {
+        <div
+            class="actions"
+            id=dom_id(&["container-actions", &c.names])
+            hx-swap-oob=oob.then_some("true")
+        >
+            {button(SseTask::ContainerStart, "Start", "docker container start", false)}
+            {button(SseTask::ContainerStop, "Stop", "docker container stop", true)}
+            {button(SseTask::ContainerRestart, "Restart", "docker container restart", false)}
+            {button(SseTask::ContainerPause, "Pause", "docker container pause", false)}
+            {button(SseTask::ContainerUnpause, "Unpause", "docker container unpause", false)}
+            <span class="kill">
+                {button(SseTask::ContainerKill, "Kill", "docker container kill --signal", true)}
+                <select name="signal" disabled=!c.can(&SseTask::ContainerKill)>
+                    {signals}
+                </select>
+            </span>
+            {button(SseTask::ContainerRemove, "Remove", "docker container rm", true)}
+            {recreate}
+            <a class="button" href=format!("/containers/{}", c.id)>"Details"</a>
+            <a class="button" href=LogSource::Container(c.names.clone()).path()>"Logs"</a>
+            <a class="button" href=format!("/metrics/container/{}", c.names)>"Metrics"</a>
+            {(c.state == "running").then(|| view! {
+                <a class="button" href=format!("/containers/{}/terminal", c.names) title="docker exec -it">
+                    "Terminal"
+                </a>
+            })}
+        </div>
+    }
+}
+
+/// e.g. "1/2 running"
+pub fn standalone_summary(containers: &[Container]) -> String {
+    let running = containers.iter().filter(|c| c.state == "running").count();
+    format!("{}/{} running", running, containers.len())
+}
+
+/// Containers that aren't part of a compose project
+#[component]
+pub fn StandaloneContainersComponent(
+    containers: Vec<Container>,
+    #[prop(optional)] oob: bool,
+) -> impl IntoView {
+    let section = (!containers.is_empty()).then(|| {
+        let summary = standalone_summary(&containers);
+        let (containers, _) = create_signal(containers);
+        view! {
+            <section class="project">
+                <h3>
+                    "Standalone containers"
+                    " "
+                    <SummaryComponent id="standalone-summary".to_string() summary=summary />
+                </h3>
+                <For
+                    each=move || containers.get()
+                    key=|c| c.id.clone()
+                    children=move |c| {
+                        view! {
+                            <ContainerComponent c=c />
+                        }
+                    }
+                />
+            </section>
+        }
+    });
+
+    view! {
+        <div id="standalone" hx-swap-oob=oob.then_some("true")>
+            {section}
+        </div>
+    }
+}
+
+/// e.g. `1m30s` for a healthcheck interval in nanoseconds
+fn format_nanos(nanos: i64) -> String {
+    let secs = nanos / 1_000_000_000;
+    match (secs / 60, secs % 60) {
+        (0, _) if nanos > 0 && secs == 0 => format!("{}ms", nanos / 1_000_000),
+        (0, secs) => format!("{}s", secs),
+        (mins, 0) => format!("{}m", mins),
+        (mins, secs) => format!("{}m{}s", mins, secs),
+    }
+}
+
+fn format_bindings(bindings: &Option<Vec<PortBinding>>) -> String {
+    let bindings = bindings
+        .iter()
+        .flatten()
+        .map(|b| match b.host_ip.as_str() {
+            "" => b.host_port.clone(),
+            ip if ip.contains(':') => format!("[{}]:{}", ip, b.host_port),
+            ip => format!("{}:{}", ip, b.host_port),
+        })
+        .collect::<Vec<_>>();
+
+    if bindings.is_empty() {
+        "not published".to_string()
+    } else {
+        bindings.join(", ")
+    }
+}
+
+/// A two column table, or a notice when there are no rows
+pub fn key_value_table(rows: Vec<(String, String)>, empty: &'static str) -> View {
+    if rows.is_empty() {
+        return view! { <p><small>{empty}</small></p> }.into_view();
+    }
+
+    let rows = rows
+        .into_iter()
+        .map(|(key, value)| view! { <tr><td>{key}</td><td><code>{value}</code></td></tr> })
+        .collect::<Vec<_>>();
+
+    view! { <table><tbody>{rows}</tbody></table> }.into_view()
+}
+
+/// Everything `docker inspect` knows about a container, laid out by topic.
+/// Secret environment values are masked, also in the raw json.
+#[component]
+pub fn ContainerDetailComponent(
+    inspect: ContainerInspect,
+    /// pretty printed inspect json
+    raw: String,
+    /// `docker top`, not set when the container isn't running
+    top: Option<Result<Top, String>>,
+) -> impl IntoView {
+    let name = inspect.name().to_string();
+    let state = &inspect.state;
+
+    let mut overview = vec![
+        ("id".to_string(), inspect.id.clone()),
+        ("image".to_string(), inspect.config.image.clone()),
+        ("image id".to_string(), inspect.image.clone()),
+        ("status".to_string(), state.status.clone()),
+        ("created".to_string(), inspect.created.clone()),
+        ("started".to_string(), state.started_at.clone()),
+    ];
+    if !state.running {
+        overview.push(("finished".to_string(), state.finished_at.clone()));
+        overview.push(("exit code".to_string(), state.exit_code.to_string()));
+    }
+    if state.oom_killed {
+        overview.push(("oom killed".to_string(), "true".to_string()));
+    }
+    overview.push(("restarts".to_string(), inspect.restart_count.to_string()));
+
+    let restart = &inspect.host_config.restart_policy;
+    let restart_policy = match (restart.name.as_str(), restart.maximum_retry_count) {
+        ("" | "no", _) => "no".to_string(),
+        (name, 0) => name.to_string(),
+        (name, retries) => format!("{} (max {} retries)", name, retries),
+    };
+    overview.push(("restart policy".to_string(), restart_policy));
+
+    let mut labels = inspect
+        .config
+        .labels
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .collect::<Vec<_>>();
+    labels.sort();
+
+    let mounts = if inspect.mounts.is_empty() {
+        view! { <p><small>"No mounts"</small></p> }.into_view()
+    } else {
+        let rows = inspect
+            .mounts
+            .iter()
+            .map(|m| {
+                let source = m.name.clone().unwrap_or_else(|| m.source.clone());
+                let mode = match (m.rw, m.mode.as_str()) {
+                    (true, "") => "rw".to_string(),
+                    (false, "") => "ro".to_string(),
+                    (true, mode) => format!("rw,{}", mode),
+                    (false, mode) => format!("ro,{}", mode),
+                };
                 view! {
-                    <ContainerComponent c=c />
+                    <tr>
+                        <td>{m.kind.clone()}</td>
+                        <td><code>{source}</code></td>
+                        <td><code>{m.destination.clone()}</code></td>
+                        <td>{mode}</td>
+                    </tr>
                 }
+            })
+            .collect::<Vec<_>>();
+        view! {
+            <table>
+                <thead><tr><th>"Type"</th><th>"Source"</th><th>"Destination"</th><th>"Mode"</th></tr></thead>
+                <tbody>{rows}</tbody>
+            </table>
+        }
+        .into_view()
+    };
+
+    // the running container's ports, or the configured ones when it's stopped
+    let mut ports = inspect
+        .network_settings
+        .ports
+        .clone()
+        .filter(|ports| !ports.is_empty())
+        .or_else(|| inspect.host_config.port_bindings.clone())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(port, bindings)| (port, format_bindings(&bindings)))
+        .collect::<Vec<_>>();
+    ports.sort();
+
+    let mut networks = inspect.network_settings.networks.iter().collect::<Vec<_>>();
+    networks.sort_by(|a, b| a.0.cmp(b.0));
+    let networks = if networks.is_empty() {
+        view! { <p><small>"No networks"</small></p> }.into_view()
+    } else {
+        let rows = networks
+            .into_iter()
+            .map(|(network, endpoint)| {
+                let mut ips = vec![
+                    endpoint.ip_address.clone(),
+                    endpoint.global_ipv6_address.clone(),
+                ];
+                ips.retain(|ip| !ip.is_empty());
+                let aliases = endpoint.aliases.clone().unwrap_or_default().join(", ");
+                view! {
+                    <tr>
+                        <td>{network.clone()}</td>
+                        <td><code>{ips.join(", ")}</code></td>
+                        <td><code>{endpoint.gateway.clone()}</code></td>
+                        <td><code>{endpoint.mac_address.clone()}</code></td>
+                        <td>{aliases}</td>
+                    </tr>
+                }
+            })
+            .collect::<Vec<_>>();
+        view! {
+            <table>
+                <thead><tr><th>"Network"</th><th>"IP"</th><th>"Gateway"</th><th>"MAC"</th><th>"Aliases"</th></tr></thead>
+                <tbody>{rows}</tbody>
+            </table>
+        }
+        .into_view()
+    };
+
+    let health = match (&inspect.config.healthcheck, &state.health) {
+        (None, None) => view! { <p><small>"No healthcheck"</small></p> }.into_view(),
+        (check, health) => {
+            let mut rows = vec![];
+            if let Some(health) = health {
+                rows.push(("status".to_string(), health.status.clone()));
+                rows.push((
+                    "failing streak".to_string(),
+                    health.failing_streak.to_string(),
+                ));
             }
-        />
+            if let Some(check) = check {
+                rows.push((
+                    "test".to_string(),
+                    check.test.clone().unwrap_or_default().join(" "),
+                ));
+                for (key, nanos) in [
+                    ("interval", check.interval),
+                    ("timeout", check.timeout),
+                    ("start period", check.start_period),
+                ] {
+                    if nanos > 0 {
+                        rows.push((key.to_string(), format_nanos(nanos)));
+                    }
+                }
+                if check.retries > 0 {
+                    rows.push(("retries".to_string(), check.retries.to_string()));
+                }
+            }
+
+            let probes = health
+                .iter()
+                .flat_map(|health| health.log.iter().rev())
+                .map(|probe| {
+                    view! {
+                        <tr>
+                            <td>{probe.start.clone()}</td>
+                            <td>{probe.exit_code}</td>
+                            <td><pre>{probe.output.trim().to_string()}</pre></td>
+                        </tr>
+                    }
+                })
+                .collect::<Vec<_>>();
+            let probes = (!probes.is_empty()).then(|| {
+                view! {
+                    <table>
+                        <thead><tr><th>"Probe"</th><th>"Exit code"</th><th>"Output"</th></tr></thead>
+                        <tbody>{probes}</tbody>
+                    </table>
+                }
+            });
+
+            view! {
+                {key_value_table(rows, "")}
+                {probes}
+            }
+            .into_view()
+        }
+    };
+
+    let host = &inspect.host_config;
+    let mut limits = vec![];
+    if host.memory > 0 {
+        limits.push(("memory".to_string(), format_bytes(host.memory as u64)));
+    }
+    if host.memory_reservation > 0 {
+        limits.push((
+            "memory reservation".to_string(),
+            format_bytes(host.memory_reservation as u64),
+        ));
+    }
+    match host.memory_swap {
+        -1 => limits.push(("memory + swap".to_string(), "unlimited".to_string())),
+        swap if swap > 0 => limits.push(("memory + swap".to_string(), format_bytes(swap as u64))),
+        _ => {}
+    }
+    if host.nano_cpus > 0 {
+        limits.push((
+            "cpus".to_string(),
+            format!("{}", host.nano_cpus as f64 / 1e9),
+        ));
+    }
+    if host.cpu_shares > 0 {
+        limits.push(("cpu shares".to_string(), host.cpu_shares.to_string()));
+    }
+    if !host.cpuset_cpus.is_empty() {
+        limits.push(("cpuset".to_string(), host.cpuset_cpus.clone()));
+    }
+    if let Some(pids) = host.pids_limit.filter(|pids| *pids > 0) {
+        limits.push(("pids".to_string(), pids.to_string()));
+    }
+
+    let processes = match top {
+        None => view! { <p><small>"Not running"</small></p> }.into_view(),
+        Some(Err(e)) => view! { <p class="notice">{e}</p> }.into_view(),
+        Some(Ok(top)) => {
+            let titles = top
+                .titles
+                .into_iter()
+                .map(|title| view! { <th>{title}</th> })
+                .collect::<Vec<_>>();
+            let rows = top
+                .processes
+                .into_iter()
+                .map(|columns| {
+                    let columns = columns
+                        .into_iter()
+                        .map(|column| view! { <td>{column}</td> })
+                        .collect::<Vec<_>>();
+                    view! { <tr>{columns}</tr> }
+                })
+                .collect::<Vec<_>>();
+            view! {
+                <table>
+                    <thead><tr>{titles}</tr></thead>
+                    <tbody>{rows}</tbody>
+                </table>
+            }
+            .into_view()
+        }
+    };
+
+    let terminal = state.running.then(|| {
+        view! { <a class="button" href=format!("/containers/{}/terminal", name)>"Terminal"</a> }
+    });
+
+    view! {
+        <h3>{name.clone()} " " <small>{state.status.clone()}</small></h3>
+        <div class="actions">
+            <a class="button" href=LogSource::Container(name.clone()).path()>"Logs"</a>
+            <a class="button" href=format!("/metrics/container/{}", name)>"Metrics"</a>
+            {terminal}
+        </div>
+        <div class="container-detail">
+            <h4>"Overview"</h4>
+            {key_value_table(overview, "")}
+            <p><a href=format!("/images/{}", inspect.image)>"Image details"</a></p>
+            <h4>"Environment"</h4>
+            {key_value_table(inspect.env(), "No environment variables")}
+            <h4>"Labels"</h4>
+            {key_value_table(labels, "No labels")}
+            <h4>"Mounts"</h4>
+            {mounts}
+            <h4>"Ports"</h4>
+            {key_value_table(ports, "No ports")}
+            <h4>"Networks"</h4>
+            {networks}
+            <h4>"Healthcheck"</h4>
+            {health}
+            <h4>"Resource limits"</h4>
+            {key_value_table(limits, "No limits")}
+            <h4>"Processes"</h4>
+            {processes}
+            <details>
+                <summary>"Raw JSON"</summary>
+                <pre>{raw}</pre>
+            </details>
+        </div>
     }
 }
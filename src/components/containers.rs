@@ -0,0 +1,132 @@
+use leptos::*;
+
+use crate::{
+    components::{container::StandaloneContainersComponent, project::ProjectListComponent},
+    config::Config,
+    container::Container,
+    filter::{ContainerFilter, ContainerSort, SortOrder, CONTAINER_STATES, HEALTH_FILTERS},
+    project::Project,
+};
+
+/// `<option>`s of a filter select, the first one clears the filter
+pub fn filter_options(any: &str, values: Vec<String>, selected: &Option<String>) -> impl IntoView {
+    let options = values
+        .into_iter()
+        .map(|value| {
+            let is_selected = selected.as_ref() == Some(&value);
+            view! { <option value=value.clone() selected=is_selected>{value}</option> }
+        })
+        .collect::<Vec<_>>();
+
+    view! {
+        <option value="">{any.to_string()}</option>
+        {options}
+    }
+}
+
+pub fn order_options(order: SortOrder) -> impl IntoView {
+    view! {
+        <option value="asc" selected=order == SortOrder::Asc>"Ascending"</option>
+        <option value="desc" selected=order == SortOrder::Desc>"Descending"</option>
+    }
+}
+
+/// Containers page, the filters and the list they are applied to
+#[component]
+pub fn ContainersComponent(
+    filter: ContainerFilter,
+    /// every project, for the project filter
+    project_names: Vec<String>,
+    projects: Vec<Project>,
+    standalone: Vec<Container>,
+    config: Config,
+) -> impl IntoView {
+    let states = CONTAINER_STATES.iter().map(|s| s.to_string()).collect();
+    let health = HEALTH_FILTERS.iter().map(|s| s.to_string()).collect();
+    let sorts = ContainerSort::ALL
+        .iter()
+        .map(|sort| {
+            view! {
+                <option value=sort.to_str() selected=*sort == filter.sort>{sort.to_str()}</option>
+            }
+        })
+        .collect::<Vec<_>>();
+
+    view! {
+        <form
+            class="filters"
+            hx-get="/components/containers/list"
+            hx-target="#containers-list"
+            hx-trigger="change, submit"
+            hx-indicator="next .loader"
+        >
+            <input
+                type="search"
+                name="q"
+                placeholder="Search"
+                value=filter.q.clone()
+                hx-get="/components/containers/list"
+                hx-trigger="keyup changed delay:300ms, search"
+                hx-include="closest form"
+            />
+            <select name="state" title="State">
+                {filter_options("Any state", states, &filter.state)}
+            </select>
+            <select name="project" title="Project">
+                {filter_options("Any project", project_names, &filter.project)}
+            </select>
+            <select name="health" title="Health">
+                {filter_options("Any health", health, &filter.health)}
+            </select>
+            <label title="a newer image was pulled, the container still runs the old one">
+                <input type="checkbox" name="has-update" value="true" checked=filter.has_update />
+                "Has update"
+            </label>
+            <select name="sort" title="Sort by">{sorts}</select>
+            <select name="order" title="Order">{order_options(filter.order)}</select>
+        </form>
+        <div class="loader htmx-indicator">"Loading..."</div>
+        <div id="containers-list">
+            <ContainerListComponent
+                filter=filter.clone()
+                projects=projects
+                standalone=standalone
+                config=config
+            />
+        </div>
+    }
+}
+
+/// The filtered projects and standalone containers with their live stats.
+/// Changes from the docker events are swapped in over `/events/stream`, see `live`.
+#[component]
+pub fn ContainerListComponent(
+    filter: ContainerFilter,
+    projects: Vec<Project>,
+    standalone: Vec<Container>,
+    config: Config,
+) -> impl IntoView {
+    let empty = projects.is_empty() && standalone.is_empty();
+
+    view! {
+        <div hx-ext="sse" sse-connect=format!("/events/stream{}", filter.query())>
+            <div sse-swap="inventory" hidden></div>
+            <div sse-connect="/stats/stream">
+                <EmptyNoticeComponent empty=empty />
+                <div id="projects">
+                    <ProjectListComponent projects=projects config=config />
+                </div>
+                <StandaloneContainersComponent containers=standalone />
+            </div>
+        </div>
+    }
+}
+
+#[component]
+pub fn EmptyNoticeComponent(empty: bool, #[prop(optional)] oob: bool) -> impl IntoView {
+    view! {
+        <div id="containers-empty" hx-swap-oob=oob.then_some("true")>
+            {empty.then(|| view! { <p class="notice">"No containers match the filters."</p> })}
+        </div>
+    }
+}
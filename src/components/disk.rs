@@ -0,0 +1,231 @@
+use leptos::*;
+
+use crate::{
+    disk::{DiskUsage, DiskUsageDetail, FreeSpace, ProjectDiskUsage},
+    util::format_bytes,
+};
+
+/// Rows shown per category of the top consumers
+const TOP: usize = 10;
+
+/// A table of the largest items, or a notice when there are none
+fn top_table(headers: [&'static str; 4], rows: Vec<View>, empty: &'static str) -> View {
+    if rows.is_empty() {
+        return view! { <p><small>{empty}</small></p> }.into_view();
+    }
+
+    let headers = headers
+        .into_iter()
+        .map(|header| view! { <th>{header}</th> })
+        .collect::<Vec<_>>();
+
+    view! {
+        <table style="width:100%">
+            <thead><tr>{headers}</tr></thead>
+            <tbody>{rows}</tbody>
+        </table>
+    }
+    .into_view()
+}
+
+fn free_space(free: Result<FreeSpace, String>) -> View {
+    let free = match free {
+        Ok(free) => free,
+        Err(e) => {
+            return view! {
+                <p class="notice">
+                    "The free space of docker's data root is unknown: " {e}
+                    ". mgdocker has to run on the docker host or have the data root mounted."
+                </p>
+            }
+            .into_view()
+        }
+    };
+
+    let used = free.size.saturating_sub(free.available);
+    // less than a tenth free is when builds and pulls start failing
+    let low = free.available * 10 < free.size;
+
+    view! {
+        <p class=low.then_some("notice")>
+            <b>{format_bytes(free.available)}</b>
+            " free of " {format_bytes(free.size)} " on the filesystem of "
+            <code>{free.root_dir}</code>
+        </p>
+        <progress value=used max=free.size style="width:100%"></progress>
+    }
+    .into_view()
+}
+
+/// Where docker's disk space went: totals, the free space left, totals per
+/// compose project and the largest images, containers, volumes and build cache
+#[component]
+pub fn DiskComponent(
+    usage: Vec<DiskUsage>,
+    detail: DiskUsageDetail,
+    projects: Vec<ProjectDiskUsage>,
+    free: Result<FreeSpace, String>,
+) -> impl IntoView {
+    let reclaimable = usage
+        .iter()
+        .filter_map(|u| u.reclaimable_bytes())
+        .sum::<u64>();
+    let totals = usage
+        .into_iter()
+        .map(|u| {
+            let count = |count: Option<u64>| count.map(|c| c.to_string()).unwrap_or_default();
+            view! {
+                <tr>
+                    <td>{u.kind.clone()}</td>
+                    <td>{count(u.total_count)}</td>
+                    <td>{count(u.active)}</td>
+                    <td>{format_bytes(u.size)}</td>
+                    <td>{u.reclaimable.clone()}</td>
+                </tr>
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let projects = projects
+        .into_iter()
+        .map(|p| {
+            view! {
+                <tr>
+                    <td><a href=format!("/?project={}", p.name)>{p.name.clone()}</a></td>
+                    <td>{format_bytes(p.containers)}</td>
+                    <td>{format_bytes(p.volumes)}</td>
+                    <td>{format_bytes(p.images)}</td>
+                    <td><b>{format_bytes(p.total())}</b></td>
+                </tr>
+            }
+        })
+        .collect::<Vec<_>>();
+    let projects = if projects.is_empty() {
+        view! { <p><small>"No compose projects"</small></p> }.into_view()
+    } else {
+        view! {
+            <table style="width:100%">
+                <thead>
+                    <tr>
+                        <th>"Project"</th>
+                        <th>"Containers"</th>
+                        <th>"Volumes"</th>
+                        <th title="layers shared with other images are left out, images used by several projects count for each">
+                            "Images"
+                        </th>
+                        <th>"Total"</th>
+                    </tr>
+                </thead>
+                <tbody>{projects}</tbody>
+            </table>
+        }
+        .into_view()
+    };
+
+    let mut images = detail.images;
+    images.sort_by(|a, b| a.id.cmp(&b.id));
+    images.dedup_by(|a, b| a.id == b.id);
+    images.sort_by_key(|image| std::cmp::Reverse(image.size));
+    let images = images
+        .into_iter()
+        .take(TOP)
+        .map(|image| {
+            let name = image.reference().unwrap_or_else(|| image.id.clone());
+            view! {
+                <tr>
+                    <td><a href=format!("/images/{}", image.id)>{name}</a></td>
+                    <td>{image.containers.map(|c| c.to_string())}</td>
+                    <td>{image.unique_size.map(format_bytes)}</td>
+                    <td>{format_bytes(image.size)}</td>
+                </tr>
+            }
+            .into_view()
+        })
+        .collect();
+
+    let mut containers = detail.containers;
+    containers.sort_by_key(|c| std::cmp::Reverse(c.size.map(|size| size.rw)));
+    let containers = containers
+        .into_iter()
+        .take(TOP)
+        .map(|c| {
+            view! {
+                <tr>
+                    <td><a href=format!("/containers/{}", c.names)>{c.names.clone()}</a></td>
+                    <td>{c.project()}</td>
+                    <td>{c.state.clone()}</td>
+                    <td>{c.size.map(|size| format_bytes(size.rw))}</td>
+                </tr>
+            }
+            .into_view()
+        })
+        .collect();
+
+    let mut volumes = detail.volumes;
+    volumes.sort_by_key(|v| std::cmp::Reverse(v.size));
+    let volumes = volumes
+        .into_iter()
+        .take(TOP)
+        .map(|v| {
+            view! {
+                <tr>
+                    <td><a href=format!("/volumes/{}", v.name)>{v.name.clone()}</a></td>
+                    <td>{v.project()}</td>
+                    <td>{v.links.map(|links| links.to_string())}</td>
+                    <td>{v.size.map(format_bytes)}</td>
+                </tr>
+            }
+            .into_view()
+        })
+        .collect();
+
+    let mut build_cache = detail.build_cache;
+    build_cache.sort_by_key(|record| std::cmp::Reverse(record.size));
+    let build_cache = build_cache
+        .into_iter()
+        .take(TOP)
+        .map(|record| {
+            view! {
+                <tr>
+                    <td><code>{record.description}</code></td>
+                    <td>{record.kind}</td>
+                    <td>{record.last_used_since}</td>
+                    <td>{record.size.map(format_bytes)}</td>
+                </tr>
+            }
+            .into_view()
+        })
+        .collect();
+
+    view! {
+        <h4>"Free space"</h4>
+        {free_space(free)}
+        <h4>"Totals"</h4>
+        <table style="width:100%">
+            <thead>
+                <tr>
+                    <th>"Type"</th>
+                    <th>"Total"</th>
+                    <th>"Active"</th>
+                    <th>"Size"</th>
+                    <th>"Reclaimable"</th>
+                </tr>
+            </thead>
+            <tbody>{totals}</tbody>
+        </table>
+        <p>
+            <a class="button" href="/prune">"Prune"</a>
+            " " {format_bytes(reclaimable)} " reclaimable in total"
+        </p>
+        <h4>"Compose projects"</h4>
+        {projects}
+        <h4>"Largest images"</h4>
+        {top_table(["Image", "Containers", "Unique", "Size"], images, "No images")}
+        <h4>"Largest containers"</h4>
+        {top_table(["Container", "Project", "State", "Writable layer"], containers, "No containers")}
+        <h4>"Largest volumes"</h4>
+        {top_table(["Volume", "Project", "Containers", "Size"], volumes, "No volumes")}
+        <h4>"Largest build cache records"</h4>
+        {top_table(["Step", "Type", "Last used", "Size"], build_cache, "No build cache")}
+    }
+}
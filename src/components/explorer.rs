@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use leptos::*;
+
+use crate::{
+    components::shared::time::RelativeTimeComponent,
+    explorer::{Change, EntryKind, ImageTree},
+    util::format_bytes,
+};
+
+/// Rows of the largest wasted files
+const TOP_WASTED: usize = 20;
+
+/// Url of the directory listing as a layer leaves it
+fn tree_url(id: &str, layer: usize, path: &str) -> String {
+    let query =
+        serde_urlencoded::to_string([("layer", layer.to_string()), ("path", path.to_string())])
+            .unwrap_or_default();
+    format!("/components/images/{}/layers/tree?{}", id, query)
+}
+
+fn change_badge(change: Option<Change>) -> impl IntoView {
+    change.map(|change| {
+        let class = format!("layer-change layer-change-{}", change.to_str());
+        view! { <small class=class>{change.to_str()}</small> }
+    })
+}
+
+/// Layers of an image with what each changed and the space later layers
+/// waste, and the filesystem browser
+#[component]
+pub fn ImageLayersComponent(
+    id: String,
+    name: String,
+    tree: Result<Arc<ImageTree>, String>,
+) -> impl IntoView {
+    let tree = match tree {
+        Ok(tree) => tree,
+        Err(e) => {
+            return view! {
+                <h3>"Layers of " <a href=format!("/images/{}", id)>{name}</a></h3>
+                <p class="notice">{e}</p>
+            }
+            .into_view()
+        }
+    };
+
+    let total = tree.layers.iter().map(|layer| layer.size).sum::<u64>();
+    let wasted = tree.wasted();
+    let wasted_total = wasted.iter().map(|file| file.size).sum::<u64>();
+    let summary = format!(
+        "{} layers, {} of files, {} of them hidden by later layers.",
+        tree.layers.len(),
+        format_bytes(total),
+        format_bytes(wasted_total)
+    );
+
+    let layers = tree
+        .layers
+        .iter()
+        .enumerate()
+        .map(|(index, layer)| {
+            let created = layer
+                .created
+                .map(|time| view! { <RelativeTimeComponent time=time /> });
+            view! {
+                <tr>
+                    <td>{index}</td>
+                    <td>{created}</td>
+                    <td><code>{layer.created_by.clone()}</code></td>
+                    <td>{format_bytes(layer.size)}</td>
+                    <td>
+                        {layer.count(Change::Added)} " / " {layer.count(Change::Modified)} " / "
+                        {layer.count(Change::Deleted)}
+                    </td>
+                    <td>{format_bytes(layer.wasted())}</td>
+                    <td>
+                        <button
+                            hx-get=tree_url(&tree.id, index, "")
+                            hx-target="#layer-tree"
+                            hx-indicator="#layer_tree_loader"
+                        >
+                            "Browse"
+                        </button>
+                    </td>
+                </tr>
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let wasted = if wasted.is_empty() {
+        view! { <p><small>"No layer hides files of another."</small></p> }.into_view()
+    } else {
+        let rows = wasted
+            .into_iter()
+            .take(TOP_WASTED)
+            .map(|file| {
+                let dir = file.path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+                view! {
+                    <tr>
+                        <td>
+                            <a
+                                href="#layer-tree"
+                                hx-get=tree_url(&tree.id, file.layer, dir)
+                                hx-target="#layer-tree"
+                            >
+                                <code>"/" {file.path}</code>
+                            </a>
+                        </td>
+                        <td>{format_bytes(file.size)}</td>
+                        <td>{file.layer}</td>
+                        <td>{file.overwritten_by}</td>
+                    </tr>
+                }
+            })
+            .collect::<Vec<_>>();
+        view! {
+            <table>
+                <thead>
+                    <tr><th>"Path"</th><th>"Size"</th><th>"Layer"</th><th>"Hidden by layer"</th></tr>
+                </thead>
+                <tbody>{rows}</tbody>
+            </table>
+        }
+        .into_view()
+    };
+
+    let last = tree.layers.len().saturating_sub(1);
+    view! {
+        <h3>"Layers of " <a href=format!("/images/{}", id)>{name}</a></h3>
+        <p>{summary}</p>
+        <div class="container-detail">
+            <table>
+                <thead>
+                    <tr>
+                        <th>"#"</th>
+                        <th>"Created"</th>
+                        <th>"Instruction"</th>
+                        <th>"Size"</th>
+                        <th title="added / modified / deleted paths">"Changes"</th>
+                        <th title="files of the layer that later layers replace or delete">"Wasted"</th>
+                        <th></th>
+                    </tr>
+                </thead>
+                <tbody>{layers}</tbody>
+            </table>
+            <h4>"Largest wasted files"</h4>
+            {wasted}
+            <h4>"Files"</h4>
+            <div id="layer_tree_loader" class="loader htmx-indicator">"Loading..."</div>
+            <div id="layer-tree" hx-get=tree_url(&tree.id, last, "") hx-trigger="load"></div>
+        </div>
+    }
+    .into_view()
+}
+
+/// A directory of the merged filesystem as a layer leaves it, with what the
+/// layer changed in it
+#[component]
+pub fn LayerTreeComponent(tree: Arc<ImageTree>, layer: usize, path: String) -> impl IntoView {
+    let Some(current) = tree.layers.get(layer) else {
+        return view! { <p class="notice">"The image has no layer " {layer}</p> }.into_view();
+    };
+    let id = tree.id.clone();
+    let path = path.trim_matches('/').to_string();
+
+    let link = |label: String, layer: usize, path: &str| {
+        view! {
+            <a href="#layer-tree" hx-get=tree_url(&id, layer, path) hx-target="#layer-tree">
+                {label}
+            </a>
+        }
+    };
+
+    let mut crumbs = vec![link("/".to_string(), layer, "").into_view()];
+    let mut dir = String::new();
+    for part in path.split('/').filter(|part| !part.is_empty()) {
+        if !dir.is_empty() {
+            dir.push('/');
+        }
+        dir.push_str(part);
+        crumbs.push(link(format!("{}/", part), layer, &dir).into_view());
+    }
+
+    let layers = (layer > 0)
+        .then(|| link("previous layer".to_string(), layer - 1, &path).into_view())
+        .into_iter()
+        .chain(
+            (layer + 1 < tree.layers.len())
+                .then(|| link("next layer".to_string(), layer + 1, &path).into_view()),
+        )
+        .collect::<Vec<_>>();
+
+    let entries = tree.list(layer, &path);
+    let rows = entries
+        .into_iter()
+        .map(|entry| {
+            let deleted = entry.change == Some(Change::Deleted);
+            let name = match (entry.kind, deleted) {
+                (EntryKind::Dir, false) => {
+                    link(format!("{}/", entry.name), layer, &entry.path).into_view()
+                }
+                (EntryKind::Dir, true) => format!("{}/", entry.name).into_view(),
+                (EntryKind::Symlink | EntryKind::Hardlink, _) => {
+                    let arrow = if entry.kind == EntryKind::Symlink {
+                        " -> "
+                    } else {
+                        " => "
+                    };
+                    format!("{}{}{}", entry.name, arrow, entry.link.unwrap_or_default()).into_view()
+                }
+                _ => entry.name.into_view(),
+            };
+            let mut notes = vec![];
+            if let Some(later) = entry.overwritten_by {
+                notes.push(format!("hidden by layer {}", later));
+            }
+            if entry.changes_below > 0 {
+                notes.push(format!("{} changed below", entry.changes_below));
+            }
+            view! {
+                <tr class:layer-deleted=deleted>
+                    <td><code>{name}</code></td>
+                    <td>{change_badge(entry.change)}</td>
+                    <td>{(entry.size > 0).then(|| format_bytes(entry.size))}</td>
+                    <td>{(!deleted).then_some(entry.layer)}</td>
+                    <td><small>{notes.join(", ")}</small></td>
+                </tr>
+            }
+        })
+        .collect::<Vec<_>>();
+    let listing = if rows.is_empty() {
+        view! { <p><small>"Empty directory"</small></p> }.into_view()
+    } else {
+        view! {
+            <table>
+                <thead>
+                    <tr><th>"Name"</th><th></th><th>"Size"</th><th>"Layer"</th><th></th></tr>
+                </thead>
+                <tbody>{rows}</tbody>
+            </table>
+        }
+        .into_view()
+    };
+
+    view! {
+        <p>
+            <b>"Layer " {layer} ": "</b> <code>{current.created_by.clone()}</code>
+        </p>
+        <p>{crumbs} " " <small>{layers.into_iter().map(|l| view! { {l} " " }).collect::<Vec<_>>()}</small></p>
+        {listing}
+    }
+    .into_view()
+}
@@ -1,46 +1,222 @@
+use std::collections::HashMap;
+
 use leptos::*;
 
-use crate::{image::Image, model::SseTask};
+use crate::{
+    archive::Compression,
+    components::{
+        container::key_value_table,
+        containers::{filter_options, order_options},
+        shared::time::RelativeTimeComponent,
+    },
+    fields::parse_timestamp,
+    filter::{ImageFilter, ImageSort},
+    image::{Image, ImageHistory, ImageUsage, PLATFORMS},
+    inspect::ImageInspect,
+    model::SseTask,
+    util::format_bytes,
+};
 
+/// Images page, the pull and prune tasks, the filters and the images they are applied to
 #[component]
-pub fn ImagesComponent(images: Vec<Image>) -> impl IntoView {
-    let images = images
+pub fn ImagesComponent(
+    images: Vec<Image>,
+    usage: HashMap<String, ImageUsage>,
+    filter: ImageFilter,
+) -> impl IntoView {
+    let pull_url = format!(
+        "/components/shared/sse/{}/{}",
+        SseTask::ImagePull,
+        SseTask::ImagePull
+    );
+    let platforms = PLATFORMS.iter().map(|p| p.to_string()).collect();
+    let sorts = ImageSort::ALL
         .iter()
-        .map(move |image| {
+        .map(|sort| {
             view! {
-                <tr>
-                    <td>{image.repository.clone()}</td>
-                    <td>{image.tag.clone()}</td>
-                    <td>{image.created_since.clone()}</td>
-                    <td>{image.size.clone()}</td>
-                </tr>
+                <option value=sort.to_str() selected=*sort == filter.sort>{sort.to_str()}</option>
             }
         })
         .collect::<Vec<_>>();
 
-    let prune_url = format!(
-        "/components/shared/sse/{}/{}",
-        SseTask::PruneImages,
-        SseTask::PruneImages
-    );
+    let compressions = Compression::ALL
+        .iter()
+        .map(|c| view! { <option value=c.to_str()>{c.to_str()}</option> })
+        .collect::<Vec<_>>();
+
     view! {
+        <form
+            class="pull"
+            hx-get=pull_url
+            hx-swap="innerHTML"
+            hx-target="#image_task_container"
+            hx-indicator="#image_task_loader"
+        >
+            <input name="reference" placeholder="repository:tag or repository@digest" required />
+            <select name="platform" title="Platform">
+                {filter_options("Host platform", platforms, &None)}
+            </select>
+            <button type="submit" title="docker pull">"Pull"</button>
+        </form>
         <button
-            hx-get=prune_url
+            hx-get="/components/prune/images"
             hx-swap="innerHTML"
-            hx-target="next #image_task_container"
-            title="docker image prune --all --force"
-            hx-indicator="next .loader"
+            hx-target="#image_task_container"
+            title="list the images no container uses, to pick the ones to remove"
+            hx-indicator="#image_task_loader"
         >
             "Prune"
         </button>
-        <div class="loader htmx-indicator">"Loading..."</div>
+        <form id="image-export" class="pull" method="get" action="/images/export">
+            <select name="compression" title="Compression">{compressions}</select>
+            <button type="submit" title="docker save the checked images">"Export"</button>
+        </form>
+        <form id="image-import" class="pull" data-import="/images/import">
+            <input
+                type="file"
+                accept=".tar,.tar.gz,.tgz,.tar.zst,.tar.xz,.tar.bz2"
+                title="an archive of docker save, compressed or not"
+                required
+            />
+            <button type="submit" title="docker load">"Import"</button>
+            <progress hidden></progress>
+        </form>
+        <div id="image_task_loader" class="loader htmx-indicator">"Loading..."</div>
         <div id="image_task_container"></div>
+        <form
+            class="filters"
+            hx-get="/components/images/list"
+            hx-target="#images-list"
+            hx-trigger="change, submit"
+        >
+            <input
+                type="search"
+                name="q"
+                placeholder="Search"
+                value=filter.q.clone()
+                hx-get="/components/images/list"
+                hx-trigger="keyup changed delay:300ms, search"
+                hx-include="closest form"
+            />
+            <label title="untagged images">
+                <input type="checkbox" name="dangling" value="true" checked=filter.dangling />
+                "Dangling"
+            </label>
+            <select name="sort" title="Sort by">{sorts}</select>
+            <select name="order" title="Order">{order_options(filter.order)}</select>
+        </form>
+        <div id="images-list">
+            <ImageTableComponent images=images usage=usage />
+        </div>
+    }
+}
+
+/// Badges for images that take up space for nothing or for an old version
+fn image_flags(dangling: bool, usage: &ImageUsage) -> impl IntoView {
+    let mut flags = vec![];
+    if usage.containers.is_empty() {
+        flags.push(("unused", "no container uses the image".to_string()));
+    }
+    if usage.is_superseded() {
+        let references = usage.superseded_by.iter().cloned().collect::<Vec<_>>();
+        flags.push((
+            "superseded",
+            format!(
+                "{} was pulled again, recreate the containers to use the newer image",
+                references.join(", ")
+            ),
+        ));
+    } else if dangling {
+        flags.push(("dangling", "untagged".to_string()));
+    }
+
+    flags
+        .into_iter()
+        .map(|(flag, title)| {
+            let class = format!("image-flag image-flag-{}", flag);
+            view! { <small class=class title=title>{flag}</small> " " }
+        })
+        .collect::<Vec<_>>()
+}
+
+/// Links to the compose projects and the containers using an image
+fn used_by(usage: &ImageUsage) -> View {
+    if usage.containers.is_empty() {
+        return view! { <small>"No containers"</small> }.into_view();
+    }
+
+    let projects = usage
+        .projects()
+        .into_iter()
+        .map(|p| view! { <a href=format!("/?project={}", p)>{p.clone()}</a> " " })
+        .collect::<Vec<_>>();
+    let containers = usage
+        .containers
+        .iter()
+        .map(|c| {
+            let title = format!("{} ({})", c.state, c.image);
+            view! {
+                <a href=format!("/containers/{}", c.names) title=title>{c.names.clone()}</a>
+                " "
+            }
+        })
+        .collect::<Vec<_>>();
+
+    view! {
+        <b>{projects}</b>
+        <small>{containers}</small>
+    }
+    .into_view()
+}
+
+#[component]
+pub fn ImageTableComponent(
+    images: Vec<Image>,
+    /// by image id, see `Image::usage`
+    usage: HashMap<String, ImageUsage>,
+) -> impl IntoView {
+    if images.is_empty() {
+        return view! { <p class="notice">"No images match the filters."</p> }.into_view();
+    }
+
+    let images = images
+        .iter()
+        .map(move |image| {
+            let usage = usage.get(&image.id).cloned().unwrap_or_default();
+            // saved by reference the tag is kept in the archive
+            let export = image.reference().unwrap_or_else(|| image.id.clone());
+            view! {
+                <tr>
+                    <td>
+                        <input
+                            type="checkbox"
+                            name="image"
+                            value=export
+                            form="image-export"
+                            title="select for export"
+                        />
+                    </td>
+                    <td><a href=format!("/images/{}", image.id)>{image.repository.clone()}</a></td>
+                    <td>{image.tag.clone()}</td>
+                    <td>{image_flags(image.is_dangling(), &usage)}</td>
+                    <td>{used_by(&usage)}</td>
+                    <td><RelativeTimeComponent time=image.created_at /></td>
+                    <td>{format_bytes(image.size)}</td>
+                </tr>
+            }
+        })
+        .collect::<Vec<_>>();
+
+    view! {
         <table style="width:100%">
             <thead>
                 <tr>
+                    <th></th>
                     <th>Repository</th>
                     <th>Tag</th>
-                    <th>Created Since</th>
+                    <th></th>
+                    <th>Used by</th>
+                    <th>Created</th>
                     <th>Size</th>
                 </tr>
             </thead>
@@ -49,4 +225,188 @@ pub fn ImagesComponent(images: Vec<Image>) -> impl IntoView {
             </tbody>
         </table>
     }
+    .into_view()
+}
+
+/// What `docker load` said about an imported archive
+#[component]
+pub fn ImageImportComponent(result: Result<String, String>) -> impl IntoView {
+    match result {
+        Ok(output) => view! { <pre><code>{output}</code></pre> }.into_view(),
+        Err(e) => view! { <p class="notice">{e}</p> }.into_view(),
+    }
+}
+
+/// Detail page of an image: remove, tag and untag, the inspect data and the layers
+#[component]
+pub fn ImageDetailComponent(
+    inspect: ImageInspect,
+    /// pretty printed inspect json
+    raw: String,
+    history: Result<Vec<ImageHistory>, String>,
+    usage: ImageUsage,
+) -> impl IntoView {
+    let task_url = |task: SseTask| format!("/components/shared/sse/{}/{}", inspect.id, task);
+    let title = inspect
+        .repo_tags
+        .first()
+        .cloned()
+        .unwrap_or_else(|| inspect.id.clone());
+
+    let tags = inspect
+        .repo_tags
+        .iter()
+        .map(|tag| {
+            let url = format!("{}?tag={}", task_url(SseTask::ImageUntag), tag);
+            view! {
+                <tr>
+                    <td><code>{tag.clone()}</code></td>
+                    <td>
+                        <button
+                            hx-get=url
+                            hx-swap="innerHTML"
+                            hx-target="#image_task_container"
+                            hx-indicator="#image_task_loader"
+                            hx-confirm=format!("Untag {}?", tag)
+                            title=format!("docker image rm {}", tag)
+                            disabled=inspect.repo_tags.len() < 2
+                        >
+                            "Untag"
+                        </button>
+                    </td>
+                </tr>
+            }
+        })
+        .collect::<Vec<_>>();
+    let tags = if tags.is_empty() {
+        view! { <p><small>"Untagged"</small></p> }.into_view()
+    } else {
+        view! { <table><tbody>{tags}</tbody></table> }.into_view()
+    };
+
+    let created = match parse_timestamp(&inspect.created) {
+        Some(time) => view! { <RelativeTimeComponent time=time /> }.into_view(),
+        None => inspect.created.clone().into_view(),
+    };
+    let overview = vec![
+        ("id".to_string(), inspect.id.clone()),
+        ("digests".to_string(), inspect.repo_digests.join(", ")),
+        ("platform".to_string(), inspect.platform()),
+        ("size".to_string(), format_bytes(inspect.size)),
+    ];
+
+    let config = &inspect.config;
+    let join = |args: &Option<Vec<String>>| args.clone().unwrap_or_default().join(" ");
+    let mut run = vec![
+        ("entrypoint".to_string(), join(&config.entrypoint)),
+        ("cmd".to_string(), join(&config.cmd)),
+        ("working dir".to_string(), config.working_dir.clone()),
+        ("user".to_string(), config.user.clone()),
+    ];
+    run.retain(|(_, value)| !value.is_empty());
+
+    let mut ports = config
+        .exposed_ports
+        .clone()
+        .unwrap_or_default()
+        .into_keys()
+        .collect::<Vec<_>>();
+    ports.sort();
+    let ports = ports
+        .into_iter()
+        .map(|port| (port, String::new()))
+        .collect::<Vec<_>>();
+
+    let mut labels = config
+        .labels
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .collect::<Vec<_>>();
+    labels.sort();
+
+    let history = match history {
+        Err(e) => view! { <p class="notice">{e}</p> }.into_view(),
+        Ok(layers) => {
+            let rows = layers
+                .into_iter()
+                .map(|layer| {
+                    view! {
+                        <tr>
+                            <td><RelativeTimeComponent time=layer.created_at /></td>
+                            <td><code>{layer.created_by}</code></td>
+                            <td>{format_bytes(layer.size)}</td>
+                            <td>{layer.comment}</td>
+                        </tr>
+                    }
+                })
+                .collect::<Vec<_>>();
+            view! {
+                <table>
+                    <thead><tr><th>"Created"</th><th>"Instruction"</th><th>"Size"</th><th>"Comment"</th></tr></thead>
+                    <tbody>{rows}</tbody>
+                </table>
+            }
+            .into_view()
+        }
+    };
+
+    view! {
+        <h3>{title} " " {image_flags(inspect.repo_tags.is_empty(), &usage)}</h3>
+        <div class="actions">
+            <span class="remove">
+                <button
+                    hx-get=task_url(SseTask::ImageRemove)
+                    hx-swap="innerHTML"
+                    hx-target="#image_task_container"
+                    hx-indicator="#image_task_loader"
+                    hx-confirm="Remove the image?"
+                    hx-include="closest .remove"
+                    title="docker image rm"
+                >
+                    "Remove"
+                </button>
+                <label title="remove the image even though containers use it">
+                    <input type="checkbox" name="force" value="true" />
+                    "force"
+                </label>
+            </span>
+            <form
+                class="tag"
+                hx-get=task_url(SseTask::ImageTag)
+                hx-swap="innerHTML"
+                hx-target="#image_task_container"
+                hx-indicator="#image_task_loader"
+            >
+                <input name="tag" placeholder="repository:tag" required />
+                <button type="submit" title="docker image tag">"Tag"</button>
+            </form>
+        </div>
+        <div id="image_task_loader" class="loader htmx-indicator">"Loading..."</div>
+        <div id="image_task_container"></div>
+        <div class="container-detail">
+            <h4>"Overview"</h4>
+            <p><b>"created: "</b> {created}</p>
+            {key_value_table(overview, "")}
+            <h4>"Tags"</h4>
+            {tags}
+            <h4>"Run"</h4>
+            {key_value_table(run, "No entrypoint or command")}
+            <h4>"Exposed ports"</h4>
+            {key_value_table(ports, "No exposed ports")}
+            <h4>"Environment"</h4>
+            {key_value_table(config.env(), "No environment variables")}
+            <h4>"Labels"</h4>
+            {key_value_table(labels, "No labels")}
+            <h4>"Used by"</h4>
+            <p>{used_by(&usage)}</p>
+            <h4>"History"</h4>
+            {history}
+            <p><a href=format!("/images/{}/layers", inspect.id)>"Explore the layers and files"</a></p>
+            <details>
+                <summary>"Raw JSON"</summary>
+                <pre>{raw}</pre>
+            </details>
+        </div>
+    }
 }
@@ -4,6 +4,18 @@ use crate::{components::app::AppComponent, model::AppPage};
 
 #[component]
 pub fn IndexComponent(app_page: AppPage) -> impl IntoView {
+    let terminal = matches!(app_page, AppPage::Terminal(_)).then(|| {
+        view! {
+            <link rel="stylesheet" href="https://unpkg.com/@xterm/xterm@5.5.0/css/xterm.css"/>
+            <script src="https://unpkg.com/@xterm/xterm@5.5.0/lib/xterm.js"></script>
+            <script src="https://unpkg.com/@xterm/addon-fit@0.10.0/lib/addon-fit.js"></script>
+            <script src="/terminal.js"></script>
+        }
+    });
+
+    let import = matches!(app_page, AppPage::Images(_))
+        .then(|| view! { <script src="/import.js"></script> });
+
     view! {
         <html>
             <head>
@@ -12,6 +24,8 @@ pub fn IndexComponent(app_page: AppPage) -> impl IntoView {
                 <link rel="stylesheet" href="/index.css"/>
                 <script src="https://unpkg.com/htmx.org@1.9.10"></script>
                 <script src="https://unpkg.com/htmx.org/dist/ext/sse.js"></script>
+                {terminal}
+                {import}
             </head>
             <body>
                 <AppComponent app_page=app_page/>
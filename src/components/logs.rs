@@ -0,0 +1,183 @@
+use leptos::*;
+
+use crate::logs::{service_color, LogLevel, LogLine, LogOptions, LogSource, LogStream};
+
+/// Log options form, the viewer is loaded below it on submit
+#[component]
+pub fn LogsComponent(source: LogSource) -> impl IntoView {
+    let viewer_url = format!("/components{}/viewer", source.path());
+    let download = format!(
+        "window.location = '{}/download?' + new URLSearchParams(new FormData(this.form))",
+        source.path()
+    );
+
+    let levels = LogLevel::ALL
+        .iter()
+        .map(|level| view! { <option value=level.to_str()>{level.to_str()}</option> })
+        .collect::<Vec<_>>();
+
+    view! {
+        <h3>
+            "Logs "
+            <small>{source.kind().to_string()} " " {source.name().to_string()}</small>
+        </h3>
+        <form
+            class="log-options"
+            hx-get=viewer_url
+            hx-target="#log-viewer"
+            hx-swap="innerHTML"
+            hx-trigger="submit, load"
+        >
+            <label>
+                "Tail"
+                <input type="number" name="tail" min="0" value=LogOptions::DEFAULT_TAIL />
+            </label>
+            <label>
+                "Since"
+                <input type="text" name="since" placeholder="10m or 2024-01-02T15:04" />
+            </label>
+            <label>
+                "Until"
+                <input type="text" name="until" placeholder="10m or 2024-01-02T15:04" />
+            </label>
+            <label>
+                "Stream"
+                <select name="stream">
+                    <option value="">"stdout + stderr"</option>
+                    <option value="stdout">"stdout"</option>
+                    <option value="stderr">"stderr"</option>
+                </select>
+            </label>
+            <label>
+                "Filter"
+                <input type="text" name="filter" placeholder="regex" />
+            </label>
+            <label>
+                <input type="checkbox" name="timestamps" />
+                " Timestamps"
+            </label>
+            <label title="json fields shown as columns">
+                "Columns"
+                <input type="text" name="columns" value=LogOptions::DEFAULT_COLUMNS />
+            </label>
+            <label title="only json lines with at least this level">
+                "Level"
+                <select name="level">
+                    <option value="">"any"</option>
+                    {levels}
+                </select>
+            </label>
+            <label title="only json lines with these field values">
+                "Fields"
+                <input type="text" name="fields" placeholder="user=bob, status=500" />
+            </label>
+            <div class="actions">
+                <button type="submit">"Show"</button>
+                <button type="button" hx-on:click=download title="download the selected range">
+                    "Download"
+                </button>
+            </div>
+        </form>
+        <div id="log-viewer"></div>
+    }
+}
+
+/// Follows the logs over sse, see `LogLineComponent` for each line
+#[component]
+pub fn LogViewerComponent(
+    source: LogSource,
+    /// raw query string with the `LogOptions`
+    query: Option<String>,
+) -> impl IntoView {
+    let sse_connect = format!("{}/stream?{}", source.path(), query.unwrap_or_default());
+
+    view! {
+        <div class="actions">
+            <button
+                type="button"
+                hx-on:click="let o = document.getElementById('log-output'); o.dataset.paused = o.dataset.paused ? '' : '1'; this.innerText = o.dataset.paused ? 'Resume scroll' : 'Pause scroll';"
+            >
+                "Pause scroll"
+            </button>
+            <button type="button" hx-on:click="document.getElementById('log-output').innerHTML = ''">
+                "Clear"
+            </button>
+        </div>
+        <div
+            id="log-output"
+            class="log-output"
+            hx-ext="sse"
+            sse-connect=sse_connect
+            sse-swap="log"
+            hx-swap="beforeend"
+            hx-on:htmx:after-settle="while (this.childElementCount > 5000) this.firstElementChild.remove(); if (!this.dataset.paused) this.scrollTo(0, this.scrollHeight);"
+        ></div>
+    }
+}
+
+/// A log line, json lines are shown as the selected columns followed by the other fields
+#[component]
+pub fn LogLineComponent(line: LogLine, columns: Vec<String>) -> impl IntoView {
+    let mut class = vec!["log-line"];
+    if line.stream == LogStream::Stderr {
+        class.push("log-stderr");
+    }
+    if line.fields.is_some() {
+        class.push("log-json");
+    }
+    let level = line.level();
+    if level.is_some_and(|level| level >= LogLevel::Error) {
+        class.push("log-error");
+    }
+    let class = class.join(" ");
+
+    let text = if line.fields.is_some() {
+        let (shown, rest) = line.split_columns(&columns);
+
+        let shown = shown
+            .into_iter()
+            .map(|(column, value)| {
+                let class = match (column.as_str(), level) {
+                    ("level", Some(level)) => format!("log-field log-level-{}", level.to_str()),
+                    _ => "log-field".to_string(),
+                };
+                view! { <span class=class title=column>{value}</span> }
+            })
+            .collect::<Vec<_>>();
+
+        let rest = rest
+            .into_iter()
+            .map(|(key, value)| view! { <span class="log-extra">{key} "=" {value}</span> })
+            .collect::<Vec<_>>();
+
+        view! { {shown} {rest} }.into_view()
+    } else {
+        view! { <span>{line.text}</span> }.into_view()
+    };
+
+    let timestamp = line
+        .timestamp
+        .map(|timestamp| view! { <span class="log-timestamp">{timestamp}</span> });
+
+    let container = line.container;
+    let service = line.service.map(|service| {
+        let style = format!("color:{}", service_color(&service));
+        view! { <span class="log-service" style=style title=container>{service}</span> }
+    });
+
+    view! {
+        <div class=class>
+            {timestamp}
+            {service}
+            {text}
+        </div>
+    }
+}
+
+/// Shown in place of the viewer when the options are invalid
+#[component]
+pub fn LogErrorComponent(error: String) -> impl IntoView {
+    view! {
+        <p class="notice">{error}</p>
+    }
+}
@@ -0,0 +1,219 @@
+use chrono::{Local, TimeZone};
+use leptos::*;
+
+use crate::{
+    logs::LogSource,
+    stats::StatsSample,
+    store::{unix_secs, Annotation, MetricsRange},
+    util::format_bytes,
+};
+
+const WIDTH: f64 = 600.0;
+const HEIGHT: f64 = 120.0;
+
+fn format_time(secs: i64, range: MetricsRange) -> String {
+    let format = match range {
+        MetricsRange::Hour | MetricsRange::Day => "%H:%M",
+        MetricsRange::Week | MetricsRange::Month => "%b %d %H:%M",
+    };
+    Local
+        .timestamp_opt(secs, 0)
+        .single()
+        .map(|time| time.format(format).to_string())
+        .unwrap_or_default()
+}
+
+/// Range buttons, the charts are loaded below
+#[component]
+pub fn MetricsComponent(source: LogSource) -> impl IntoView {
+    let charts_url = format!(
+        "/components/metrics/{}/{}/charts",
+        source.kind(),
+        source.name()
+    );
+
+    let buttons = MetricsRange::ALL
+        .iter()
+        .map(|range| {
+            view! {
+                <button
+                    hx-get=format!("{}?range={}", charts_url, range.to_str())
+                    hx-target="#metrics-charts"
+                    hx-indicator="next .loader"
+                >
+                    {range.to_str()}
+                </button>
+            }
+        })
+        .collect::<Vec<_>>();
+
+    view! {
+        <h3>
+            "Metrics "
+            <small>{source.kind().to_string()} " " {source.name().to_string()}</small>
+        </h3>
+        <div class="actions">{buttons}</div>
+        <div class="loader htmx-indicator">"Loading..."</div>
+        <div
+            id="metrics-charts"
+            hx-get=format!("{}?range={}", charts_url, MetricsRange::default().to_str())
+            hx-trigger="load"
+        ></div>
+    }
+}
+
+/// Cpu, memory and network charts of the range
+#[component]
+pub fn MetricsChartsComponent(
+    range: MetricsRange,
+    samples: Vec<StatsSample>,
+    annotations: Vec<Annotation>,
+    /// unix seconds, the right edge of the charts
+    now: i64,
+) -> impl IntoView {
+    if samples.is_empty() {
+        return view! {
+            <p class="notice">"No samples in the last " {range.to_str()} " yet."</p>
+        }
+        .into_view();
+    }
+
+    let start = now - range.duration().as_secs() as i64;
+    let point = |sample: &StatsSample, value: f64| (unix_secs(sample.time), value);
+
+    let cpu = samples
+        .iter()
+        .map(|s| point(s, s.cpu_percent))
+        .collect::<Vec<_>>();
+    let mem = samples
+        .iter()
+        .map(|s| point(s, s.mem_usage as f64))
+        .collect::<Vec<_>>();
+    let net = samples
+        .iter()
+        .zip(samples.iter().skip(1))
+        .map(|(a, b)| {
+            let secs = (unix_secs(b.time) - unix_secs(a.time)).max(1) as f64;
+            let bytes = (b.net_rx + b.net_tx).saturating_sub(a.net_rx + a.net_tx);
+            point(b, bytes as f64 / secs)
+        })
+        .collect::<Vec<_>>();
+
+    let mem_limit = samples.iter().map(|s| s.mem_limit).max().unwrap_or(0);
+    let mem_title = if mem_limit > 0 {
+        format!("Memory (limit {})", format_bytes(mem_limit))
+    } else {
+        "Memory".to_string()
+    };
+
+    view! {
+        <ChartComponent
+            title="CPU".to_string()
+            points=cpu
+            format=|value| format!("{:.1}%", value)
+            range=range
+            start=start
+            end=now
+            annotations=annotations.clone()
+        />
+        <ChartComponent
+            title=mem_title
+            points=mem
+            format=|value| format_bytes(value as u64)
+            range=range
+            start=start
+            end=now
+            annotations=annotations.clone()
+        />
+        <ChartComponent
+            title="Network".to_string()
+            points=net
+            format=|value| format!("{}/s", format_bytes(value as u64))
+            range=range
+            start=start
+            end=now
+            annotations=annotations
+        />
+    }
+    .into_view()
+}
+
+/// A line chart over `start..end` with a marker per annotation. The line
+/// is broken where samples are missing, e.g. while the container was stopped.
+#[component]
+pub fn ChartComponent(
+    title: String,
+    /// unix seconds and value, oldest first
+    points: Vec<(i64, f64)>,
+    format: fn(f64) -> String,
+    range: MetricsRange,
+    start: i64,
+    end: i64,
+    annotations: Vec<Annotation>,
+) -> impl IntoView {
+    let max = points
+        .iter()
+        .map(|(_, value)| *value)
+        .fold(0.0, f64::max)
+        .max(f64::EPSILON);
+    let span = (end - start).max(1) as f64;
+    let x = move |time: i64| (time - start) as f64 * WIDTH / span;
+    let y = |value: f64| HEIGHT - value / max * (HEIGHT - 2.0);
+
+    // a gap of more than a few sample intervals means there is no data
+    let gap = points
+        .iter()
+        .zip(points.iter().skip(1))
+        .map(|(a, b)| b.0 - a.0)
+        .min()
+        .unwrap_or(0)
+        * 3;
+
+    let mut lines: Vec<Vec<String>> = vec![];
+    let mut previous: Option<i64> = None;
+    for (time, value) in points.iter() {
+        if previous.is_none_or(|previous| time - previous > gap) {
+            lines.push(vec![]);
+        }
+        if let Some(line) = lines.last_mut() {
+            line.push(format!("{:.1},{:.1}", x(*time), y(*value)));
+        }
+        previous = Some(*time);
+    }
+
+    let lines = lines
+        .into_iter()
+        .map(|points| view! { <polyline points=points.join(" ") /> })
+        .collect::<Vec<_>>();
+
+    let markers = annotations
+        .into_iter()
+        .map(|a| {
+            let x = format!("{:.1}", x(a.time));
+            let title = format!("{} {} at {}", a.task, a.name, format_time(a.time, range));
+            view! {
+                <line class="annotation" x1=x.clone() x2=x y1="0" y2=HEIGHT>
+                    <title>{title}</title>
+                </line>
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let latest = points.last().map(|(_, value)| format(*value));
+
+    view! {
+        <figure class="chart">
+            <figcaption>
+                {title} " " <small>{latest.map(|latest| format!("now {}, ", latest))} "max " {format(max)}</small>
+            </figcaption>
+            <svg viewBox=format!("0 0 {} {}", WIDTH, HEIGHT) preserveAspectRatio="none">
+                {lines}
+                {markers}
+            </svg>
+            <div class="chart-axis">
+                <small>{format_time(start, range)}</small>
+                <small>{format_time(end, range)}</small>
+            </div>
+        </figure>
+    }
+}
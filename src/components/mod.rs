@@ -1,5 +1,16 @@
 pub mod app;
 pub mod container;
+pub mod containers;
+pub mod disk;
+pub mod explorer;
 pub mod images;
 pub mod index;
+pub mod logs;
+pub mod metrics;
+pub mod project;
+pub mod prune;
+pub mod retention;
 pub mod shared;
+pub mod stats;
+pub mod terminal;
+pub mod volumes;
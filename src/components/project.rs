@@ -0,0 +1,207 @@
+use leptos::*;
+
+use crate::{
+    components::{container::ContainerComponent, shared::summary::SummaryComponent},
+    compose::ComposeFile,
+    config::Config,
+    container::Container,
+    logs::LogSource,
+    model::SseTask,
+    project::{Project, Service, UpdateOptions},
+    util::dom_id,
+};
+
+#[component]
+pub fn ProjectComponent(p: Project, update: UpdateOptions) -> impl IntoView {
+    let pull_url = format!("/components/shared/sse/{}/{}", p.name, SseTask::Pull);
+    let update_url = format!("/components/shared/sse/{}/{}", p.name, SseTask::Update);
+    let config_url = format!("/components/projects/{}/config", p.name);
+    let logs_url = LogSource::Project(p.name.clone()).path();
+    let metrics_url = format!("/metrics/project/{}", p.name);
+    let summary = p.summary();
+    let (services, _) = create_signal::<Vec<Service>>(p.services);
+    let project = p.name.clone();
+
+    view! {
+        <section class="project">
+            <h3>
+                {p.name.clone()}
+                " "
+                <SummaryComponent id=dom_id(&["project-summary", &p.name]) summary=summary />
+            </h3>
+            <div sse-swap=format!("stats-project-{}", p.name)></div>
+            <div style="display:flex;gap:0.5rem">
+                <button
+                    hx-get=pull_url
+                    hx-swap="innerHTML"
+                    hx-target="next #project_task_container"
+                    title="docker compose pull"
+                    hx-indicator="next .loader"
+                >
+                    "Pull"
+                </button>
+                <button
+                    hx-get=update_url
+                    hx-swap="innerHTML"
+                    hx-target="next #project_task_container"
+                    hx-indicator="next .loader"
+                    title=update.description()
+                >
+                    "Update"
+                </button>
+                <button
+                    hx-get=config_url
+                    hx-swap="innerHTML"
+                    hx-target="next #project_task_container"
+                    hx-indicator="next .loader"
+                >
+                    "View Config"
+                </button>
+                <a class="button" href=logs_url>"Logs"</a>
+                <a class="button" href=metrics_url>"Metrics"</a>
+            </div>
+            <div class="loader htmx-indicator">"Loading..."</div>
+            <div id="project_task_container"></div>
+            <div id=dom_id(&["project-services", &p.name])>
+                <For
+                    each=move || services.get()
+                    key=|s| s.name.clone()
+                    children=move |s: Service| {
+                        view! {
+                            <ServiceComponent project=project.clone() s=s />
+                        }
+                    }
+                />
+            </div>
+        </section>
+    }
+}
+
+#[component]
+pub fn ServiceComponent(project: String, s: Service) -> impl IntoView {
+    let summary = s.summary();
+
+    let buttons = [
+        (SseTask::ServicePull, "Pull", "docker compose pull"),
+        (
+            SseTask::ServiceRecreate,
+            "Recreate",
+            "docker compose up -d --no-deps",
+        ),
+        (SseTask::ServiceRestart, "Restart", "docker compose restart"),
+        (SseTask::ServiceStop, "Stop", "docker compose stop"),
+        (SseTask::ServiceStart, "Start", "docker compose start"),
+        (
+            SseTask::ServiceLogs,
+            "Logs",
+            "docker compose logs --tail 200",
+        ),
+    ]
+    .into_iter()
+    .map(|(task, label, title)| {
+        let url = format!(
+            "/components/shared/sse/{}/{}?service={}",
+            project, task, s.name
+        );
+        view! {
+            <button
+                hx-get=url
+                hx-swap="innerHTML"
+                hx-target="next #service_task_container"
+                hx-indicator="next .loader"
+                title=format!("{} {}", title, s.name)
+            >
+                {label}
+            </button>
+        }
+    })
+    .collect::<Vec<_>>();
+
+    view! {
+        <div class="service">
+            <div>
+                <b>{s.name.clone()}</b>
+                " "
+                <SummaryComponent id=dom_id(&["service-summary", &project, &s.name]) summary=summary />
+            </div>
+            <div class="actions">
+                {buttons}
+            </div>
+            <div class="loader htmx-indicator">"Loading..."</div>
+            <div id="service_task_container"></div>
+            <ServiceContainersComponent project=project.clone() service=s.name containers=s.containers />
+        </div>
+    }
+}
+
+/// A service's containers, swapped in as a whole by the live updates when
+/// containers are added or removed
+#[component]
+pub fn ServiceContainersComponent(
+    project: String,
+    service: String,
+    containers: Vec<Container>,
+    #[prop(optional)] oob: bool,
+) -> impl IntoView {
+    let (containers, _) = create_signal(containers);
+
+    view! {
+        <div id=dom_id(&["service-containers", &project, &service]) hx-swap-oob=oob.then_some("true")>
+            <For
+                each=move || containers.get()
+                key=|c| c.id.clone()
+                children=move |c| {
+                    view! {
+                        <ContainerComponent c=c />
+                    }
+                }
+            />
+        </div>
+    }
+}
+
+#[component]
+pub fn ProjectListComponent(projects: Vec<Project>, config: Config) -> impl IntoView {
+    let (projects, _) = create_signal::<Vec<Project>>(projects);
+
+    view! {
+        <For
+            each=move || projects.get()
+            key=|p| p.name.clone()
+            children=move |p: Project| {
+                let update = p.update_options(&config.project(&p.name));
+                view! {
+                    <ProjectComponent p=p update=update />
+                }
+            }
+        />
+    }
+}
+
+/// The project's compose files, one tab per file
+#[component]
+pub fn ComposeConfigComponent(name: String, files: Vec<ComposeFile>) -> impl IntoView {
+    let tabs = files
+        .into_iter()
+        .enumerate()
+        .map(|(i, file)| {
+            let id = format!("config-{}-{}", name, i);
+            let file_name = file
+                .path
+                .rsplit_once('/')
+                .map(|(_, file_name)| file_name.to_string())
+                .unwrap_or(file.path.clone());
+            view! {
+                <input type="radio" name=format!("config-{}", name) id=id.clone() checked=i == 0 />
+                <label for=id title=file.path>{file_name}</label>
+                <pre style="max-height:30rem;overflow:auto;"><code>{file.contents}</code></pre>
+            }
+        })
+        .collect::<Vec<_>>();
+
+    view! {
+        <div class="tabs">
+            {tabs}
+        </div>
+    }
+}
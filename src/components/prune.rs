@@ -0,0 +1,185 @@
+use leptos::*;
+
+use crate::{
+    components::shared::time::RelativeTimeComponent,
+    model::SseTask,
+    prune::{PruneItem, PruneKind},
+    util::format_bytes,
+};
+
+fn task_url(task: SseTask) -> String {
+    format!("/components/shared/sse/{}/{}", task, task)
+}
+
+/// Prune page, a section per resource and a system prune of several of them.
+/// The system prune removes the items still checked in the sections.
+#[component]
+pub fn PruneComponent(previews: Vec<(PruneKind, Result<Vec<PruneItem>, String>)>) -> impl IntoView {
+    let kinds = PruneKind::ALL
+        .iter()
+        .map(|kind| {
+            view! {
+                <label>
+                    <input
+                        type="checkbox"
+                        name="kind"
+                        value=kind.to_str()
+                        checked=*kind != PruneKind::Volumes
+                    />
+                    {kind.title()}
+                </label>
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let sections = previews
+        .into_iter()
+        .map(|(kind, items)| {
+            view! {
+                <section>
+                    <h3>{kind.title()}</h3>
+                    <PruneSectionComponent kind=kind items=items />
+                </section>
+            }
+        })
+        .collect::<Vec<_>>();
+
+    view! {
+        <section>
+            <h3>"System prune"</h3>
+            <form
+                class="filters"
+                hx-post=task_url(SseTask::PruneSystem)
+                hx-swap="innerHTML"
+                hx-target="#prune_system_task_container"
+                hx-indicator="#prune_system_task_loader"
+                hx-include="#prune-sections tbody input"
+                hx-confirm="Prune the items checked below?"
+            >
+                {kinds}
+                <label title="volumes are only removed when this is checked">
+                    <input type="checkbox" name="confirm" value="true" />
+                    <b>"Delete the data of the checked volumes"</b>
+                </label>
+                <button type="submit" title="prune the checked resources one after the other">
+                    "System prune"
+                </button>
+            </form>
+            <div id="prune_system_task_loader" class="loader htmx-indicator">"Loading..."</div>
+            <div id="prune_system_task_container"></div>
+        </section>
+        <div id="prune-sections">{sections}</div>
+    }
+}
+
+/// What a prune of one resource would remove, nothing is removed until the
+/// selection is confirmed. Unchecked items are kept.
+#[component]
+pub fn PruneSectionComponent(
+    kind: PruneKind,
+    items: Result<Vec<PruneItem>, String>,
+) -> impl IntoView {
+    let id = format!("prune-{}", kind.to_str());
+    let items = match items {
+        Ok(items) => items,
+        Err(e) => return view! { <div id=id><p class="notice">{e}</p></div> }.into_view(),
+    };
+    if items.is_empty() {
+        return view! { <div id=id><p><small>"Nothing to prune."</small></p></div> }.into_view();
+    }
+
+    let total = items.iter().filter_map(|item| item.size).sum::<u64>();
+    let summary = match (items.len(), kind) {
+        (_, PruneKind::Networks) => format!("{} to prune", items.len()),
+        (n, _) => format!("{} to prune, {} in total", n, format_bytes(total)),
+    };
+    let note = match kind {
+        PruneKind::Images => "Layers shared with the images that are kept stay on disk.",
+        PruneKind::BuildCache => "Only the checked records are removed, records in use are kept.",
+        _ => "",
+    };
+
+    let rows = items
+        .into_iter()
+        .map(|item| {
+            let checkbox = view! {
+                <input type="checkbox" name=kind.param() value=item.id.clone() checked />
+            };
+            let name = match item.href {
+                Some(href) => view! { <a href=href>{item.name}</a> }.into_view(),
+                None => view! { <code>{item.name}</code> }.into_view(),
+            };
+            let last_used_by = (!item.containers.is_empty()).then(|| {
+                let containers = item
+                    .containers
+                    .iter()
+                    .map(|c| {
+                        let detail = match c.project() {
+                            Some(project) => format!(" ({}, {}) ", project, c.state),
+                            None => format!(" ({}) ", c.state),
+                        };
+                        view! {
+                            <a href=format!("/containers/{}", c.names)>{c.names.clone()}</a>
+                            <small>{detail}</small>
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                view! {
+                    <small title="containers of the same repository, the image may be their previous version">
+                        "last used by "
+                    </small>
+                    {containers}
+                }
+            });
+
+            view! {
+                <tr>
+                    <td>{checkbox}</td>
+                    <td>{name}</td>
+                    <td><small>{item.detail}</small> {last_used_by}</td>
+                    <td>{item.created.map(|time| view! { <RelativeTimeComponent time=time /> })}</td>
+                    <td>{item.size.map(format_bytes)}</td>
+                </tr>
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let confirm = (kind == PruneKind::Volumes).then(|| {
+        view! {
+            <label>
+                <input type="checkbox" name="confirm" value="true" required />
+                <b>"Delete the data of the checked volumes"</b>
+            </label>
+        }
+    });
+
+    view! {
+        <div id=id.clone()>
+            <form
+                hx-post=task_url(kind.task())
+                hx-swap="innerHTML"
+                hx-target=format!("#{}", id)
+                hx-indicator="next .loader"
+                hx-confirm=format!("Prune the checked {}?", kind.title().to_lowercase())
+            >
+                <p>{summary} ". " {note}</p>
+                <table style="width:100%">
+                    <thead>
+                        <tr>
+                            <th></th>
+                            <th>"Name"</th>
+                            <th></th>
+                            <th>"Created"</th>
+                            <th>"Size"</th>
+                        </tr>
+                    </thead>
+                    <tbody>{rows}</tbody>
+                </table>
+                {confirm}
+                <button type="submit">"Prune"</button>
+            </form>
+            <div class="loader htmx-indicator">"Loading..."</div>
+        </div>
+    }
+    .into_view()
+}
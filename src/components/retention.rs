@@ -0,0 +1,186 @@
+use chrono::DateTime;
+use leptos::*;
+
+use crate::{
+    components::shared::time::RelativeTimeComponent, config::RetentionConfig, model::SseTask,
+    retention::RetentionItem, store::RetentionRecord, util::format_bytes,
+};
+
+/// The configured rules in words
+fn rules(config: &RetentionConfig) -> Vec<String> {
+    let mut rules = vec![];
+    if let Some(keep) = config.keep_images_per_repository {
+        rules.push(format!(
+            "keep the {} newest tagged images of each repository",
+            keep
+        ));
+    }
+    if let Some(days) = config.stopped_containers_days {
+        rules.push(format!(
+            "remove stopped containers {} days after they exited",
+            days
+        ));
+    }
+    if let Some(hours) = config.dangling_images_hours {
+        rules.push(format!(
+            "remove untagged images {} hours after they were created",
+            hours
+        ));
+    }
+    if config.keep_project_images && config.has_rules() {
+        rules.push(
+            "keep the stopped containers of compose projects and the images the projects last ran"
+                .to_string(),
+        );
+    }
+    rules
+}
+
+/// The retention policy, what its next run removes and what it removed
+#[component]
+pub fn RetentionComponent(
+    config: RetentionConfig,
+    items: Result<Vec<RetentionItem>, String>,
+    history: Result<Vec<RetentionRecord>, String>,
+) -> impl IntoView {
+    let schedule = if !config.has_rules() {
+        view! {
+            <p class="notice">
+                "No retention rules, set them under " <code>"[retention]"</code> " in the config file."
+            </p>
+        }
+    } else if config.enabled {
+        view! { <p>"Runs every " {config.interval_mins.max(1)} " minutes."</p> }
+    } else {
+        view! {
+            <p class="notice">
+                "Automatic cleanup is off, set " <code>"enabled = true"</code>
+                " under " <code>"[retention]"</code> " to run it every "
+                {config.interval_mins.max(1)} " minutes."
+            </p>
+        }
+    };
+    let rules = rules(&config)
+        .into_iter()
+        .map(|rule| view! { <li>{rule}</li> })
+        .collect::<Vec<_>>();
+
+    let next_run = match items {
+        Err(e) => view! { <p class="notice">{e}</p> }.into_view(),
+        Ok(items) if items.is_empty() => {
+            view! { <p><small>"Nothing to remove."</small></p> }.into_view()
+        }
+        Ok(items) => {
+            let total = items.iter().filter_map(|item| item.size).sum::<u64>();
+            let summary = format!(
+                "{} to remove, {} in total",
+                items.len(),
+                format_bytes(total)
+            );
+            let rows = items
+                .into_iter()
+                .map(|item| {
+                    let name = match item.href {
+                        Some(href) => view! { <a href=href>{item.name}</a> }.into_view(),
+                        None => view! { <code>{item.name}</code> }.into_view(),
+                    };
+                    view! {
+                        <tr>
+                            <td>{item.rule.to_str()}</td>
+                            <td>{name}</td>
+                            <td><small>{item.reason}</small></td>
+                            <td>{item.size.map(format_bytes)}</td>
+                        </tr>
+                    }
+                })
+                .collect::<Vec<_>>();
+            view! {
+                <p>{summary} ". Images shared by tags of other repositories only lose their tag."</p>
+                <table style="width:100%">
+                    <thead>
+                        <tr>
+                            <th>"Rule"</th>
+                            <th>"Name"</th>
+                            <th></th>
+                            <th>"Size"</th>
+                        </tr>
+                    </thead>
+                    <tbody>{rows}</tbody>
+                </table>
+            }
+            .into_view()
+        }
+    };
+
+    let history = match history {
+        Err(e) => view! { <p class="notice">{e}</p> }.into_view(),
+        Ok(history) if history.is_empty() => {
+            view! { <p><small>"Nothing removed yet."</small></p> }.into_view()
+        }
+        Ok(history) => {
+            let rows = history
+                .into_iter()
+                .map(|record| {
+                    let time = DateTime::from_timestamp(record.time, 0)
+                        .map(|time| view! { <RelativeTimeComponent time=time.fixed_offset() /> });
+                    let result = match record.error {
+                        Some(error) => view! { <span class="notice">{error}</span> }.into_view(),
+                        None => "removed".into_view(),
+                    };
+                    view! {
+                        <tr>
+                            <td>{time}</td>
+                            <td>{record.rule}</td>
+                            <td><code>{record.name}</code></td>
+                            <td>{record.size.map(format_bytes)}</td>
+                            <td><small>{result}</small></td>
+                        </tr>
+                    }
+                })
+                .collect::<Vec<_>>();
+            view! {
+                <table style="width:100%">
+                    <thead>
+                        <tr>
+                            <th>"Time"</th>
+                            <th>"Rule"</th>
+                            <th>"Name"</th>
+                            <th>"Size"</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>{rows}</tbody>
+                </table>
+            }
+            .into_view()
+        }
+    };
+
+    let task = SseTask::RetentionRun;
+    view! {
+        <section>
+            <h3>"Retention policy"</h3>
+            {schedule}
+            <ul>{rules}</ul>
+        </section>
+        <section>
+            <h3>"Next run"</h3>
+            {next_run}
+            <button
+                hx-get=format!("/components/shared/sse/{}/{}", task, task)
+                hx-swap="innerHTML"
+                hx-target="#retention_task_container"
+                hx-indicator="#retention_task_loader"
+                hx-confirm="Remove what the retention policy selects now?"
+            >
+                "Run now"
+            </button>
+            <div id="retention_task_loader" class="loader htmx-indicator">"Loading..."</div>
+            <div id="retention_task_container"></div>
+        </section>
+        <section>
+            <h3>"History"</h3>
+            {history}
+        </section>
+    }
+}
@@ -1 +1,3 @@
 pub mod sse;
+pub mod summary;
+pub mod time;
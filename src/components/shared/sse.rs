@@ -3,16 +3,26 @@ use leptos::*;
 use crate::model::SseTask;
 
 #[component]
-pub fn SseResultsComponent(name: String, task: SseTask) -> impl IntoView {
-    let sse_connect = format!("/components/shared/sse/connect/{}/{}", name.clone(), task);
+pub fn SseResultsComponent(
+    name: String,
+    task: SseTask,
+    /// name of the sse event to swap in, see `SseParams::event_name`
+    event: String,
+    /// raw query string forwarded to the connect handler
+    query: Option<String>,
+) -> impl IntoView {
+    let sse_connect = match query {
+        Some(query) => format!("/components/shared/sse/connect/{}/{}?{}", name, task, query),
+        None => format!("/components/shared/sse/connect/{}/{}", name, task),
+    };
     view! {
-        <pre id=name.clone() style="max-height:20rem;overflow:auto;"
+        <pre id=event.clone() style="max-height:20rem;overflow:auto;"
              hx-on:htmx:after-settle="this.scrollTo(0, this.scrollHeight);"
             >
             <code
                 hx-ext="sse"
                 sse-connect=sse_connect
-                sse-swap=name.clone()
+                sse-swap=event
                 hx-swap="beforeend"
             ></code>
         </pre>
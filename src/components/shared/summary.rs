@@ -0,0 +1,10 @@
+use leptos::*;
+
+/// Counts next to a project, service or section title, swapped in on its
+/// own by the live updates
+#[component]
+pub fn SummaryComponent(id: String, summary: String, #[prop(optional)] oob: bool) -> impl IntoView {
+    view! {
+        <small id=id hx-swap-oob=oob.then_some("true")>{summary}</small>
+    }
+}
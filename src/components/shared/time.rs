@@ -0,0 +1,14 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use leptos::*;
+
+use crate::fields::format_relative;
+
+/// e.g. "3 days ago", with the full date on hover
+#[component]
+pub fn RelativeTimeComponent(time: DateTime<FixedOffset>) -> impl IntoView {
+    view! {
+        <time datetime=time.to_rfc3339() title=time.format("%Y-%m-%d %H:%M:%S %:z").to_string()>
+            {format_relative(&time, Utc::now())}
+        </time>
+    }
+}
@@ -0,0 +1,89 @@
+use leptos::*;
+
+use crate::{
+    stats::{net_rates, History, HISTORY_LEN},
+    util::format_bytes,
+};
+
+/// A small svg line chart, scaled to `max` or the largest value
+#[component]
+pub fn SparklineComponent(values: Vec<f64>, max: Option<f64>) -> impl IntoView {
+    let max = max
+        .unwrap_or_else(|| values.iter().cloned().fold(0.0, f64::max))
+        .max(f64::EPSILON);
+
+    // right aligned so the sparklines move left as samples come in
+    let offset = HISTORY_LEN.saturating_sub(values.len());
+    let points = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let x = (offset + i) as f64 * 100.0 / (HISTORY_LEN - 1) as f64;
+            let y = 20.0 - (value / max).clamp(0.0, 1.0) * 19.0;
+            format!("{:.1},{:.1}", x, y)
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    view! {
+        <svg class="sparkline" viewBox="0 0 100 20" preserveAspectRatio="none">
+            <polyline points=points />
+        </svg>
+    }
+}
+
+/// Latest usage of a container or project, with cpu, memory and network sparklines
+#[component]
+pub fn StatsComponent(history: History) -> impl IntoView {
+    let latest = match history.back() {
+        Some(latest) => *latest,
+        None => return None,
+    };
+
+    let cpu = history.iter().map(|s| s.cpu_percent).collect::<Vec<_>>();
+    let mem = history
+        .iter()
+        .map(|s| s.mem_usage as f64)
+        .collect::<Vec<_>>();
+    let net = net_rates(&history);
+
+    let mem_text = if latest.mem_limit > 0 {
+        format!(
+            "{} / {}",
+            format_bytes(latest.mem_usage),
+            format_bytes(latest.mem_limit)
+        )
+    } else {
+        format_bytes(latest.mem_usage)
+    };
+    let mem_max = (latest.mem_limit > 0).then_some(latest.mem_limit as f64);
+    let net_rate = net
+        .last()
+        .map(|rate| format!("{}/s", format_bytes(*rate as u64)));
+
+    Some(view! {
+        <span class="stats">
+            <span title="cpu">
+                {format!("cpu {:.1}%", latest.cpu_percent)}
+                <SparklineComponent values=cpu max=None />
+            </span>
+            <span title="memory usage / limit">
+                {format!("mem {}", mem_text)}
+                <SparklineComponent values=mem max=mem_max />
+            </span>
+            <span title="network received / sent">
+                {format!("net {} / {}", format_bytes(latest.net_rx), format_bytes(latest.net_tx))}
+                {net_rate.map(|rate| view! { <small>" " {rate}</small> })}
+                <SparklineComponent values=net max=None />
+            </span>
+            <span title="block io read / written">
+                {format!(
+                    "block {} / {}",
+                    format_bytes(latest.block_read),
+                    format_bytes(latest.block_write),
+                )}
+            </span>
+            <span title="processes">{format!("pids {}", latest.pids)}</span>
+        </span>
+    })
+}
@@ -0,0 +1,57 @@
+use leptos::*;
+
+use crate::config::ExecConfig;
+
+/// Shell and user selection for an exec session, the terminal itself
+/// is driven by `/terminal.js` over the `/containers/:name/exec` websocket
+#[component]
+pub fn TerminalComponent(name: String, config: ExecConfig) -> impl IntoView {
+    if let Err(e) = config.check(&name) {
+        return view! {
+            <h3>"Terminal " <small>{name}</small></h3>
+            <p class="notice">{format!("{:#}", e)}</p>
+        }
+        .into_view();
+    }
+
+    let shells = config
+        .shells
+        .iter()
+        .map(|shell| view! { <option value=shell.clone()>{shell.clone()}</option> })
+        .collect::<Vec<_>>();
+
+    let users = config
+        .users
+        .iter()
+        .map(|user| view! { <option value=user.clone()>{user.clone()}</option> })
+        .collect::<Vec<_>>();
+
+    let limit = match config.max_session_secs {
+        secs if secs % 60 == 0 => format!("sessions end after {} minutes", secs / 60),
+        secs => format!("sessions end after {} seconds", secs),
+    };
+
+    view! {
+        <h3>"Terminal " <small>{name.clone()}</small></h3>
+        <form id="terminal-form" class="log-options" data-exec=format!("/containers/{}/exec", name)>
+            <label>
+                "Shell"
+                <select name="shell">{shells}</select>
+            </label>
+            <label>
+                "User"
+                <select name="user">
+                    <option value="">"container default"</option>
+                    {users}
+                </select>
+            </label>
+            <div class="actions">
+                <button type="submit">"Connect"</button>
+                <button type="button" id="terminal-disconnect">"Disconnect"</button>
+            </div>
+        </form>
+        <p><small>{limit}</small></p>
+        <div id="terminal" class="terminal"></div>
+    }
+    .into_view()
+}
@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+
+use leptos::*;
+
+use crate::{
+    components::{container::key_value_table, shared::time::RelativeTimeComponent},
+    container::Container,
+    fields::parse_timestamp,
+    inspect::VolumeInspect,
+    model::SseTask,
+    util::format_bytes,
+    volume::Volume,
+};
+
+fn labels_list(labels: &BTreeMap<String, String>) -> impl IntoView {
+    if labels.is_empty() {
+        return None;
+    }
+    let labels = labels
+        .iter()
+        .map(|(key, value)| view! { <li><code>{key.clone()} "=" {value.clone()}</code></li> })
+        .collect::<Vec<_>>();
+    Some(view! { <ul class="labels">{labels}</ul> })
+}
+
+fn container_links(containers: &[&Container]) -> impl IntoView {
+    containers
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            view! {
+                {(i > 0).then_some(", ")}
+                <a href=format!("/containers/{}", c.names)>{c.names.clone()}</a>
+            }
+        })
+        .collect::<Vec<_>>()
+}
+
+/// Volumes page, the create form and the volumes with the containers mounting them
+#[component]
+pub fn VolumesComponent(volumes: Vec<Volume>, containers: Vec<Container>) -> impl IntoView {
+    let create_url = format!(
+        "/components/shared/sse/{}/{}",
+        SseTask::VolumeCreate,
+        SseTask::VolumeCreate
+    );
+
+    let rows = volumes
+        .iter()
+        .map(|volume| {
+            let mounted_by = volume.containers(&containers);
+            let project = volume
+                .project()
+                .map(|project| view! { <a href=format!("/?project={}", project)>{project}</a> });
+            view! {
+                <tr>
+                    <td><a href=format!("/volumes/{}", volume.name)>{volume.name.clone()}</a></td>
+                    <td>{volume.driver.clone()}</td>
+                    <td><small><code>{volume.mountpoint.clone()}</code></small></td>
+                    <td>{volume.size.map(format_bytes)}</td>
+                    <td>{project}</td>
+                    <td>{labels_list(&volume.labels)}</td>
+                    <td>{container_links(&mounted_by)}</td>
+                </tr>
+            }
+        })
+        .collect::<Vec<_>>();
+    let table = if rows.is_empty() {
+        view! { <p><small>"No volumes"</small></p> }.into_view()
+    } else {
+        view! {
+            <table style="width:100%">
+                <thead>
+                    <tr>
+                        <th>"Name"</th>
+                        <th>"Driver"</th>
+                        <th>"Mountpoint"</th>
+                        <th>"Size"</th>
+                        <th>"Project"</th>
+                        <th>"Labels"</th>
+                        <th>"Containers"</th>
+                    </tr>
+                </thead>
+                <tbody>{rows}</tbody>
+            </table>
+        }
+        .into_view()
+    };
+
+    view! {
+        <form
+            class="pull"
+            hx-get=create_url
+            hx-swap="innerHTML"
+            hx-target="#volume_task_container"
+            hx-indicator="#volume_task_loader"
+        >
+            <input name="volume" placeholder="name" required />
+            <input name="driver" placeholder="driver, local when empty" />
+            <textarea name="labels" rows="1" placeholder="key=value labels, one per line"></textarea>
+            <button type="submit" title="docker volume create">"Create"</button>
+        </form>
+        <div id="volume_task_loader" class="loader htmx-indicator">"Loading..."</div>
+        <div id="volume_task_container"></div>
+        {table}
+    }
+}
+
+/// Detail page of a volume: remove, the inspect data and the containers mounting it
+#[component]
+pub fn VolumeDetailComponent(
+    inspect: VolumeInspect,
+    /// pretty printed inspect json
+    raw: String,
+    /// from `docker system df`, when docker lists the volume there
+    volume: Option<Volume>,
+    containers: Vec<Container>,
+) -> impl IntoView {
+    let created = match parse_timestamp(&inspect.created_at) {
+        Some(time) => view! { <RelativeTimeComponent time=time /> }.into_view(),
+        None => inspect.created_at.clone().into_view(),
+    };
+    let size = volume
+        .as_ref()
+        .and_then(|volume| volume.size)
+        .map(format_bytes)
+        .unwrap_or_else(|| "unknown".to_string());
+    let project = volume.as_ref().and_then(|volume| volume.project());
+    let overview = vec![
+        ("driver".to_string(), inspect.driver.clone()),
+        ("scope".to_string(), inspect.scope.clone()),
+        ("mountpoint".to_string(), inspect.mountpoint.clone()),
+        ("size".to_string(), size),
+        ("project".to_string(), project.unwrap_or_default()),
+    ];
+
+    let labels = inspect
+        .labels
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let options = inspect
+        .options
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let mounted_by = containers
+        .iter()
+        .filter(|c| c.mounts.contains(&inspect.name))
+        .collect::<Vec<_>>();
+    let in_use = !mounted_by.is_empty();
+    let used_by = if in_use {
+        container_links(&mounted_by).into_view()
+    } else {
+        view! { <small>"No container mounts the volume"</small> }.into_view()
+    };
+
+    view! {
+        <h3>{inspect.name.clone()}</h3>
+        <div class="actions">
+            <button
+                hx-get=format!("/components/shared/sse/{}/{}", inspect.name, SseTask::VolumeRemove)
+                hx-swap="innerHTML"
+                hx-target="#volume_task_container"
+                hx-indicator="#volume_task_loader"
+                hx-confirm="Remove the volume and the data in it?"
+                title=if in_use { "remove the containers mounting the volume first" } else { "docker volume rm" }
+                disabled=in_use
+            >
+                "Remove"
+            </button>
+        </div>
+        <div id="volume_task_loader" class="loader htmx-indicator">"Loading..."</div>
+        <div id="volume_task_container"></div>
+        <div class="container-detail">
+            <h4>"Overview"</h4>
+            <p><b>"created: "</b> {created}</p>
+            {key_value_table(overview, "")}
+            <h4>"Labels"</h4>
+            {key_value_table(labels, "No labels")}
+            <h4>"Driver options"</h4>
+            {key_value_table(options, "No driver options")}
+            <h4>"Used by"</h4>
+            <p>{used_by}</p>
+            <details>
+                <summary>"Raw JSON"</summary>
+                <pre>{raw}</pre>
+            </details>
+        </div>
+    }
+}
@@ -0,0 +1,232 @@
+use std::{collections::HashMap, process::Command};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    config::ProjectConfig,
+    container::{CONFIG_FILES_LABEL, PROJECT_LABEL, SERVICE_LABEL},
+};
+
+pub const WORKING_DIR_LABEL: &str = "com.docker.compose.project.working_dir";
+pub const ENVIRONMENT_FILE_LABEL: &str = "com.docker.compose.project.environment_file";
+
+/// Everything needed to run docker compose against an existing project
+/// the same way it was brought up: `-p`, every `-f`, `--env-file` and `--profile`.
+#[derive(Debug, Clone, Default)]
+pub struct ComposeContext {
+    pub project: String,
+    pub working_dir: String,
+    pub config_files: Vec<String>,
+    pub env_files: Vec<String>,
+    pub profiles: Vec<String>,
+}
+
+/// A compose file and its contents, for the View Config tabs
+#[derive(Debug, Clone)]
+pub struct ComposeFile {
+    pub path: String,
+    pub contents: String,
+}
+
+impl ComposeContext {
+    /// Build the context from the labels compose put on the project's containers
+    pub fn get(project: &str, config: &ProjectConfig) -> Result<ComposeContext> {
+        let labels = Self::get_labels(project)?;
+
+        let split = |key: &str| -> Vec<String> {
+            labels
+                .get(key)
+                .map(|val| {
+                    val.split(',')
+                        .map(|val| val.trim().to_string())
+                        .filter(|val| !val.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let config_files = split(CONFIG_FILES_LABEL);
+        let env_files = split(ENVIRONMENT_FILE_LABEL);
+
+        // older compose versions don't set the working dir label,
+        // fall back to the directory of the first config file
+        let working_dir = match labels.get(WORKING_DIR_LABEL) {
+            Some(dir) if !dir.is_empty() => dir.clone(),
+            _ => config_files
+                .first()
+                .and_then(|file| file.rsplit_once('/'))
+                .map(|(dir, _)| dir.to_string())
+                .context("no compose file found")?,
+        };
+
+        let mut context = ComposeContext {
+            project: project.to_string(),
+            working_dir,
+            config_files,
+            env_files,
+            profiles: vec![],
+        };
+
+        context.profiles = match &config.profiles {
+            Some(profiles) => profiles.clone(),
+            None => context.active_profiles().unwrap_or_else(|e| {
+                tracing::warn!("failed to detect compose profiles for {}: {}", project, e);
+                vec![]
+            }),
+        };
+
+        Ok(context)
+    }
+
+    /// Labels of the first container in the project. `docker inspect` is used
+    /// because `docker ps` joins labels with commas, which are also the
+    /// separator of multiple config files.
+    fn get_labels(project: &str) -> Result<HashMap<String, String>> {
+        let output = Command::new("docker")
+            .arg("ps")
+            .arg("--all")
+            .arg("--quiet")
+            .arg("--no-trunc")
+            .arg("--filter")
+            .arg(format!("label={}={}", PROJECT_LABEL, project))
+            .output()?;
+
+        let output = String::from_utf8(output.stdout)?;
+        let id = output
+            .lines()
+            .map(|val| val.trim())
+            .find(|val| !val.is_empty())
+            .with_context(|| format!("no containers found for project {}", project))?;
+
+        let output = Command::new("docker")
+            .arg("inspect")
+            .arg(id)
+            .arg("--format")
+            .arg("{{json .Config.Labels}}")
+            .output()?;
+
+        let output = String::from_utf8(output.stdout)?;
+
+        Ok(serde_json::from_str(output.trim())?)
+    }
+
+    /// Names of the services in the compose config, with the active profiles
+    pub fn services(&self) -> Result<Vec<String>> {
+        let output = Command::new("docker")
+            .arg("compose")
+            .args(self.args())
+            .arg("config")
+            .arg("--services")
+            .current_dir(&self.working_dir)
+            .output()?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "docker compose config: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8(output.stdout)?
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect())
+    }
+
+    /// Profiles of the services that currently have containers. Profiles aren't
+    /// stored in the labels, so they are matched up from the resolved compose config.
+    fn active_profiles(&self) -> Result<Vec<String>> {
+        let output = Command::new("docker")
+            .arg("compose")
+            .args(self.args_without_profiles())
+            .arg("--profile")
+            .arg("*")
+            .arg("config")
+            .arg("--format")
+            .arg("json")
+            .current_dir(&self.working_dir)
+            .output()?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "docker compose config: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let config: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+
+        let output = Command::new("docker")
+            .arg("ps")
+            .arg("--all")
+            .arg("--filter")
+            .arg(format!("label={}={}", PROJECT_LABEL, self.project))
+            .arg("--format")
+            .arg(format!("{{{{ .Label \"{}\" }}}}", SERVICE_LABEL))
+            .output()?;
+
+        let output = String::from_utf8(output.stdout)?;
+        let services = output.lines().map(|val| val.trim()).collect::<Vec<_>>();
+
+        let mut profiles = services
+            .iter()
+            .filter_map(|service| config["services"][service]["profiles"].as_array())
+            .flatten()
+            .filter_map(|val| val.as_str())
+            .map(|val| val.to_string())
+            .collect::<Vec<_>>();
+
+        profiles.sort();
+        profiles.dedup();
+
+        Ok(profiles)
+    }
+
+    fn args_without_profiles(&self) -> Vec<String> {
+        let mut args = vec!["--project-name".to_string(), self.project.clone()];
+
+        for file in self.config_files.iter() {
+            args.push("--file".into());
+            args.push(file.clone());
+        }
+
+        for file in self.env_files.iter() {
+            args.push("--env-file".into());
+            args.push(file.clone());
+        }
+
+        args
+    }
+
+    /// Global docker compose arguments, to go before the subcommand
+    pub fn args(&self) -> Vec<String> {
+        let mut args = self.args_without_profiles();
+
+        for profile in self.profiles.iter() {
+            args.push("--profile".into());
+            args.push(profile.clone());
+        }
+
+        args
+    }
+
+    /// Read every config file of the project
+    pub async fn read_config_files(&self) -> Vec<ComposeFile> {
+        let mut files = vec![];
+
+        for path in self.config_files.iter() {
+            let contents = match tokio::fs::read_to_string(path).await {
+                Ok(contents) => contents,
+                Err(e) => format!("failed to read {}: {}", path, e),
+            };
+
+            files.push(ComposeFile {
+                path: path.clone(),
+                contents,
+            });
+        }
+
+        files
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use crate::model::UpdateStrategy;
+
+/// mgdocker configuration, loaded from the `--config` toml file
+///
+/// ```toml
+/// [projects.my-project]
+/// update_strategy = "pull-up"
+/// remove_orphans = true
+/// force_recreate = false
+/// profiles = ["debug"]
+///
+/// [exec]
+/// enabled = true
+///
+/// [metrics]
+/// database = "/var/lib/mgdocker/metrics.db"
+/// retention_days = 30
+///
+/// [retention]
+/// enabled = true
+/// keep_images_per_repository = 3
+/// stopped_containers_days = 7
+/// dangling_images_hours = 24
+/// ```
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// compose project settings keyed by project name
+    pub projects: HashMap<String, ProjectConfig>,
+    pub exec: ExecConfig,
+    pub metrics: MetricsConfig,
+    pub retention: RetentionConfig,
+}
+
+/// Settings for a single compose project. These take precedence
+/// over the `mgdocker.*` labels set on the project's containers.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct ProjectConfig {
+    pub update_strategy: Option<UpdateStrategy>,
+    pub remove_orphans: Option<bool>,
+    pub force_recreate: Option<bool>,
+    /// compose profiles to activate, detected from the running services when not set
+    pub profiles: Option<Vec<String>>,
+}
+
+/// Who can open a terminal into which containers, see `exec::ExecSession`.
+/// Disabled unless turned on in the config file.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct ExecConfig {
+    pub enabled: bool,
+    /// container names a terminal can be opened in, all containers when not set
+    pub containers: Option<Vec<String>>,
+    /// shells to choose from, the first one is the default
+    pub shells: Vec<String>,
+    /// users to choose from besides the container's own user
+    pub users: Vec<String>,
+    /// sessions are closed after this many seconds
+    pub max_session_secs: u64,
+}
+
+impl Default for ExecConfig {
+    fn default() -> Self {
+        ExecConfig {
+            enabled: false,
+            containers: None,
+            shells: vec!["/bin/sh".into(), "/bin/bash".into()],
+            users: vec!["root".into()],
+            max_session_secs: 1800,
+        }
+    }
+}
+
+impl ExecConfig {
+    /// Reasons a terminal can't be opened in the container, if any
+    pub fn check(&self, container: &str) -> Result<()> {
+        if !self.enabled {
+            return Err(anyhow::anyhow!(
+                "terminals are disabled, set `enabled = true` under [exec] in the config file"
+            ));
+        }
+
+        match &self.containers {
+            Some(containers) if !containers.iter().any(|c| c == container) => Err(anyhow::anyhow!(
+                "terminals are not allowed in container {}",
+                container
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Where the stats history is stored and for how long, see `store::Store`
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// sqlite database, created when it doesn't exist. Without one the
+    /// history is kept in memory and lost on exit.
+    pub database: Option<PathBuf>,
+    /// days of hourly samples and task annotations to keep
+    pub retention_days: u64,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            database: None,
+            retention_days: 30,
+        }
+    }
+}
+
+/// Rules of the automatic cleanup, see `retention::plan`. Rules that are not
+/// set remove nothing.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// run the cleanup every `interval_mins`, otherwise it only runs from the retention page
+    pub enabled: bool,
+    pub interval_mins: u64,
+    /// tagged images of a repository past the newest ones are removed
+    pub keep_images_per_repository: Option<usize>,
+    /// stopped containers of compose projects are never removed, nor the
+    /// images a project last ran, also once it was brought down
+    pub keep_project_images: bool,
+    /// stopped containers are removed this many days after they exited
+    pub stopped_containers_days: Option<u64>,
+    /// untagged images are removed this many hours after they were created
+    pub dangling_images_hours: Option<u64>,
+    /// days the history of removals is kept
+    pub history_days: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            enabled: false,
+            interval_mins: 60,
+            keep_images_per_repository: None,
+            keep_project_images: true,
+            stopped_containers_days: None,
+            dangling_images_hours: None,
+            history_days: 90,
+        }
+    }
+}
+
+impl RetentionConfig {
+    /// Whether any rule removes something
+    pub fn has_rules(&self) -> bool {
+        self.keep_images_per_repository.is_some()
+            || self.stopped_containers_days.is_some()
+            || self.dangling_images_hours.is_some()
+    }
+}
+
+impl Config {
+    /// Load the config file, or use the defaults when no path is given
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let path = match path {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+
+        toml::from_str(&contents)
+            .with_context(|| format!("failed to parse config file {}", path.display()))
+    }
+
+    pub fn project(&self, name: &str) -> ProjectConfig {
+        self.projects.get(name).cloned().unwrap_or_default()
+    }
+}
@@ -1,11 +1,40 @@
-use std::process::{Command, Stdio};
-use tokio::io::{AsyncBufReadExt, BufReader};
+use std::{
+    collections::{BTreeMap, HashMap},
+    process::Command,
+};
 
 use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
 use tokio::sync::broadcast;
 
-use crate::{util, SseEvent};
+use crate::{
+    fields::{de, parse_timestamp, ContainerSize, HealthStatus, Port},
+    inspect::{ContainerInspect, ImageInspect},
+    model::{SseEvent, SseTask},
+    util,
+};
+
+pub const PROJECT_LABEL: &str = "com.docker.compose.project";
+pub const SERVICE_LABEL: &str = "com.docker.compose.service";
+pub const CONFIG_FILES_LABEL: &str = "com.docker.compose.project.config_files";
+
+/// Signals offered by the kill action
+pub const KILL_SIGNALS: [&str; 8] = [
+    "SIGKILL", "SIGTERM", "SIGINT", "SIGQUIT", "SIGHUP", "SIGUSR1", "SIGUSR2", "SIGWINCH",
+];
+
+/// The image a container runs, `docker ps` shows the reference it was created
+/// from, or the image id once the reference points to a newer image
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerImage {
+    /// `sha256:...`
+    pub id: String,
+    /// e.g. `postgres:16`, as given to `docker run` or in the compose file
+    pub reference: String,
+}
 
+/// A line of `docker ps --format json`, the columns the cli formats as
+/// strings are parsed into their typed values, see `fields`
 #[derive(Debug, Clone, serde::Deserialize)]
 pub struct Container {
     #[serde(alias = "ID")]
@@ -14,51 +43,60 @@ pub struct Container {
     pub image: String,
     #[serde(alias = "Command")]
     pub command: String,
-    #[serde(alias = "CreatedAt")]
-    pub created_at: String,
-    #[serde(alias = "RunningFor")]
-    pub running_for: String,
-    #[serde(alias = "Ports")]
-    pub ports: String,
+    #[serde(alias = "CreatedAt", deserialize_with = "de::timestamp")]
+    pub created_at: DateTime<FixedOffset>,
+    #[serde(alias = "Ports", deserialize_with = "de::ports")]
+    pub ports: Vec<Port>,
     #[serde(alias = "Status")]
     pub status: String,
-    #[serde(alias = "Size")]
-    pub size: String,
+    /// only known when listed with `--size`
+    #[serde(alias = "Size", deserialize_with = "de::container_size")]
+    pub size: Option<ContainerSize>,
     #[serde(alias = "Names")]
     pub names: String,
-    #[serde(alias = "Labels")]
-    pub labels: String,
-    #[serde(alias = "Mounts")]
-    pub mounts: String,
-    #[serde(alias = "Networks")]
-    pub networks: String,
+    #[serde(alias = "Labels", deserialize_with = "de::labels")]
+    pub labels: BTreeMap<String, String>,
+    /// volume names and bind mount sources
+    #[serde(alias = "Mounts", deserialize_with = "de::list")]
+    pub mounts: Vec<String>,
+    #[serde(alias = "Networks", deserialize_with = "de::list")]
+    pub networks: Vec<String>,
     #[serde(alias = "State")]
     pub state: String,
-    #[serde(alias = "LocalVolumes")]
-    pub local_volumes: String,
+    #[serde(alias = "LocalVolumes", deserialize_with = "de::optional_count")]
+    pub local_volumes: Option<u64>,
 }
 
 impl Container {
     pub fn get_all() -> Result<Vec<Container>> {
-        let output = Command::new("docker")
-            .arg("ps")
-            .arg("--all")
-            .arg("--no-trunc")
-            .arg("--format")
-            .arg("'{{json .}}'")
-            .output()?;
+        Self::list(false)
+    }
+
+    /// Like `get_all` with the sizes filled in, which makes `docker ps` a lot slower
+    pub fn get_all_with_size() -> Result<Vec<Container>> {
+        Self::list(true)
+    }
+
+    fn list(size: bool) -> Result<Vec<Container>> {
+        let mut cmd = Command::new("docker");
+        cmd.arg("ps").arg("--all").arg("--no-trunc");
+        if size {
+            cmd.arg("--size");
+        }
+        let output = cmd.arg("--format").arg("'{{json .}}'").output()?;
 
         let output = String::from_utf8(output.stdout)?;
 
         let mut output: Vec<Container> = output
-            .split("\n")
+            .split('\n')
             .filter(|val| !val.is_empty())
             .map(|val| val.trim_matches('\''))
-            .map(|val| serde_json::from_str::<Container>(val))
-            .filter_map(|val| val.ok())
-            .filter(move |val| {
-                val.labels
-                    .contains("com.docker.compose.project.config_files")
+            .filter_map(|val| match serde_json::from_str::<Container>(val) {
+                Ok(container) => Some(container),
+                Err(e) => {
+                    tracing::warn!("failed to parse container {}: {}", val, e);
+                    None
+                }
             })
             .collect::<Vec<_>>();
 
@@ -67,122 +105,323 @@ impl Container {
         Ok(output)
     }
 
-    fn get_compose_config_file_path(name: String) -> Result<String> {
+    /// The images of the containers, by container id
+    pub fn images(containers: &[Container]) -> Result<HashMap<String, ContainerImage>> {
+        if containers.is_empty() {
+            return Ok(HashMap::new());
+        }
+
         let output = Command::new("docker")
+            .arg("container")
             .arg("inspect")
-            .arg(&name)
             .arg("--format")
-            .arg("'{{ index .Config.Labels \"com.docker.compose.project.config_files\" }}'")
+            .arg("{{.Id}} {{.Image}} {{.Config.Image}}")
+            .args(containers.iter().map(|c| &c.id))
             .output()?;
 
-        let output = String::from_utf8(output.stdout)?;
+        // containers removed since they were listed are left out
+        Ok(String::from_utf8(output.stdout)?
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let (id, image, reference) = (fields.next()?, fields.next()?, fields.next()?);
+                Some((
+                    id.to_string(),
+                    ContainerImage {
+                        id: image.to_string(),
+                        reference: reference.to_string(),
+                    },
+                ))
+            })
+            .collect())
+    }
 
-        if output.is_empty() {
-            return Err(anyhow::anyhow!("no compose file found"));
+    /// When the containers last exited, by container id. Containers that
+    /// never ran are left out.
+    pub fn finished_at(containers: &[Container]) -> Result<HashMap<String, DateTime<FixedOffset>>> {
+        if containers.is_empty() {
+            return Ok(HashMap::new());
         }
 
-        let output = output.replace("'", "");
-        let output = output.trim(); // remove newline
+        let output = Command::new("docker")
+            .arg("container")
+            .arg("inspect")
+            .arg("--format")
+            .arg("{{.Id}} {{.State.FinishedAt}}")
+            .args(containers.iter().map(|c| &c.id))
+            .output()?;
 
-        Ok(output.into())
+        Ok(String::from_utf8(output.stdout)?
+            .lines()
+            .filter_map(|line| {
+                let (id, finished_at) = line.split_once(' ')?;
+                // `0001-01-01T00:00:00Z` when the container never ran
+                let finished_at =
+                    parse_timestamp(finished_at).filter(|time| time.timestamp() > 0)?;
+                Some((id.to_string(), finished_at))
+            })
+            .collect())
     }
 
-    fn get_compose_dir(name: String) -> Result<String> {
-        let output = Self::get_compose_config_file_path(name)?;
-
-        let output = output
-            .split("/")
-            .map(|val| val.to_string())
-            .collect::<Vec<String>>();
-
-        let output = (&output[0..output.len() - 1]).join("/");
+    pub fn label(&self, key: &str) -> Option<String> {
+        self.labels.get(key).cloned()
+    }
 
-        Ok(output)
+    /// compose project name from the container labels
+    pub fn project(&self) -> Option<String> {
+        self.label(PROJECT_LABEL)
     }
 
-    /// docker compose pull
-    /// broadcast the stdout and stderr results to the sender
-    pub async fn pull(name: String, tx: &broadcast::Sender<SseEvent>) -> Result<()> {
-        let dir = Self::get_compose_dir(name.clone())?;
+    /// compose service name from the container labels
+    pub fn service(&self) -> Option<String> {
+        self.label(SERVICE_LABEL)
+    }
 
-        tx.send(SseEvent {
-            event: name.clone(),
-            data: "docker compose pull\n".into(),
-        })
-        .context("pull: stdout send error")?;
+    /// Containers that weren't created by docker compose, e.g. with `docker run`
+    pub fn is_standalone(&self) -> bool {
+        !self.labels.contains_key(CONFIG_FILES_LABEL)
+    }
 
-        let cmd = tokio::process::Command::new("docker")
-            .arg("compose")
-            .arg("pull")
-            .current_dir(dir)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
+    /// Healthcheck state, for containers that have one
+    pub fn health(&self) -> Option<HealthStatus> {
+        HealthStatus::from_status(&self.status)
+    }
 
-        util::execute_command(&name, cmd, tx).await?;
+    /// A newer image was pulled for the container's tag and the container
+    /// still runs the old one, `docker ps` then shows the image id instead
+    pub fn has_update(&self) -> bool {
+        let id = self.image.strip_prefix("sha256:").unwrap_or(&self.image);
+        matches!(id.len(), 12 | 64) && id.chars().all(|c| c.is_ascii_hexdigit())
+    }
 
-        Ok(())
+    /// Whether a lifecycle task can be run in the container's current state
+    pub fn can(&self, task: &SseTask) -> bool {
+        let state = self.state.as_str();
+        match task {
+            SseTask::ContainerStart => matches!(state, "created" | "exited"),
+            SseTask::ContainerStop => matches!(state, "running" | "restarting" | "paused"),
+            SseTask::ContainerRestart => matches!(state, "running" | "created" | "exited"),
+            SseTask::ContainerKill => matches!(state, "running" | "restarting"),
+            SseTask::ContainerPause => state == "running",
+            SseTask::ContainerUnpause => state == "paused",
+            SseTask::ContainerRemove => matches!(state, "created" | "exited" | "dead"),
+            SseTask::ContainerRecreate => self.is_standalone() && state != "removing",
+            _ => false,
+        }
     }
 
-    // docker compose pull && docker compose up -d
-    pub async fn update(name: String, tx: &broadcast::Sender<SseEvent>) -> Result<()> {
-        let dir = Self::get_compose_dir(name.clone())?;
+    /// docker container start/stop/restart/kill/pause/unpause/rm
+    pub async fn lifecycle(
+        name: String,
+        task: SseTask,
+        signal: Option<String>,
+        tx: &broadcast::Sender<SseEvent>,
+    ) -> Result<()> {
+        let args = match task {
+            SseTask::ContainerStart => vec!["start".to_string()],
+            SseTask::ContainerStop => vec!["stop".to_string()],
+            SseTask::ContainerRestart => vec!["restart".to_string()],
+            SseTask::ContainerPause => vec!["pause".to_string()],
+            SseTask::ContainerUnpause => vec!["unpause".to_string()],
+            SseTask::ContainerRemove => vec!["rm".to_string()],
+            SseTask::ContainerKill => {
+                let signal = signal.unwrap_or_else(|| KILL_SIGNALS[0].to_string());
+                if !KILL_SIGNALS.contains(&signal.as_str()) {
+                    return util::refuse(&name, format!("invalid signal {}", signal), tx);
+                }
+                vec!["kill".to_string(), "--signal".to_string(), signal]
+            }
+            _ => return Err(anyhow::anyhow!("lifecycle: invalid task {}", task)),
+        };
+
+        // the name comes from the url, it can't be taken for an option
+        let mut args = [vec!["container".to_string()], args].concat();
+        args.push("--".to_string());
+        args.push(name.clone());
+
+        util::docker(&name, &args, tx).await
+    }
 
-        tx.send(SseEvent {
-            event: name.clone(),
-            data: "docker compose down\n".into(),
-        })
-        .context("update: stdout send error")?;
+    /// Pull the container's image and re-create the container from it with the
+    /// same run configuration, see `ContainerInspect::create_args`.
+    /// The old container is kept under another name until the new one is up,
+    /// and restored if anything fails.
+    pub async fn recreate(name: String, tx: &broadcast::Sender<SseEvent>) -> Result<()> {
+        let inspect = ContainerInspect::get(&name)?;
+
+        let project = inspect
+            .config
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get(PROJECT_LABEL));
+        if let Some(project) = project {
+            return util::refuse(
+                &name,
+                format!(
+                    "{} belongs to compose project {}, recreate its service instead",
+                    name, project
+                ),
+                tx,
+            );
+        }
 
-        let down = tokio::process::Command::new("docker")
-            .arg("compose")
-            .arg("down")
-            .current_dir(&dir)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
+        let unsupported = inspect.unsupported();
+        if !unsupported.is_empty() {
+            return util::refuse(
+                &name,
+                format!(
+                    "{} can't be re-created with the same configuration, it uses {}",
+                    name,
+                    unsupported.join(", ")
+                ),
+                tx,
+            );
+        }
 
-        util::execute_command(&name, down, tx).await?;
+        // containers created from an image id have nothing to pull
+        if !inspect.config.image.starts_with("sha256:") {
+            util::docker(&name, &["pull", &inspect.config.image], tx).await?;
+        }
 
-        tx.send(SseEvent {
-            event: name.clone(),
-            data: "\ndocker compose up -d\n".into(),
-        })
-        .context("update: stdout send error")?;
+        let image = ImageInspect::get(&inspect.image)?;
+        let old_name = format!("{}-mgdocker-old", inspect.name());
+
+        util::docker(&name, &["container", "rename", &name, &old_name], tx).await?;
+
+        match Self::create_from(&name, &old_name, &inspect, &image, tx).await {
+            Ok(_) => util::docker(&name, &["container", "rm", &old_name], tx).await,
+            Err(e) => {
+                tx.send(SseEvent {
+                    event: name.clone(),
+                    data: format!(
+                        "\nrecreate failed: {}\nrestoring the old container\n",
+                        util::escape_html(&e.to_string())
+                    ),
+                })
+                .context("recreate: stdout send error")?;
+
+                // the new container may not exist, depending on where it failed
+                let _ = util::docker(&name, &["container", "rm", "--force", &name], tx).await;
+                util::docker(&name, &["container", "rename", &old_name, &name], tx).await?;
+                if inspect.state.running {
+                    util::docker(&name, &["container", "start", &name], tx).await?;
+                }
+
+                Err(e)
+            }
+        }
+    }
 
-        let up = tokio::process::Command::new("docker")
-            .arg("compose")
-            .arg("up")
-            .arg("-d")
-            .current_dir(&dir)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
+    /// Create the new container, then swap it in for the old one.
+    /// Ports are only bound on start, so the old container keeps running until then.
+    async fn create_from(
+        name: &str,
+        old_name: &str,
+        inspect: &ContainerInspect,
+        image: &ImageInspect,
+        tx: &broadcast::Sender<SseEvent>,
+    ) -> Result<()> {
+        let mut args = vec!["container".to_string(), "create".to_string()];
+        args.extend(inspect.create_args(image));
+        util::docker(name, &args, tx).await?;
+
+        for network in inspect.extra_networks() {
+            util::docker(name, &["network", "connect", &network, name], tx).await?;
+        }
 
-        util::execute_command(&name, up, tx).await?;
+        if inspect.state.running {
+            util::docker(name, &["container", "stop", old_name], tx).await?;
+            util::docker(name, &["container", "start", name], tx).await?;
+        }
 
         Ok(())
     }
+}
 
-    pub async fn get_config(name: String, tx: &broadcast::Sender<SseEvent>) -> Result<()> {
-        let config_file_path = Self::get_compose_config_file_path(name.clone())?;
-
-        let file = tokio::fs::File::open(config_file_path).await?;
-        let reader = BufReader::new(file);
-
-        let mut lines = reader.lines();
-        let mut output: Vec<String> = vec![];
-
-        while let Some(line) = lines.next_line().await? {
-            output.push(line);
-        }
-
-        tx.send(SseEvent {
-            event: name.clone(),
-            data: output.join("\n"),
+/// A `docker ps` line with only the columns the tests of other modules look
+/// at, see `tests::PS_LINES` for complete ones
+#[cfg(test)]
+pub fn test_container(
+    id: &str,
+    name: &str,
+    image: &str,
+    project: Option<&str>,
+    state: &str,
+) -> Container {
+    let labels = project
+        .map(|project| {
+            format!(
+                "{}={},{}=/srv/{}/compose.yml",
+                PROJECT_LABEL, project, CONFIG_FILES_LABEL, project
+            )
         })
-        .context("get_config: stdout send error")?;
+        .unwrap_or_default();
+    serde_json::from_str(&format!(
+        r#"{{"ID":"{}","Image":"{}","Command":"","CreatedAt":"2024-03-01 10:00:00 +0000 UTC","Ports":"","Status":"","Size":"","Names":"{}","Labels":"{}","Mounts":"","Networks":"","State":"{}","LocalVolumes":"0"}}"#,
+        id, image, name, labels, state
+    ))
+    .unwrap()
+}
 
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `docker ps --all --no-trunc --format '{{json .}}'` from docker 25
+    const PS_LINES: [&str; 2] = [
+        r#"{"Command":"\"docker-entrypoint.sh postgres\"","CreatedAt":"2024-02-19 21:13:29 +0100 CET","ID":"5f3b1c0d9a8e7f6b5c4d3e2f1a0b9c8d7e6f5a4b3c2d1e0f9a8b7c6d5e4f3a2b","Image":"postgres:16-alpine","Labels":"com.docker.compose.config-hash=ab12cd34,com.docker.compose.container-number=1,com.docker.compose.depends_on=,com.docker.compose.oneoff=False,com.docker.compose.project=app,com.docker.compose.project.config_files=/srv/app/compose.yml,/srv/app/compose.override.yml,com.docker.compose.project.working_dir=/srv/app,com.docker.compose.service=db,com.docker.compose.version=2.24.5","LocalVolumes":"1","Mounts":"app_db","Names":"app-db-1","Networks":"app_default","Ports":"0.0.0.0:5432->5432/tcp, :::5432->5432/tcp","RunningFor":"2 weeks ago","Size":"63B (virtual 243MB)","State":"running","Status":"Up 2 weeks (healthy)"}"#,
+        r#"{"Command":"\"/docker-entrypoint.sh nginx -g 'daemon off;'\"","CreatedAt":"2024-03-01 08:00:00 +0000 UTC","ID":"9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e0d9c8b7a6f5e4d3c2b1a0f9e8d","Image":"nginx:latest","Labels":"maintainer=NGINX Docker Maintainers <docker-maint@nginx.com>","LocalVolumes":"0","Mounts":"/srv/www,/etc/nginx/conf.d","Names":"web","Networks":"bridge,proxy","Ports":"","RunningFor":"3 days ago","Size":"0B","State":"exited","Status":"Exited (0) 3 days ago"}"#,
+    ];
+
+    #[test]
+    fn compose_container() {
+        let c: Container = serde_json::from_str(PS_LINES[0]).unwrap();
+
+        assert_eq!(c.names, "app-db-1");
+        assert_eq!(c.command, "\"docker-entrypoint.sh postgres\"");
+        assert_eq!(c.project().as_deref(), Some("app"));
+        assert_eq!(c.service().as_deref(), Some("db"));
+        assert_eq!(
+            c.label(CONFIG_FILES_LABEL).as_deref(),
+            Some("/srv/app/compose.yml,/srv/app/compose.override.yml")
+        );
+        assert_eq!(
+            c.label("com.docker.compose.depends_on").as_deref(),
+            Some("")
+        );
+        assert!(!c.is_standalone());
+
+        assert_eq!(c.ports.len(), 2);
+        assert_eq!(c.ports[0].host_port, Some(5432));
+        assert_eq!(c.ports[0].container_port, 5432);
+
+        let size = c.size.unwrap();
+        assert_eq!(size.rw, 63);
+        assert_eq!(size.virtual_size, Some(243_000_000));
+
+        assert_eq!(c.created_at.to_rfc3339(), "2024-02-19T21:13:29+01:00");
+        assert_eq!(c.mounts, vec!["app_db"]);
+        assert_eq!(c.local_volumes, Some(1));
+        assert_eq!(c.health(), Some(HealthStatus::Healthy));
+        assert!(!c.has_update());
+    }
+
+    #[test]
+    fn standalone_container() {
+        let c: Container = serde_json::from_str(PS_LINES[1]).unwrap();
+
+        assert!(c.is_standalone());
+        assert_eq!(c.project(), None);
+        assert_eq!(
+            c.label("maintainer").as_deref(),
+            Some("NGINX Docker Maintainers <docker-maint@nginx.com>")
+        );
+        assert!(c.ports.is_empty());
+        assert_eq!(c.mounts, vec!["/srv/www", "/etc/nginx/conf.d"]);
+        assert_eq!(c.networks, vec!["bridge", "proxy"]);
+        assert_eq!(c.size.map(|size| size.rw), Some(0));
+        assert_eq!(c.health(), None);
+        assert!(c.can(&SseTask::ContainerStart));
+        assert!(!c.can(&SseTask::ContainerStop));
     }
 }
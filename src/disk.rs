@@ -0,0 +1,319 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    container::Container,
+    fields::de,
+    image::{Image, ImageUsage},
+    util::parse_bytes,
+    volume::Volume,
+};
+
+/// A line of `docker system df --format json`, one per resource type
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct DiskUsage {
+    /// `Images`, `Containers`, `Local Volumes` or `Build Cache`
+    #[serde(alias = "Type")]
+    pub kind: String,
+    #[serde(alias = "TotalCount", deserialize_with = "de::optional_count")]
+    pub total_count: Option<u64>,
+    #[serde(alias = "Active", deserialize_with = "de::optional_count")]
+    pub active: Option<u64>,
+    #[serde(alias = "Size", deserialize_with = "de::bytes")]
+    pub size: u64,
+    /// what a prune would free, e.g. `1.2GB (49%)`
+    #[serde(alias = "Reclaimable")]
+    pub reclaimable: String,
+}
+
+impl DiskUsage {
+    pub fn get_all() -> Result<Vec<DiskUsage>> {
+        let output = Command::new("docker")
+            .arg("system")
+            .arg("df")
+            .arg("--format")
+            .arg("{{json .}}")
+            .output()?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "docker system df: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        String::from_utf8(output.stdout)?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("failed to parse disk usage {}", line))
+            })
+            .collect()
+    }
+
+    pub fn reclaimable_bytes(&self) -> Option<u64> {
+        parse_bytes(self.reclaimable.split('(').next().unwrap_or_default())
+    }
+
+    /// Size of one resource type, zero when docker didn't list it
+    pub fn size_of(usage: &[DiskUsage], kind: &str) -> u64 {
+        usage
+            .iter()
+            .find(|u| u.kind == kind)
+            .map(|u| u.size)
+            .unwrap_or_default()
+    }
+}
+
+/// A build cache record of `docker system df --verbose`
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct BuildCacheRecord {
+    #[serde(alias = "ID")]
+    pub id: String,
+    #[serde(alias = "CacheType", alias = "Type", default)]
+    pub kind: String,
+    /// the build step, e.g. `[2/5] RUN apt-get update`
+    #[serde(alias = "Description", default)]
+    pub description: String,
+    #[serde(alias = "Size", deserialize_with = "de::optional_bytes", default)]
+    pub size: Option<u64>,
+    #[serde(alias = "LastUsedSince", default)]
+    pub last_used_since: String,
+    /// `true` while a build uses the record
+    #[serde(alias = "InUse", default)]
+    pub in_use: String,
+}
+
+/// `docker system df --verbose`, every image, container, volume and build
+/// cache record with its size
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct DiskUsageDetail {
+    #[serde(alias = "Images", default)]
+    pub images: Vec<Image>,
+    #[serde(alias = "Containers", default)]
+    pub containers: Vec<Container>,
+    #[serde(alias = "Volumes", default)]
+    pub volumes: Vec<Volume>,
+    #[serde(alias = "BuildCache", default)]
+    pub build_cache: Vec<BuildCacheRecord>,
+}
+
+impl DiskUsageDetail {
+    /// Slow on hosts with many volumes, docker measures each of them
+    pub fn get() -> Result<DiskUsageDetail> {
+        let output = Command::new("docker")
+            .arg("system")
+            .arg("df")
+            .arg("--verbose")
+            .arg("--format")
+            .arg("{{json .}}")
+            .output()?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "docker system df: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        serde_json::from_slice(&output.stdout).context("failed to parse docker system df")
+    }
+}
+
+/// Disk space of a compose project. Images shared with other projects are
+/// counted for each of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProjectDiskUsage {
+    pub name: String,
+    /// writable layers of the containers
+    pub containers: u64,
+    pub volumes: u64,
+    /// the images the containers run, without the layers shared with other images
+    pub images: u64,
+}
+
+impl ProjectDiskUsage {
+    pub fn total(&self) -> u64 {
+        self.containers + self.volumes + self.images
+    }
+
+    /// Per project totals, largest first. `usage` maps the images to the
+    /// containers running them, see `Image::usage`.
+    pub fn from_detail(
+        detail: &DiskUsageDetail,
+        usage: &HashMap<String, ImageUsage>,
+    ) -> Vec<ProjectDiskUsage> {
+        let mut projects = BTreeMap::<String, ProjectDiskUsage>::new();
+        fn entry(
+            projects: &mut BTreeMap<String, ProjectDiskUsage>,
+            name: String,
+        ) -> &mut ProjectDiskUsage {
+            projects
+                .entry(name.clone())
+                .or_insert_with(|| ProjectDiskUsage {
+                    name,
+                    ..Default::default()
+                })
+        }
+
+        for c in detail.containers.iter() {
+            if let Some(project) = c.project() {
+                entry(&mut projects, project).containers +=
+                    c.size.map(|size| size.rw).unwrap_or_default();
+            }
+        }
+        for v in detail.volumes.iter() {
+            if let Some(project) = v.project() {
+                entry(&mut projects, project).volumes += v.size.unwrap_or_default();
+            }
+        }
+
+        // images are listed once per tag
+        let mut images = detail.images.clone();
+        images.sort_by(|a, b| a.id.cmp(&b.id));
+        images.dedup_by(|a, b| a.id == b.id);
+        for image in images {
+            let Some(image_usage) = usage.get(&image.id) else {
+                continue;
+            };
+            for project in image_usage.projects() {
+                entry(&mut projects, project).images += image.unique_size.unwrap_or(image.size);
+            }
+        }
+
+        let mut projects = projects.into_values().collect::<Vec<_>>();
+        projects.sort_by_key(|p| std::cmp::Reverse(p.total()));
+        projects
+    }
+}
+
+/// Space on the filesystem of docker's data root, where images, containers
+/// and volumes are stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreeSpace {
+    /// e.g. `/var/lib/docker`
+    pub root_dir: String,
+    pub size: u64,
+    pub available: u64,
+}
+
+impl FreeSpace {
+    /// Only works when the data root is visible to mgdocker, which isn't
+    /// the case when it runs in a container without it mounted
+    pub fn get() -> Result<FreeSpace> {
+        let output = Command::new("docker")
+            .arg("info")
+            .arg("--format")
+            .arg("{{.DockerRootDir}}")
+            .output()?;
+        let root_dir = String::from_utf8(output.stdout)?.trim().to_string();
+        if root_dir.is_empty() {
+            return Err(anyhow::anyhow!("docker info: no data root"));
+        }
+
+        let output = Command::new("df")
+            .arg("-P")
+            .arg("-k")
+            .arg(&root_dir)
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "df {}: {}",
+                root_dir,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Self::parse(root_dir, &String::from_utf8(output.stdout)?)
+    }
+
+    /// `df -P -k`: a header, then filesystem, 1024-blocks, used, available, ...
+    fn parse(root_dir: String, df: &str) -> Result<FreeSpace> {
+        let fields = df
+            .lines()
+            .nth(1)
+            .context("df: no filesystem")?
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        let kilobytes = |i: usize| -> Result<u64> {
+            let value = fields.get(i).context("df: missing column")?;
+            Ok(value.parse::<u64>().context("df: invalid size")? * 1024)
+        };
+
+        Ok(FreeSpace {
+            size: kilobytes(1)?,
+            available: kilobytes(3)?,
+            root_dir,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_usage() {
+        let line = r#"{"Active":"2","Reclaimable":"1.2GB (49%)","Size":"2.45GB","TotalCount":"7","Type":"Images"}"#;
+        let usage: DiskUsage = serde_json::from_str(line).unwrap();
+
+        assert_eq!(usage.kind, "Images");
+        assert_eq!(usage.total_count, Some(7));
+        assert_eq!(usage.reclaimable_bytes(), Some(1_200_000_000));
+        assert_eq!(DiskUsage::size_of(&[usage], "Images"), 2_450_000_000);
+    }
+
+    #[test]
+    fn free_space() {
+        let df = "Filesystem     1024-blocks     Used Available Capacity Mounted on\n/dev/vda1         25669860 19512516   4829476      81% /\n";
+        let free = FreeSpace::parse("/var/lib/docker".to_string(), df).unwrap();
+
+        assert_eq!(free.size, 25_669_860 * 1024);
+        assert_eq!(free.available, 4_829_476 * 1024);
+        assert!(FreeSpace::parse("/x".to_string(), "Filesystem\n").is_err());
+    }
+
+    #[test]
+    fn detail() {
+        let json = r#"{"BuildCache":[{"CacheType":"regular","CreatedSince":"2 days ago","Description":"[2/3] RUN apk add curl","ID":"k3ex4fq","InUse":"false","LastUsedSince":"2 days ago","Reclaimable":"true","Shared":"false","Size":"12.3MB","UsageCount":"1"}],"Containers":[],"Images":[],"Volumes":[{"Driver":"local","Labels":"com.docker.compose.project=app,com.docker.compose.volume=db","Links":"0","Mountpoint":"/var/lib/docker/volumes/app_db/_data","Name":"app_db","Scope":"local","Size":"41.2MB"}]}"#;
+        let detail: DiskUsageDetail = serde_json::from_str(json).unwrap();
+
+        let volume = &detail.volumes[0];
+        assert_eq!(volume.name, "app_db");
+        assert_eq!(volume.project().as_deref(), Some("app"));
+        assert!(volume.is_unused());
+        assert_eq!(volume.size, Some(41_200_000));
+
+        let record = &detail.build_cache[0];
+        assert_eq!(record.kind, "regular");
+        assert_eq!(record.size, Some(12_300_000));
+    }
+
+    #[test]
+    fn project_disk_usage() {
+        let json = r#"{"BuildCache":[],"Containers":[{"Command":"\"docker-entrypoint.s…\"","CreatedAt":"2024-03-01 10:00:00 +0000 UTC","ID":"aaa111","Image":"postgres:16","Labels":"com.docker.compose.project=app","LocalVolumes":"1","Mounts":"app_db","Names":"app-db-1","Networks":"app_default","Ports":"","RunningFor":"2 weeks ago","Size":"63MB","State":"running","Status":"Up 2 weeks"}],"Images":[{"Containers":"1","CreatedAt":"2024-02-13 20:26:49 +0100 CET","CreatedSince":"3 weeks ago","Digest":"\u003cnone\u003e","ID":"sha256:111","Repository":"postgres","SharedSize":"7MB","Size":"432MB","Tag":"16","UniqueSize":"425MB","VirtualSize":"432MB"},{"Containers":"1","CreatedAt":"2024-02-13 20:26:49 +0100 CET","CreatedSince":"3 weeks ago","Digest":"\u003cnone\u003e","ID":"sha256:111","Repository":"postgres","SharedSize":"7MB","Size":"432MB","Tag":"latest","UniqueSize":"425MB","VirtualSize":"432MB"}],"Volumes":[{"Driver":"local","Labels":"com.docker.compose.project=app","Links":"1","Mountpoint":"/v","Name":"app_db","Scope":"local","Size":"41MB"}]}"#;
+        let detail: DiskUsageDetail = serde_json::from_str(json).unwrap();
+        let usage = HashMap::from([(
+            "sha256:111".to_string(),
+            ImageUsage {
+                containers: detail.containers.clone(),
+                ..Default::default()
+            },
+        )]);
+
+        assert_eq!(
+            ProjectDiskUsage::from_detail(&detail, &usage),
+            vec![ProjectDiskUsage {
+                name: "app".to_string(),
+                containers: 63_000_000,
+                volumes: 41_000_000,
+                images: 425_000_000,
+            }]
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::{process::Stdio, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Command,
+    sync::{broadcast, RwLock},
+    time::timeout,
+};
+
+use crate::container::Container;
+
+/// Container events that change what the containers page shows. Healthchecks
+/// also emit exec events on every probe, those are left out.
+const ACTIONS: [&str; 13] = [
+    "create",
+    "start",
+    "restart",
+    "stop",
+    "die",
+    "kill",
+    "oom",
+    "pause",
+    "unpause",
+    "destroy",
+    "rename",
+    "update",
+    "health_status",
+];
+
+/// A task causes a burst of events, the containers are listed once it settles
+const DEBOUNCE: Duration = Duration::from_millis(500);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A line of `docker events --format json`
+#[derive(Debug, serde::Deserialize)]
+struct DockerEvent {
+    #[serde(alias = "Type")]
+    kind: String,
+    /// e.g. `start` or `health_status: healthy`
+    #[serde(alias = "Action")]
+    action: String,
+}
+
+impl DockerEvent {
+    fn is_relevant(line: &str) -> bool {
+        match serde_json::from_str::<DockerEvent>(line) {
+            Ok(event) => {
+                let action = event.action.split(':').next().unwrap_or_default();
+                event.kind == "container" && ACTIONS.contains(&action)
+            }
+            Err(e) => {
+                tracing::warn!("failed to parse docker event {}: {}", line, e);
+                false
+            }
+        }
+    }
+}
+
+/// The containers, kept current from the docker events for the live updates
+/// of the containers page, see `live`
+pub struct Inventory {
+    pub containers: Vec<Container>,
+}
+
+impl Inventory {
+    /// Listed up front, a page loaded before the first event has to be
+    /// diffed against the same containers it was rendered from
+    pub fn new() -> Inventory {
+        let containers = Container::get_all().unwrap_or_else(|e| {
+            tracing::warn!("failed to list containers: {:#}", e);
+            vec![]
+        });
+        Inventory { containers }
+    }
+
+    /// Follow `docker events`, re-connecting when it exits, and notify `tx`
+    /// whenever the containers were listed again
+    pub async fn run(inventory: Arc<RwLock<Inventory>>, tx: broadcast::Sender<()>) {
+        loop {
+            if let Err(e) = Self::watch(&inventory, &tx).await {
+                tracing::warn!("docker events: {:#}", e);
+            }
+            tokio::time::sleep(RECONNECT_INTERVAL).await;
+        }
+    }
+
+    async fn watch(inventory: &RwLock<Inventory>, tx: &broadcast::Sender<()>) -> Result<()> {
+        let mut child = Command::new("docker")
+            .arg("events")
+            .arg("--filter")
+            .arg("type=container")
+            .arg("--format")
+            .arg("{{json .}}")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdout = child.stdout.take().context("docker events: no stdout")?;
+        let mut lines = BufReader::new(stdout).lines();
+
+        // events may have been missed while disconnected
+        Self::refresh(inventory, tx).await;
+
+        while let Some(line) = lines.next_line().await? {
+            if !DockerEvent::is_relevant(&line) {
+                continue;
+            }
+
+            let mut closed = false;
+            loop {
+                match timeout(DEBOUNCE, lines.next_line()).await {
+                    Ok(Ok(Some(_))) => continue,
+                    Ok(Ok(None)) => closed = true,
+                    Ok(Err(e)) => return Err(e.into()),
+                    Err(_) => {}
+                }
+                break;
+            }
+
+            Self::refresh(inventory, tx).await;
+            if closed {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn refresh(inventory: &RwLock<Inventory>, tx: &broadcast::Sender<()>) {
+        let containers = tokio::task::spawn_blocking(Container::get_all)
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|containers| containers);
+        match containers {
+            Ok(containers) => {
+                inventory.write().await.containers = containers;
+                // no receivers while nobody has the containers page open
+                let _ = tx.send(());
+            }
+            Err(e) => tracing::warn!("failed to list containers: {:#}", e),
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use futures::{SinkExt, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{config::ExecConfig, util::empty_as_none};
+
+/// Longest reason a websocket close frame can carry, in bytes
+const MAX_CLOSE_REASON: usize = 123;
+
+/// Shell and user picked on the terminal page
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct ExecParams {
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub shell: Option<String>,
+    /// runs as the container's own user when not set
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub user: Option<String>,
+}
+
+/// Messages sent by the terminal page, keystrokes and size changes.
+/// The pty output goes back as binary messages.
+#[derive(Debug, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ClientMessage {
+    Input { data: String },
+    Resize { cols: u16, rows: u16 },
+}
+
+/// Browsers send the `Origin` of the page opening a websocket but don't apply
+/// the same-origin policy to it, so without this check any site the operator
+/// visits could open a shell. The origin has to be the host the request was
+/// sent to.
+pub fn check_origin(origin: Option<&str>, host: Option<&str>) -> Result<()> {
+    let origin = origin.context("missing Origin header")?;
+    let host = host.context("missing Host header")?;
+    let origin_host = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"))
+        .and_then(|rest| rest.split('/').next())
+        .with_context(|| format!("invalid Origin {}", origin))?;
+
+    if !origin_host.eq_ignore_ascii_case(host) {
+        return Err(anyhow::anyhow!(
+            "Origin {} does not match Host {}",
+            origin,
+            host
+        ));
+    }
+
+    Ok(())
+}
+
+/// `reason` cut to fit a close frame, a longer one fails to send
+fn close_reason(reason: &str) -> String {
+    let mut end = reason.len().min(MAX_CLOSE_REASON);
+    while !reason.is_char_boundary(end) {
+        end -= 1;
+    }
+    reason[..end].to_string()
+}
+
+/// An interactive `docker exec -it` session relayed over a websocket
+#[derive(Debug, Clone)]
+pub struct ExecSession {
+    pub container: String,
+    pub shell: String,
+    pub user: Option<String>,
+    pub client: SocketAddr,
+    pub max_duration: Duration,
+}
+
+impl ExecSession {
+    /// Check the request against the `[exec]` config before the websocket is upgraded
+    pub fn new(
+        container: String,
+        params: ExecParams,
+        client: SocketAddr,
+        config: &ExecConfig,
+    ) -> Result<ExecSession> {
+        config.check(&container)?;
+
+        let shell = match params.shell {
+            Some(shell) if config.shells.contains(&shell) => shell,
+            Some(shell) => return Err(anyhow::anyhow!("shell {} is not allowed", shell)),
+            None => config
+                .shells
+                .first()
+                .cloned()
+                .context("no shells configured")?,
+        };
+
+        if let Some(user) = &params.user {
+            if !config.users.contains(user) {
+                return Err(anyhow::anyhow!("user {} is not allowed", user));
+            }
+        }
+
+        Ok(ExecSession {
+            container,
+            shell,
+            user: params.user,
+            client,
+            max_duration: Duration::from_secs(config.max_session_secs),
+        })
+    }
+
+    fn args(&self) -> Vec<String> {
+        let mut args = vec!["exec".to_string(), "--interactive".into(), "--tty".into()];
+        if let Some(user) = &self.user {
+            args.push("--user".into());
+            args.push(user.clone());
+        }
+        args.push(self.container.clone());
+        args.push(self.shell.clone());
+        args
+    }
+
+    /// Run the session until the shell exits, the browser disconnects or the
+    /// time limit is reached. The start and end are logged to the `audit` target.
+    pub async fn run(self, socket: WebSocket) {
+        let started = Instant::now();
+        tracing::info!(
+            target: "audit",
+            client = %self.client,
+            container = %self.container,
+            user = self.user.as_deref().unwrap_or("default"),
+            shell = %self.shell,
+            "exec session started"
+        );
+
+        let reason = match self.relay(socket).await {
+            Ok(reason) => reason,
+            Err(e) => format!("error: {:#}", e),
+        };
+
+        tracing::info!(
+            target: "audit",
+            client = %self.client,
+            container = %self.container,
+            duration_secs = started.elapsed().as_secs(),
+            "exec session ended: {}",
+            reason
+        );
+    }
+
+    /// Copy between the websocket and the pty, returns why the session ended
+    async fn relay(&self, socket: WebSocket) -> Result<String> {
+        let (pty, pts) = pty_process::open()?;
+        pty.resize(pty_process::Size::new(24, 80))?;
+
+        let mut child = pty_process::Command::new("docker")
+            .args(self.args())
+            .kill_on_drop(true)
+            .spawn(pts)?;
+
+        let (mut pty_read, mut pty_write) = pty.into_split();
+        let (mut ws_tx, mut ws_rx) = socket.split();
+        let deadline = tokio::time::sleep(self.max_duration);
+        tokio::pin!(deadline);
+        let mut buf = [0u8; 4096];
+
+        let reason = loop {
+            tokio::select! {
+                read = pty_read.read(&mut buf) => match read {
+                    // reading the pty fails with EIO once the shell has exited
+                    Ok(0) | Err(_) => break "shell exited".to_string(),
+                    Ok(n) => ws_tx.send(Message::Binary(buf[..n].to_vec())).await?,
+                },
+                msg = ws_rx.next() => match msg {
+                    Some(Ok(Message::Text(text))) => match serde_json::from_str(&text)? {
+                        ClientMessage::Input { data } => pty_write.write_all(data.as_bytes()).await?,
+                        ClientMessage::Resize { cols, rows } => {
+                            pty_write.resize(pty_process::Size::new(rows, cols))?
+                        }
+                    },
+                    Some(Ok(Message::Binary(data))) => pty_write.write_all(&data).await?,
+                    Some(Ok(Message::Close(_))) | None => break "client disconnected".to_string(),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => break format!("websocket error: {}", e),
+                },
+                status = child.wait() => {
+                    break format!("shell exited with {}", status?);
+                }
+                _ = &mut deadline => {
+                    break format!("session time limit of {}s reached", self.max_duration.as_secs());
+                }
+            }
+        };
+
+        // the close reason is shown in the terminal
+        ws_tx
+            .send(Message::Close(Some(CloseFrame {
+                code: axum::extract::ws::close_code::NORMAL,
+                reason: close_reason(&reason).into(),
+            })))
+            .await
+            .ok();
+
+        child.start_kill().ok();
+
+        Ok(reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn check_origin() {
+        let check = super::check_origin;
+        assert!(check(Some("http://localhost:3000"), Some("localhost:3000")).is_ok());
+        assert!(check(
+            Some("https://Docker.example.com"),
+            Some("docker.example.com")
+        )
+        .is_ok());
+        assert!(check(Some("https://evil.example"), Some("localhost:3000")).is_err());
+        assert!(check(Some("http://localhost:3001"), Some("localhost:3000")).is_err());
+        assert!(check(Some("null"), Some("localhost:3000")).is_err());
+        assert!(check(None, Some("localhost:3000")).is_err());
+    }
+
+    #[test]
+    fn close_reason() {
+        assert_eq!(super::close_reason("shell exited"), "shell exited");
+        assert_eq!(super::close_reason(&"x".repeat(200)).len(), 123);
+        // a two byte char straddling the limit is left out
+        let reason = format!("{}é", "x".repeat(122));
+        assert_eq!(super::close_reason(&reason), "x".repeat(122));
+    }
+}
@@ -0,0 +1,831 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{Cursor, Read},
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use flate2::read::GzDecoder;
+use tar::{Archive, EntryType};
+
+use crate::fields::parse_timestamp;
+
+/// Files of an image archive that aren't layers and are kept to be parsed,
+/// the manifest and the image config
+const MAX_METADATA_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Image trees kept after they were read, each read is a `docker save`
+const CACHED_TREES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    Hardlink,
+    Other,
+}
+
+impl EntryKind {
+    fn from_tar(entry_type: EntryType) -> Self {
+        match entry_type {
+            EntryType::Regular | EntryType::Continuous => Self::File,
+            EntryType::Directory => Self::Dir,
+            EntryType::Symlink => Self::Symlink,
+            EntryType::Link => Self::Hardlink,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// How a layer changed a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl Change {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Modified => "modified",
+            Self::Deleted => "deleted",
+        }
+    }
+}
+
+/// A path of a layer's tar, without the leading `./` or `/`
+#[derive(Debug, Clone, PartialEq)]
+struct LayerEntry {
+    path: String,
+    kind: EntryKind,
+    size: u64,
+    link: Option<String>,
+}
+
+/// A layer of the image, in the order they are applied
+#[derive(Debug, Clone, Default)]
+pub struct Layer {
+    /// `sha256:...` of the uncompressed layer
+    pub diff_id: String,
+    /// the Dockerfile instruction that made the layer
+    pub created_by: String,
+    pub created: Option<DateTime<FixedOffset>>,
+    /// bytes of the files in the layer
+    pub size: u64,
+    entries: Vec<LayerEntry>,
+    /// paths removed by whiteouts, `.wh.<name>`
+    whiteouts: Vec<String>,
+    /// directories the lower layers' content of is hidden, `.wh..wh..opq`
+    opaque: Vec<String>,
+    /// what the layer changed in the merged filesystem, with the size of the
+    /// deleted paths, see `ImageTree::replay`
+    changes: BTreeMap<String, (Change, u64)>,
+    /// files of this layer that a later one replaces or deletes, and the index of that layer
+    overwritten: HashMap<String, usize>,
+}
+
+impl Layer {
+    pub fn count(&self, change: Change) -> usize {
+        self.changes.values().filter(|(c, _)| *c == change).count()
+    }
+
+    /// Bytes of the layer's files that later layers hide, they still take up space
+    pub fn wasted(&self) -> u64 {
+        self.entries
+            .iter()
+            .filter(|entry| self.overwritten.contains_key(&entry.path))
+            .map(|entry| entry.size)
+            .sum()
+    }
+}
+
+/// A path of the merged filesystem as seen after a layer
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeEntry {
+    pub name: String,
+    /// without the leading `/`
+    pub path: String,
+    pub kind: EntryKind,
+    /// the file size, or the size of everything below a directory
+    pub size: u64,
+    pub link: Option<String>,
+    /// index of the layer that last changed the path
+    pub layer: usize,
+    /// how the viewed layer changed the path
+    pub change: Option<Change>,
+    /// paths below a directory the viewed layer changed
+    pub changes_below: usize,
+    /// index of a later layer that replaces or deletes the file
+    pub overwritten_by: Option<usize>,
+}
+
+/// A file a later layer hides while it still takes up space in its own
+#[derive(Debug, Clone, PartialEq)]
+pub struct WastedFile {
+    pub path: String,
+    pub size: u64,
+    pub layer: usize,
+    pub overwritten_by: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    kind: EntryKind,
+    size: u64,
+    link: Option<String>,
+    layer: usize,
+}
+
+/// The layers of an image read from `docker save`, for browsing the
+/// filesystem as each layer leaves it
+#[derive(Debug, Clone, Default)]
+pub struct ImageTree {
+    pub id: String,
+    pub layers: Vec<Layer>,
+}
+
+#[derive(serde::Deserialize)]
+struct ArchiveManifest {
+    #[serde(rename = "Config")]
+    config: String,
+    #[serde(rename = "Layers")]
+    layers: Vec<String>,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default)]
+struct ImageConfig {
+    history: Vec<ConfigHistory>,
+    rootfs: RootFs,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default)]
+struct ConfigHistory {
+    created: String,
+    created_by: String,
+    empty_layer: bool,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default)]
+struct RootFs {
+    diff_ids: Vec<String>,
+}
+
+/// Resolve `..` and `.` of `target` relative to the directory `base`
+fn join(base: &str, target: &str) -> String {
+    let mut parts = match target.starts_with('/') {
+        true => vec![],
+        false => base
+            .split('/')
+            .filter(|p| !p.is_empty())
+            .collect::<Vec<_>>(),
+    };
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("")
+}
+
+/// `path` itself and everything below it, the whole map for the root. Siblings
+/// like `doc-base` sort between `doc` and `doc/`, so the range starts at `doc/`.
+fn subtree<'a, V>(
+    map: &'a BTreeMap<String, V>,
+    path: &'a str,
+) -> impl Iterator<Item = (&'a String, &'a V)> {
+    let prefix = match path {
+        "" => String::new(),
+        path => format!("{}/", path),
+    };
+    map.get_key_value(path).into_iter().chain(
+        map.range(prefix.clone()..)
+            .take_while(move |(p, _)| p.starts_with(&prefix)),
+    )
+}
+
+/// Remove `path` and everything below it, or only what is below it with `keep_root`
+fn remove_subtree(
+    map: &mut BTreeMap<String, Node>,
+    path: &str,
+    keep_root: bool,
+) -> Vec<(String, Node)> {
+    let paths = subtree(map, path)
+        .filter(|(p, _)| !(keep_root && p.as_str() == path))
+        .map(|(p, _)| p.clone())
+        .collect::<Vec<_>>();
+    paths
+        .into_iter()
+        .filter_map(|p| map.remove(&p).map(|node| (p, node)))
+        .collect()
+}
+
+/// Record removed paths as deletions of the layer, and files as overwritten in
+/// the layer that added them
+fn record_removed(
+    removed: Vec<(String, Node)>,
+    changes: &mut BTreeMap<String, (Change, u64)>,
+    overwritten: &mut Vec<(usize, String)>,
+) {
+    for (path, node) in removed {
+        if node.kind == EntryKind::File {
+            overwritten.push((node.layer, path.clone()));
+        }
+        changes.insert(path, (Change::Deleted, node.size));
+    }
+}
+
+/// The entries of a layer's tar, as it streams by
+fn read_layer(reader: impl Read) -> Result<Layer> {
+    let mut layer = Layer::default();
+
+    for entry in Archive::new(reader).entries()? {
+        let entry = entry?;
+        let path = join("", &entry.path()?.to_string_lossy());
+        if path.is_empty() {
+            continue;
+        }
+
+        let (dir, name) = path.rsplit_once('/').unwrap_or(("", &path));
+        if name == ".wh..wh..opq" {
+            layer.opaque.push(dir.to_string());
+            continue;
+        }
+        if let Some(name) = name.strip_prefix(".wh.") {
+            layer.whiteouts.push(join(dir, name));
+            continue;
+        }
+
+        let kind = EntryKind::from_tar(entry.header().entry_type());
+        let size = match kind {
+            EntryKind::File => entry.header().size()?,
+            _ => 0,
+        };
+        layer.size += size;
+        layer.entries.push(LayerEntry {
+            path,
+            kind,
+            size,
+            link: entry
+                .link_name()?
+                .map(|link| link.to_string_lossy().to_string()),
+        });
+    }
+
+    Ok(layer)
+}
+
+impl ImageTree {
+    /// `docker save` the image and read the layers as the archive streams by,
+    /// only the file metadata is kept
+    pub fn load(id: &str) -> Result<ImageTree> {
+        let mut child = Command::new("docker")
+            .arg("save")
+            .arg(id)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let tree = Self::read(child.stdout.take().context("docker save: no stdout")?);
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "docker save: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let mut tree = tree?;
+        tree.id = id.to_string();
+        Ok(tree)
+    }
+
+    /// Read an image archive. Layers come before the manifest that orders
+    /// them, so each is read as it is found and put in place at the end.
+    pub fn read(archive: impl Read) -> Result<ImageTree> {
+        let mut layers = HashMap::<String, Layer>::new();
+        let mut metadata = HashMap::<String, Vec<u8>>::new();
+        // the legacy format links layers that are in the image twice
+        let mut links = HashMap::<String, String>::new();
+
+        for entry in Archive::new(archive).entries()? {
+            let mut entry = entry?;
+            let path = join("", &entry.path()?.to_string_lossy());
+
+            match entry.header().entry_type() {
+                EntryType::Symlink => {
+                    if let Some(target) = entry.link_name()? {
+                        links.insert(path.clone(), join(parent(&path), &target.to_string_lossy()));
+                    }
+                }
+                EntryType::Regular | EntryType::Continuous => {
+                    let mut head = Vec::with_capacity(512);
+                    (&mut entry).take(512).read_to_end(&mut head)?;
+
+                    let gzip = head.starts_with(&[0x1f, 0x8b]);
+                    let tar = head.get(257..262) == Some(b"ustar".as_slice())
+                        // an empty layer is only the end of archive zeros
+                        || (head.len() == 512 && head.iter().all(|b| *b == 0));
+
+                    if gzip {
+                        let reader = GzDecoder::new(Cursor::new(head).chain(entry));
+                        let layer = read_layer(reader)
+                            .with_context(|| format!("failed to read layer {}", path))?;
+                        layers.insert(path, layer);
+                    } else if tar {
+                        let layer = read_layer(Cursor::new(head).chain(entry))
+                            .with_context(|| format!("failed to read layer {}", path))?;
+                        layers.insert(path, layer);
+                    } else if entry.size() <= MAX_METADATA_SIZE {
+                        let mut data = head;
+                        entry.read_to_end(&mut data)?;
+                        metadata.insert(path, data);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let resolve = |path: &str| {
+            let path = join("", path);
+            links.get(&path).cloned().unwrap_or(path)
+        };
+
+        let manifest = metadata
+            .get("manifest.json")
+            .context("not an image archive, there is no manifest.json")?;
+        let manifest = serde_json::from_slice::<Vec<ArchiveManifest>>(manifest)
+            .context("failed to parse manifest.json")?
+            .into_iter()
+            .next()
+            .context("manifest.json lists no image")?;
+        let config = match metadata.get(&resolve(&manifest.config)) {
+            Some(config) => {
+                serde_json::from_slice(config).context("failed to parse the image config")?
+            }
+            None => ImageConfig::default(),
+        };
+        let history = config
+            .history
+            .into_iter()
+            .filter(|h| !h.empty_layer)
+            .collect::<Vec<_>>();
+
+        let layers = manifest
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                let mut layer = layers
+                    .get(&resolve(path))
+                    .cloned()
+                    .with_context(|| format!("layer {} is missing from the archive", path))?;
+                layer.diff_id = config.rootfs.diff_ids.get(i).cloned().unwrap_or_default();
+                if let Some(history) = history.get(i) {
+                    layer.created_by = history.created_by.clone();
+                    layer.created = parse_timestamp(&history.created);
+                }
+                Ok(layer)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut tree = ImageTree {
+            id: String::new(),
+            layers,
+        };
+        let mut replayed = vec![];
+        tree.replay(
+            tree.layers.len().saturating_sub(1),
+            |index, changes, overwritten| replayed.push((index, changes, overwritten)),
+        );
+        for (index, changes, overwritten) in replayed {
+            tree.layers[index].changes = changes;
+            for (lower, path) in overwritten {
+                tree.layers[lower].overwritten.entry(path).or_insert(index);
+            }
+        }
+        Ok(tree)
+    }
+
+    /// Apply the layers up to the one at `last` and return the merged
+    /// filesystem, `on_layer` gets what each layer changed and the files of
+    /// lower layers it overwrote
+    fn replay(
+        &self,
+        last: usize,
+        mut on_layer: impl FnMut(usize, BTreeMap<String, (Change, u64)>, Vec<(usize, String)>),
+    ) -> BTreeMap<String, Node> {
+        let mut merged = BTreeMap::<String, Node>::new();
+
+        for index in 0..self.layers.len().min(last + 1) {
+            let mut changes = BTreeMap::new();
+            let mut overwritten = vec![];
+
+            let layer = &self.layers[index];
+            for dir in layer.opaque.iter() {
+                record_removed(
+                    remove_subtree(&mut merged, dir, true),
+                    &mut changes,
+                    &mut overwritten,
+                );
+            }
+            for path in layer.whiteouts.iter() {
+                record_removed(
+                    remove_subtree(&mut merged, path, false),
+                    &mut changes,
+                    &mut overwritten,
+                );
+            }
+            for entry in layer.entries.iter() {
+                let change = match merged.get(&entry.path) {
+                    // parent directories are repeated in the layers that change their content
+                    Some(node) if node.kind == EntryKind::Dir && entry.kind == EntryKind::Dir => {
+                        continue
+                    }
+                    Some(node) => {
+                        if node.kind == EntryKind::Dir {
+                            record_removed(
+                                remove_subtree(&mut merged, &entry.path, true),
+                                &mut changes,
+                                &mut overwritten,
+                            );
+                        } else if node.kind == EntryKind::File {
+                            overwritten.push((node.layer, entry.path.clone()));
+                        }
+                        Change::Modified
+                    }
+                    None => Change::Added,
+                };
+                changes.insert(entry.path.clone(), (change, entry.size));
+                merged.insert(
+                    entry.path.clone(),
+                    Node {
+                        kind: entry.kind,
+                        size: entry.size,
+                        link: entry.link.clone(),
+                        layer: index,
+                    },
+                );
+            }
+
+            on_layer(index, changes, overwritten);
+        }
+
+        merged
+    }
+
+    /// The entries of a directory as the layer at `index` leaves it, and
+    /// the ones it deleted there. `dir` is empty for the root.
+    pub fn list(&self, index: usize, dir: &str) -> Vec<TreeEntry> {
+        let Some(layer) = self.layers.get(index) else {
+            return vec![];
+        };
+        let merged = self.replay(index, |_, _, _| {});
+        let dir = join("", dir);
+        let prefix = if dir.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", dir)
+        };
+
+        let mut entries = BTreeMap::<String, TreeEntry>::new();
+        for (path, node) in merged.range(prefix.clone()..) {
+            let Some(rest) = path.strip_prefix(&prefix) else {
+                break;
+            };
+            if rest.is_empty() {
+                continue;
+            }
+            let (name, below) = match rest.split_once('/') {
+                Some((name, _)) => (name, true),
+                None => (rest, false),
+            };
+
+            let entry = entries
+                .entry(name.to_string())
+                .or_insert_with(|| TreeEntry {
+                    name: name.to_string(),
+                    path: format!("{}{}", prefix, name),
+                    // layers don't always have entries for the parent directories
+                    kind: EntryKind::Dir,
+                    size: 0,
+                    link: None,
+                    layer: node.layer,
+                    change: None,
+                    changes_below: 0,
+                    overwritten_by: None,
+                });
+            if below {
+                entry.size += node.size;
+                if node.layer == index {
+                    entry.changes_below += 1;
+                }
+            } else {
+                entry.kind = node.kind;
+                entry.size += node.size;
+                entry.link = node.link.clone();
+                entry.layer = node.layer;
+                entry.change = layer.changes.get(path).map(|(change, _)| *change);
+                entry.overwritten_by = self.layers[node.layer].overwritten.get(path).copied();
+            }
+        }
+
+        // deletions below the directories that are left
+        for (path, (change, _)) in layer.changes.range(prefix.clone()..) {
+            let Some(rest) = path.strip_prefix(&prefix) else {
+                break;
+            };
+            if let (Change::Deleted, Some((name, _))) = (change, rest.split_once('/')) {
+                if let Some(entry) = entries.get_mut(name) {
+                    entry.changes_below += 1;
+                }
+            }
+        }
+
+        for (path, (change, size)) in layer.changes.iter() {
+            if *change != Change::Deleted || parent(path) != dir {
+                continue;
+            }
+            let name = path.rsplit('/').next().unwrap_or(path);
+            let deleted_below = layer
+                .changes
+                .range(format!("{}/", path)..)
+                .take_while(|(p, _)| p.starts_with(&format!("{}/", path)))
+                .collect::<Vec<_>>();
+            entries.insert(
+                name.to_string(),
+                TreeEntry {
+                    name: name.to_string(),
+                    path: path.clone(),
+                    kind: if deleted_below.is_empty() {
+                        EntryKind::File
+                    } else {
+                        EntryKind::Dir
+                    },
+                    size: size + deleted_below.iter().map(|(_, (_, size))| size).sum::<u64>(),
+                    link: None,
+                    layer: index,
+                    change: Some(Change::Deleted),
+                    changes_below: deleted_below.len(),
+                    overwritten_by: None,
+                },
+            );
+        }
+
+        let mut entries = entries.into_values().collect::<Vec<_>>();
+        entries.sort_by(|a, b| {
+            (b.kind == EntryKind::Dir)
+                .cmp(&(a.kind == EntryKind::Dir))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        entries
+    }
+
+    /// Files that later layers replace or delete, largest first
+    pub fn wasted(&self) -> Vec<WastedFile> {
+        let mut wasted = self
+            .layers
+            .iter()
+            .enumerate()
+            .flat_map(|(index, layer)| {
+                layer.entries.iter().filter_map(move |entry| {
+                    let overwritten_by = *layer.overwritten.get(&entry.path)?;
+                    (entry.size > 0).then(|| WastedFile {
+                        path: entry.path.clone(),
+                        size: entry.size,
+                        layer: index,
+                        overwritten_by,
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        wasted.sort_by_key(|file| std::cmp::Reverse(file.size));
+        wasted
+    }
+}
+
+/// The image trees read last, browsing a tree doesn't save the image again
+#[derive(Default)]
+pub struct TreeCache {
+    trees: Mutex<Vec<Arc<ImageTree>>>,
+}
+
+impl TreeCache {
+    /// The tree of the image, read with `ImageTree::load` when it isn't cached
+    pub async fn get(&self, id: &str) -> Result<Arc<ImageTree>> {
+        let cached = self.trees().iter().find(|tree| tree.id == id).cloned();
+        if let Some(tree) = cached {
+            return Ok(tree);
+        }
+
+        let load_id = id.to_string();
+        let tree = Arc::new(tokio::task::spawn_blocking(move || ImageTree::load(&load_id)).await??);
+
+        let mut trees = self.trees();
+        trees.retain(|cached| cached.id != id);
+        trees.insert(0, tree.clone());
+        trees.truncate(CACHED_TREES);
+        Ok(tree)
+    }
+
+    fn trees(&self) -> std::sync::MutexGuard<'_, Vec<Arc<ImageTree>>> {
+        self.trees.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(entries: &[(&str, Option<&[u8]>)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            match data {
+                Some(data) => {
+                    header.set_entry_type(EntryType::Regular);
+                    header.set_size(data.len() as u64);
+                    header.set_mode(0o644);
+                    builder.append_data(&mut header, path, *data).unwrap();
+                }
+                None => {
+                    header.set_entry_type(EntryType::Directory);
+                    header.set_size(0);
+                    header.set_mode(0o755);
+                    builder.append_data(&mut header, path, &[][..]).unwrap();
+                }
+            }
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn archive() -> Vec<u8> {
+        let base = layer(&[
+            ("etc/", None),
+            ("etc/config", Some(&[0; 10])),
+            ("big", Some(&[0; 100])),
+            ("cache/", None),
+            ("cache/a", Some(&[0; 7])),
+        ]);
+        let change = layer(&[
+            ("etc/", None),
+            ("etc/config", Some(&[0; 5])),
+            (".wh.big", Some(&[])),
+            ("cache/", None),
+            ("cache/.wh..wh..opq", Some(&[])),
+            ("cache/b", Some(&[0; 3])),
+            ("new", Some(&[0; 3])),
+        ]);
+        image(&base, &change)
+    }
+
+    /// A `docker save` archive of two layers
+    fn image(base: &[u8], change: &[u8]) -> Vec<u8> {
+        let config = r#"{"history":[{"created":"2024-03-01T10:00:00Z","created_by":"ADD rootfs.tar /"},{"created_by":"ENV A=1","empty_layer":true},{"created":"2024-03-02T10:00:00Z","created_by":"RUN ./change.sh"}],"rootfs":{"type":"layers","diff_ids":["sha256:base","sha256:change"]}}"#;
+        let manifest = r#"[{"Config":"blobs/sha256/config","RepoTags":["app:1"],"Layers":["blobs/sha256/base","blobs/sha256/change"]}]"#;
+
+        let mut builder = tar::Builder::new(vec![]);
+        // the layers before the manifest, as docker writes them
+        let files: [(&str, &[u8]); 4] = [
+            ("blobs/sha256/base", base),
+            ("blobs/sha256/change", change),
+            ("blobs/sha256/config", config.as_bytes()),
+            ("manifest.json", manifest.as_bytes()),
+        ];
+        for (path, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, path, data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn layers() {
+        let tree = ImageTree::read(Cursor::new(archive())).unwrap();
+
+        let [base, change] = &tree.layers[..] else {
+            panic!("expected two layers");
+        };
+        assert_eq!(base.created_by, "ADD rootfs.tar /");
+        assert_eq!(change.created_by, "RUN ./change.sh");
+        assert_eq!(change.diff_id, "sha256:change");
+        assert_eq!(base.size, 117);
+        assert_eq!(base.wasted(), 117);
+        assert_eq!(change.count(Change::Added), 2);
+        assert_eq!(change.count(Change::Modified), 1);
+        assert_eq!(change.count(Change::Deleted), 2);
+
+        assert_eq!(
+            tree.wasted()
+                .iter()
+                .map(|f| (f.path.as_str(), f.overwritten_by))
+                .collect::<Vec<_>>(),
+            vec![("big", 1), ("etc/config", 1), ("cache/a", 1)]
+        );
+    }
+
+    #[test]
+    fn list() {
+        let tree = ImageTree::read(Cursor::new(archive())).unwrap();
+
+        let root = tree.list(0, "");
+        let names = root.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["cache", "etc", "big"]);
+        assert_eq!(root[2].overwritten_by, Some(1));
+        assert_eq!(root[2].change, Some(Change::Added));
+
+        let root = tree.list(1, "");
+        let big = root.iter().find(|e| e.name == "big").unwrap();
+        assert_eq!(big.change, Some(Change::Deleted));
+        assert_eq!(big.size, 100);
+        let etc = root.iter().find(|e| e.name == "etc").unwrap();
+        assert_eq!((etc.size, etc.changes_below), (5, 1));
+        let cache = root.iter().find(|e| e.name == "cache").unwrap();
+        assert_eq!(cache.changes_below, 2);
+
+        let cache = tree.list(1, "/cache");
+        assert_eq!(
+            cache
+                .iter()
+                .map(|e| (e.name.as_str(), e.change))
+                .collect::<Vec<_>>(),
+            vec![("a", Some(Change::Deleted)), ("b", Some(Change::Added))]
+        );
+    }
+
+    #[test]
+    fn whiteout_with_sibling() {
+        let base = layer(&[
+            ("usr/", None),
+            ("usr/share/", None),
+            ("usr/share/doc/", None),
+            ("usr/share/doc/README", Some(&[0; 10])),
+            ("usr/share/doc-base/", None),
+            ("usr/share/doc-base/index", Some(&[0; 4])),
+        ]);
+        let change = layer(&[
+            ("usr/", None),
+            ("usr/share/", None),
+            ("usr/share/.wh.doc", Some(&[])),
+        ]);
+        let tree = ImageTree::read(Cursor::new(image(&base, &change))).unwrap();
+
+        assert_eq!(tree.layers[1].count(Change::Deleted), 2);
+        assert_eq!(
+            tree.wasted()
+                .iter()
+                .map(|f| (f.path.as_str(), f.overwritten_by))
+                .collect::<Vec<_>>(),
+            vec![("usr/share/doc/README", 1)]
+        );
+        let share = tree.list(1, "/usr/share");
+        assert_eq!(
+            share
+                .iter()
+                .map(|e| (e.name.as_str(), e.change))
+                .collect::<Vec<_>>(),
+            vec![("doc", Some(Change::Deleted)), ("doc-base", None)]
+        );
+    }
+
+    #[test]
+    fn subtree() {
+        let map = ["a", "a-b", "a.c", "a/b", "a/b/c", "ab"]
+            .into_iter()
+            .map(|p| (p.to_string(), ()))
+            .collect::<BTreeMap<_, _>>();
+        let paths = |path| {
+            super::subtree(&map, path)
+                .map(|(p, _)| p.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(paths("a"), vec!["a", "a/b", "a/b/c"]);
+        assert_eq!(paths("a/b"), vec!["a/b", "a/b/c"]);
+        assert_eq!(paths("").len(), 6);
+    }
+
+    #[test]
+    fn join() {
+        assert_eq!(super::join("", "./usr/bin/"), "usr/bin");
+        assert_eq!(super::join("abc", "../def/layer.tar"), "def/layer.tar");
+        assert_eq!(super::join("a/b", "/etc"), "etc");
+    }
+}
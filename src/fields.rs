@@ -0,0 +1,470 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+};
+
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::{Deserialize, Deserializer};
+
+use crate::util::{format_bytes, parse_bytes};
+
+/// A published or exposed port from the `Ports` column of `docker ps`,
+/// e.g. `0.0.0.0:8080->80/tcp` or `5432/tcp`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Port {
+    /// not set for ports that are only exposed
+    pub host_ip: Option<String>,
+    pub host_port: Option<u16>,
+    pub container_port: u16,
+    pub protocol: String,
+}
+
+impl Port {
+    /// Whether the port is published on every interface of the host
+    pub fn is_wildcard(&self) -> bool {
+        matches!(self.host_ip.as_deref(), Some("0.0.0.0" | "::"))
+    }
+}
+
+impl Display for Port {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (&self.host_ip, self.host_port) {
+            (Some(ip), Some(port)) if ip.contains(':') => write!(
+                f,
+                "[{}]:{}->{}/{}",
+                ip, port, self.container_port, self.protocol
+            ),
+            (Some(ip), Some(port)) => {
+                write!(
+                    f,
+                    "{}:{}->{}/{}",
+                    ip, port, self.container_port, self.protocol
+                )
+            }
+            _ => write!(f, "{}/{}", self.container_port, self.protocol),
+        }
+    }
+}
+
+/// `80` or a range like `8000-8010`
+fn parse_port_range(s: &str) -> Option<Vec<u16>> {
+    match s.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (start.parse::<u16>().ok()?, end.parse::<u16>().ok()?);
+            (start <= end).then(|| (start..=end).collect())
+        }
+        None => Some(vec![s.parse().ok()?]),
+    }
+}
+
+/// Parse the `Ports` column, port ranges are expanded to a `Port` each
+pub fn parse_ports(s: &str) -> Vec<Port> {
+    let mut ports = vec![];
+
+    for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (host, container) = match entry.split_once("->") {
+            Some((host, container)) => (Some(host), container),
+            None => (None, entry),
+        };
+        let (container, protocol) = container.split_once('/').unwrap_or((container, "tcp"));
+        let Some(container_ports) = parse_port_range(container) else {
+            continue;
+        };
+
+        let host = host.and_then(|host| {
+            // the ip is the part before the last colon, ipv6 may be in brackets
+            let (ip, port) = host.rsplit_once(':')?;
+            let ip = ip.trim_start_matches('[').trim_end_matches(']');
+            Some((ip.to_string(), parse_port_range(port)?))
+        });
+
+        for (i, container_port) in container_ports.iter().enumerate() {
+            let (host_ip, host_port) = match &host {
+                Some((ip, host_ports)) => (Some(ip.clone()), host_ports.get(i).copied()),
+                None => (None, None),
+            };
+            ports.push(Port {
+                host_ip,
+                host_port,
+                container_port: *container_port,
+                protocol: protocol.to_string(),
+            });
+        }
+    }
+
+    ports
+}
+
+/// Parse the `Labels` column. `docker ps` joins labels as `key=value,key=value`,
+/// so a segment without `=` belongs to the previous value (e.g. multiple config files).
+pub fn parse_labels(s: &str) -> BTreeMap<String, String> {
+    let mut labels = BTreeMap::new();
+    let mut last: Option<String> = None;
+
+    for segment in s.split(',').filter(|segment| !segment.is_empty()) {
+        match segment.split_once('=') {
+            Some((key, value)) => {
+                labels.insert(key.to_string(), value.to_string());
+                last = Some(key.to_string());
+            }
+            None => {
+                if let Some(value) = last.as_ref().and_then(|key| labels.get_mut(key)) {
+                    value.push(',');
+                    value.push_str(segment);
+                }
+            }
+        }
+    }
+
+    labels
+}
+
+/// Comma separated columns such as `Mounts` and `Networks`
+pub fn parse_list(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|val| !val.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Sizes the cli prints as `N/A` when unknown
+pub fn parse_optional_bytes(s: &str) -> Option<u64> {
+    match s.trim() {
+        "" | "N/A" => None,
+        s => parse_bytes(s),
+    }
+}
+
+/// Writable layer and total size of a container, from the `Size` column,
+/// e.g. `63B (virtual 432MB)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContainerSize {
+    pub rw: u64,
+    pub virtual_size: Option<u64>,
+}
+
+impl ContainerSize {
+    pub fn parse(s: &str) -> Option<ContainerSize> {
+        let (rw, virtual_size) = match s.split_once('(') {
+            Some((rw, rest)) => {
+                let virtual_size = rest
+                    .trim_end_matches(')')
+                    .trim()
+                    .trim_start_matches("virtual")
+                    .trim();
+                (rw, parse_optional_bytes(virtual_size))
+            }
+            None => (s, None),
+        };
+
+        Some(ContainerSize {
+            rw: parse_optional_bytes(rw)?,
+            virtual_size,
+        })
+    }
+}
+
+impl Display for ContainerSize {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.virtual_size {
+            Some(virtual_size) => write!(
+                f,
+                "{} (virtual {})",
+                format_bytes(self.rw),
+                format_bytes(virtual_size)
+            ),
+            None => write!(f, "{}", format_bytes(self.rw)),
+        }
+    }
+}
+
+/// Parse the cli timestamps, e.g. `2024-03-01 10:00:00 +0000 UTC`
+pub fn parse_timestamp(s: &str) -> Option<DateTime<FixedOffset>> {
+    // the trailing zone abbreviation is redundant with the offset, and not
+    // always letters, go prints e.g. `+04` for zones without a name
+    let parts = s.split_whitespace().collect::<Vec<_>>();
+    let s = match parts.len() {
+        4 => parts[..3].join(" "),
+        _ => s.trim().to_string(),
+    };
+    let s = s.as_str();
+
+    DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S %z")
+        .or_else(|_| DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f %z"))
+        .or_else(|_| DateTime::parse_from_rfc3339(s))
+        .ok()
+}
+
+/// e.g. `5 minutes ago` or `3 weeks ago`
+pub fn format_relative(time: &DateTime<FixedOffset>, now: DateTime<Utc>) -> String {
+    let secs = (now - time.with_timezone(&Utc)).num_seconds();
+    if secs < 0 {
+        return "in the future".to_string();
+    }
+
+    let (value, unit) = match secs {
+        0..=59 => return "just now".to_string(),
+        60..=3_599 => (secs / 60, "minute"),
+        3_600..=86_399 => (secs / 3_600, "hour"),
+        86_400..=1_209_599 => (secs / 86_400, "day"),
+        1_209_600..=5_183_999 => (secs / 604_800, "week"),
+        5_184_000..=31_535_999 => (secs / 2_592_000, "month"),
+        _ => (secs / 31_536_000, "year"),
+    };
+
+    let plural = if value == 1 { "" } else { "s" };
+    format!("{} {}{} ago", value, unit, plural)
+}
+
+/// Healthcheck state from the `Status` column, e.g. `Up 2 hours (healthy)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HealthStatus {
+    Starting,
+    Healthy,
+    Unhealthy,
+}
+
+impl HealthStatus {
+    pub fn from_status(status: &str) -> Option<HealthStatus> {
+        if status.ends_with("(health: starting)") {
+            Some(Self::Starting)
+        } else if status.ends_with("(unhealthy)") {
+            Some(Self::Unhealthy)
+        } else if status.ends_with("(healthy)") {
+            Some(Self::Healthy)
+        } else {
+            None
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Starting => "starting",
+            Self::Healthy => "healthy",
+            Self::Unhealthy => "unhealthy",
+        }
+    }
+}
+
+/// Field deserializers for the cli's json output, which formats everything as strings
+pub mod de {
+    use super::*;
+
+    pub fn ports<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Port>, D::Error> {
+        Ok(parse_ports(&String::deserialize(deserializer)?))
+    }
+
+    pub fn labels<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<String, String>, D::Error> {
+        Ok(parse_labels(&String::deserialize(deserializer)?))
+    }
+
+    pub fn list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+        Ok(parse_list(&String::deserialize(deserializer)?))
+    }
+
+    pub fn optional_bytes<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        Ok(parse_optional_bytes(&String::deserialize(deserializer)?))
+    }
+
+    pub fn bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        parse_bytes(&s).ok_or_else(|| serde::de::Error::custom(format!("invalid size {}", s)))
+    }
+
+    pub fn container_size<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<ContainerSize>, D::Error> {
+        Ok(ContainerSize::parse(&String::deserialize(deserializer)?))
+    }
+
+    pub fn timestamp<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<FixedOffset>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        parse_timestamp(&s)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid timestamp {}", s)))
+    }
+
+    /// Counts the cli prints as `N/A` when unknown
+    pub fn optional_count<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        Ok(String::deserialize(deserializer)?.trim().parse().ok())
+    }
+
+    /// `<none>` for untagged images
+    pub fn none_as_empty<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<String>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok((s != "<none>" && !s.is_empty()).then_some(s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Timelike;
+
+    #[test]
+    fn ports() {
+        let ports = parse_ports("0.0.0.0:8080->80/tcp, :::8080->80/tcp, 5432/tcp");
+        assert_eq!(
+            ports,
+            vec![
+                Port {
+                    host_ip: Some("0.0.0.0".into()),
+                    host_port: Some(8080),
+                    container_port: 80,
+                    protocol: "tcp".into(),
+                },
+                Port {
+                    host_ip: Some("::".into()),
+                    host_port: Some(8080),
+                    container_port: 80,
+                    protocol: "tcp".into(),
+                },
+                Port {
+                    host_ip: None,
+                    host_port: None,
+                    container_port: 5432,
+                    protocol: "tcp".into(),
+                },
+            ]
+        );
+        assert!(ports[0].is_wildcard());
+        assert_eq!(ports[1].to_string(), "[::]:8080->80/tcp");
+    }
+
+    #[test]
+    fn port_ranges_and_brackets() {
+        let ports = parse_ports("127.0.0.1:8000-8001->9000-9001/udp, [::]:53->53/udp");
+        let published = ports
+            .iter()
+            .map(|p| {
+                (
+                    p.host_ip.clone().unwrap(),
+                    p.host_port.unwrap(),
+                    p.container_port,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            published,
+            vec![
+                ("127.0.0.1".to_string(), 8000, 9000),
+                ("127.0.0.1".to_string(), 8001, 9001),
+                ("::".to_string(), 53, 53),
+            ]
+        );
+        assert!(ports.iter().all(|p| p.protocol == "udp"));
+        assert!(parse_ports("").is_empty());
+    }
+
+    #[test]
+    fn labels_with_commas() {
+        let labels = parse_labels(
+            "com.docker.compose.project=app,com.docker.compose.project.config_files=/srv/app/compose.yml,/srv/app/override.yml,com.docker.compose.service=db",
+        );
+        assert_eq!(labels.len(), 3);
+        assert_eq!(
+            labels["com.docker.compose.project.config_files"],
+            "/srv/app/compose.yml,/srv/app/override.yml"
+        );
+        assert_eq!(labels["com.docker.compose.service"], "db");
+        assert!(parse_labels("").is_empty());
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(
+            ContainerSize::parse("63B (virtual 432MB)"),
+            Some(ContainerSize {
+                rw: 63,
+                virtual_size: Some(432_000_000),
+            })
+        );
+        assert_eq!(
+            ContainerSize::parse("1.09kB"),
+            Some(ContainerSize {
+                rw: 1_090,
+                virtual_size: None,
+            })
+        );
+        assert_eq!(ContainerSize::parse("N/A"), None);
+        assert_eq!(parse_bytes("1.944GiB"), Some(2_087_354_106));
+        assert_eq!(format_bytes(187_000_000), "187.0MB");
+    }
+
+    #[test]
+    fn timestamps() {
+        let time = parse_timestamp("2024-03-01 10:00:00 +0000 UTC").unwrap();
+        assert_eq!(time.to_rfc3339(), "2024-03-01T10:00:00+00:00");
+
+        let time = parse_timestamp("2024-03-01 11:00:00 +0100 CET").unwrap();
+        assert_eq!(
+            time.with_timezone(&Utc).to_rfc3339(),
+            "2024-03-01T10:00:00+00:00"
+        );
+
+        // zones without a name, e.g. Asia/Dubai, are abbreviated by their offset
+        let time = parse_timestamp("2024-03-01 14:00:00 +0400 +04").unwrap();
+        assert_eq!(
+            time.with_timezone(&Utc).to_rfc3339(),
+            "2024-03-01T10:00:00+00:00"
+        );
+        let time = parse_timestamp("2024-03-01 07:00:00.5 -0300 -03").unwrap();
+        assert_eq!(time.with_timezone(&Utc).hour(), 10);
+
+        assert!(parse_timestamp("yesterday").is_none());
+    }
+
+    #[test]
+    fn relative_times() {
+        let time = parse_timestamp("2024-03-01 10:00:00 +0000 UTC").unwrap();
+        let at = |s: &str| parse_timestamp(s).unwrap().with_timezone(&Utc);
+
+        assert_eq!(
+            format_relative(&time, at("2024-03-01 10:00:30 +0000 UTC")),
+            "just now"
+        );
+        assert_eq!(
+            format_relative(&time, at("2024-03-01 10:01:00 +0000 UTC")),
+            "1 minute ago"
+        );
+        assert_eq!(
+            format_relative(&time, at("2024-03-01 15:00:00 +0000 UTC")),
+            "5 hours ago"
+        );
+        assert_eq!(
+            format_relative(&time, at("2024-03-15 10:00:00 +0000 UTC")),
+            "2 weeks ago"
+        );
+        assert_eq!(
+            format_relative(&time, at("2025-03-02 10:00:00 +0000 UTC")),
+            "1 year ago"
+        );
+    }
+
+    #[test]
+    fn health() {
+        assert_eq!(
+            HealthStatus::from_status("Up 2 weeks (healthy)"),
+            Some(HealthStatus::Healthy)
+        );
+        assert_eq!(
+            HealthStatus::from_status("Up 3 seconds (health: starting)"),
+            Some(HealthStatus::Starting)
+        );
+        assert_eq!(
+            HealthStatus::from_status("Up 5 minutes (unhealthy)"),
+            Some(HealthStatus::Unhealthy)
+        );
+        assert_eq!(HealthStatus::from_status("Exited (0) 3 days ago"), None);
+    }
+}
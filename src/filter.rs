@@ -0,0 +1,289 @@
+use std::cmp::Ordering;
+
+use crate::{container::Container, image::Image, project::Project, util::empty_as_none};
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// Case insensitive substring match of the search box against any of the fields
+fn search(q: &Option<String>, fields: &[&str]) -> bool {
+    match q {
+        None => true,
+        Some(q) => {
+            let q = q.to_lowercase();
+            fields.iter().any(|field| field.to_lowercase().contains(&q))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn apply(self, ordering: Ordering) -> Ordering {
+        match self {
+            Self::Asc => ordering,
+            Self::Desc => ordering.reverse(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerSort {
+    #[default]
+    Name,
+    State,
+    Created,
+    Size,
+    Image,
+}
+
+impl ContainerSort {
+    pub const ALL: [ContainerSort; 5] = [
+        Self::Name,
+        Self::State,
+        Self::Created,
+        Self::Size,
+        Self::Image,
+    ];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::State => "state",
+            Self::Created => "created",
+            Self::Size => "size",
+            Self::Image => "image",
+        }
+    }
+}
+
+/// Sort and filters of the containers page, kept in the query string so a
+/// view can be bookmarked
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ContainerFilter {
+    /// searched in the name, image, id and project
+    #[serde(
+        deserialize_with = "empty_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub q: Option<String>,
+    #[serde(skip_serializing_if = "is_default")]
+    pub sort: ContainerSort,
+    #[serde(skip_serializing_if = "is_default")]
+    pub order: SortOrder,
+    #[serde(
+        deserialize_with = "empty_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub state: Option<String>,
+    #[serde(
+        deserialize_with = "empty_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub project: Option<String>,
+    /// a health status, or `none` for containers without a healthcheck
+    #[serde(
+        deserialize_with = "empty_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub health: Option<String>,
+    #[serde(skip_serializing_if = "is_default")]
+    pub has_update: bool,
+}
+
+pub const CONTAINER_STATES: [&str; 7] = [
+    "running",
+    "exited",
+    "paused",
+    "restarting",
+    "created",
+    "removing",
+    "dead",
+];
+
+pub const HEALTH_FILTERS: [&str; 4] = ["healthy", "unhealthy", "starting", "none"];
+
+impl ContainerFilter {
+    /// e.g. `?sort=created&order=desc`, empty when nothing is set
+    pub fn query(&self) -> String {
+        match serde_urlencoded::to_string(self) {
+            Ok(query) if !query.is_empty() => format!("?{}", query),
+            _ => String::new(),
+        }
+    }
+
+    pub fn matches(&self, c: &Container) -> bool {
+        let project = c.project().unwrap_or_default();
+
+        search(&self.q, &[&c.names, &c.image, &c.id, &project])
+            && self.state.as_ref().is_none_or(|state| *state == c.state)
+            && self.project.as_ref().is_none_or(|p| *p == project)
+            && self
+                .health
+                .as_deref()
+                .is_none_or(|health| c.health().map_or("none", |status| status.to_str()) == health)
+            && (!self.has_update || c.has_update())
+    }
+
+    /// The matching containers in the selected order
+    pub fn apply(&self, containers: Vec<Container>) -> Vec<Container> {
+        let mut containers = containers
+            .into_iter()
+            .filter(|c| self.matches(c))
+            .collect::<Vec<_>>();
+
+        containers.sort_by(|a, b| {
+            let ordering = match self.sort {
+                ContainerSort::Name => Ordering::Equal,
+                ContainerSort::State => a.state.cmp(&b.state),
+                ContainerSort::Created => a.created_at.cmp(&b.created_at),
+                ContainerSort::Size => a.size.map(|s| s.rw).cmp(&b.size.map(|s| s.rw)),
+                ContainerSort::Image => a.image.cmp(&b.image),
+            };
+            self.order
+                .apply(ordering.then_with(|| a.names.cmp(&b.names)))
+        });
+
+        containers
+    }
+
+    /// The matching compose projects and standalone containers. Projects and
+    /// services are ordered by name, or by their first container for the other sorts.
+    pub fn group(&self, containers: Vec<Container>) -> (Vec<Project>, Vec<Container>) {
+        let containers = self.apply(containers);
+        let standalone = containers
+            .iter()
+            .filter(|c| c.is_standalone())
+            .cloned()
+            .collect();
+        let mut projects = Project::group(containers.clone());
+
+        if self.sort == ContainerSort::Name {
+            if self.order == SortOrder::Desc {
+                projects.reverse();
+                projects.iter_mut().for_each(|p| p.services.reverse());
+            }
+        } else {
+            let position = |c: &Container| containers.iter().position(|other| other.id == c.id);
+            for p in projects.iter_mut() {
+                p.services
+                    .sort_by_key(|s| s.containers.iter().filter_map(position).min());
+            }
+            projects.sort_by_key(|p| p.containers().filter_map(position).min());
+        }
+
+        (projects, standalone)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageSort {
+    #[default]
+    Repository,
+    Tag,
+    Created,
+    Size,
+}
+
+impl ImageSort {
+    pub const ALL: [ImageSort; 4] = [Self::Repository, Self::Tag, Self::Created, Self::Size];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Repository => "repository",
+            Self::Tag => "tag",
+            Self::Created => "created",
+            Self::Size => "size",
+        }
+    }
+}
+
+/// Sort and filters of the images page, see `ContainerFilter`
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ImageFilter {
+    /// searched in the repository, tag and id
+    #[serde(
+        deserialize_with = "empty_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub q: Option<String>,
+    #[serde(skip_serializing_if = "is_default")]
+    pub sort: ImageSort,
+    #[serde(skip_serializing_if = "is_default")]
+    pub order: SortOrder,
+    #[serde(skip_serializing_if = "is_default")]
+    pub dangling: bool,
+}
+
+impl ImageFilter {
+    /// e.g. `?sort=size&order=desc`, empty when nothing is set
+    pub fn query(&self) -> String {
+        match serde_urlencoded::to_string(self) {
+            Ok(query) if !query.is_empty() => format!("?{}", query),
+            _ => String::new(),
+        }
+    }
+
+    pub fn matches(&self, image: &Image) -> bool {
+        search(&self.q, &[&image.repository, &image.tag, &image.id])
+            && (!self.dangling || image.is_dangling())
+    }
+
+    /// The matching images in the selected order
+    pub fn apply(&self, images: Vec<Image>) -> Vec<Image> {
+        let mut images = images
+            .into_iter()
+            .filter(|image| self.matches(image))
+            .collect::<Vec<_>>();
+
+        images.sort_by(|a, b| {
+            let ordering = match self.sort {
+                ImageSort::Repository => Ordering::Equal,
+                ImageSort::Tag => a.tag.cmp(&b.tag),
+                ImageSort::Created => a.created_at.cmp(&b.created_at),
+                ImageSort::Size => a.size.cmp(&b.size),
+            };
+            self.order.apply(
+                ordering
+                    .then_with(|| a.repository.cmp(&b.repository))
+                    .then_with(|| a.tag.cmp(&b.tag)),
+            )
+        });
+
+        images
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_round_trip() {
+        let filter: ContainerFilter =
+            serde_urlencoded::from_str("q=db&sort=created&order=desc&state=&has-update=true")
+                .unwrap();
+
+        assert_eq!(filter.q.as_deref(), Some("db"));
+        assert_eq!(filter.sort, ContainerSort::Created);
+        assert_eq!(filter.state, None);
+        assert!(filter.has_update);
+        assert_eq!(
+            filter.query(),
+            "?q=db&sort=created&order=desc&has-update=true"
+        );
+        assert_eq!(ContainerFilter::default().query(), "");
+        assert_eq!(ImageFilter::default().query(), "");
+    }
+}
@@ -1,41 +1,291 @@
-use std::process::{Command, Stdio};
+use std::{
+    collections::{BTreeSet, HashMap},
+    process::Command,
+};
 
 use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
 use tokio::sync::broadcast;
 
-use crate::{model::SseEvent, util};
+use crate::{
+    container::{Container, ContainerImage},
+    fields::de,
+    inspect::ImageInspect,
+    model::SseEvent,
+    util,
+};
 
+/// A line of `docker images --format json`, with the sizes and dates parsed.
+/// Mirrors the full output, not every field is displayed.
+#[allow(dead_code)]
 #[derive(Debug, Clone, serde::Deserialize)]
 pub struct Image {
-    #[serde(alias = "Containers")]
-    pub containers: String,
-    #[serde(alias = "CreatedAt")]
-    pub created_at: String,
+    /// only known after `docker system df`
+    #[serde(alias = "Containers", deserialize_with = "de::optional_count")]
+    pub containers: Option<u64>,
+    #[serde(alias = "CreatedAt", deserialize_with = "de::timestamp")]
+    pub created_at: DateTime<FixedOffset>,
     #[serde(alias = "CreatedSince")]
     pub created_since: String,
-    #[serde(alias = "Digest")]
-    pub digest: String,
+    #[serde(alias = "Digest", deserialize_with = "de::none_as_empty")]
+    pub digest: Option<String>,
     #[serde(alias = "ID")]
     pub id: String,
     #[serde(alias = "Repository")]
     pub repository: String,
-    #[serde(alias = "SharedSize")]
-    pub shared_size: String,
-    #[serde(alias = "Size")]
-    pub size: String,
+    #[serde(alias = "SharedSize", deserialize_with = "de::optional_bytes")]
+    pub shared_size: Option<u64>,
+    #[serde(alias = "Size", deserialize_with = "de::bytes")]
+    pub size: u64,
     #[serde(alias = "Tag")]
     pub tag: String,
-    #[serde(alias = "UniqueSize")]
-    pub unique_size: String,
-    #[serde(alias = "VirtualSize")]
-    pub virtual_size: String,
+    #[serde(alias = "UniqueSize", deserialize_with = "de::optional_bytes")]
+    pub unique_size: Option<u64>,
+    #[serde(alias = "VirtualSize", deserialize_with = "de::optional_bytes")]
+    pub virtual_size: Option<u64>,
+}
+
+/// The containers created from an image, running or not
+#[derive(Debug, Clone, Default)]
+pub struct ImageUsage {
+    pub containers: Vec<Container>,
+    /// the references the containers were created from that were pulled
+    /// again and now point to a newer image
+    pub superseded_by: BTreeSet<String>,
+}
+
+impl ImageUsage {
+    /// compose projects of the containers
+    pub fn projects(&self) -> BTreeSet<String> {
+        self.containers.iter().filter_map(|c| c.project()).collect()
+    }
+
+    pub fn is_superseded(&self) -> bool {
+        !self.superseded_by.is_empty()
+    }
+}
+
+/// `nginx` and `docker.io/library/nginx:latest` are both listed as
+/// `nginx:latest` by `docker images`
+fn normalize_reference(reference: &str) -> String {
+    let name = [
+        "docker.io/library/",
+        "index.docker.io/library/",
+        "docker.io/",
+    ]
+    .iter()
+    .find_map(|prefix| reference.strip_prefix(prefix))
+    .unwrap_or(reference);
+
+    let last = name.rsplit('/').next().unwrap_or(name);
+    if last.contains(':') {
+        name.to_string()
+    } else {
+        format!("{}:latest", name)
+    }
+}
+
+/// `postgres` for `postgres:16`, `docker.io/library/postgres@sha256:...`, ...
+fn repository(reference: &str) -> String {
+    let reference = normalize_reference(reference.split('@').next().unwrap_or(reference));
+    match reference.rsplit_once(':') {
+        Some((name, tag)) if !tag.contains('/') => name.to_string(),
+        _ => reference,
+    }
+}
+
+/// An image no container uses, which a prune would remove
+#[derive(Debug, Clone)]
+pub struct PruneCandidate {
+    pub inspect: ImageInspect,
+    /// containers created from another tag or an older pull of the image's
+    /// repository, the image may be the version to roll them back to
+    pub last_used_by: Vec<Container>,
+}
+
+impl PruneCandidate {
+    fn new(
+        inspect: ImageInspect,
+        containers: &[Container],
+        container_images: &HashMap<String, ContainerImage>,
+    ) -> PruneCandidate {
+        let repositories = inspect
+            .repo_tags
+            .iter()
+            .chain(inspect.repo_digests.iter())
+            .map(|reference| repository(reference))
+            .collect::<BTreeSet<_>>();
+
+        let last_used_by = containers
+            .iter()
+            .filter(|c| {
+                container_images
+                    .get(&c.id)
+                    .is_some_and(|image| repositories.contains(&repository(&image.reference)))
+            })
+            .cloned()
+            .collect();
+
+        PruneCandidate {
+            inspect,
+            last_used_by,
+        }
+    }
+
+    /// What to pass to `docker image rm`. Removing the tags rather than the id
+    /// removes images with several tags without `--force`, which would also
+    /// remove images that a container started using since the preview.
+    pub fn references(&self) -> Vec<String> {
+        if self.inspect.repo_tags.is_empty() {
+            vec![self.inspect.id.clone()]
+        } else {
+            self.inspect.repo_tags.clone()
+        }
+    }
+}
+
+/// Group the containers by image id, see `Image::usage`
+fn usage_by_image(
+    images: &[Image],
+    containers: Vec<Container>,
+    container_images: &HashMap<String, ContainerImage>,
+) -> HashMap<String, ImageUsage> {
+    let mut usage = HashMap::<String, ImageUsage>::new();
+
+    for c in containers {
+        let Some(image) = container_images.get(&c.id) else {
+            continue;
+        };
+        let entry = usage.entry(image.id.clone()).or_default();
+
+        // digests and ids pin the image, they can't be superseded
+        let pinned = image.reference.contains('@') || image.reference.starts_with("sha256:");
+        if !pinned {
+            let reference = normalize_reference(&image.reference);
+            let still_tagged = images
+                .iter()
+                .any(|i| i.id == image.id && i.reference().as_ref() == Some(&reference));
+            if !still_tagged {
+                entry.superseded_by.insert(reference);
+            }
+        }
+
+        entry.containers.push(c);
+    }
+
+    usage
+}
+
+/// A line of `docker image history --format json`, newest layer first
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ImageHistory {
+    #[serde(alias = "CreatedAt", deserialize_with = "de::timestamp")]
+    pub created_at: DateTime<FixedOffset>,
+    /// the Dockerfile instruction
+    #[serde(alias = "CreatedBy")]
+    pub created_by: String,
+    #[serde(alias = "Size", deserialize_with = "de::bytes")]
+    pub size: u64,
+    #[serde(alias = "Comment")]
+    pub comment: String,
+}
+
+/// Check a `[registry[:port]/]path[:tag]` image reference before it's
+/// handed to docker
+pub fn validate_reference(reference: &str) -> Result<()> {
+    let component = "[a-z0-9]+(?:(?:[._]|__|-+)[a-z0-9]+)*";
+    let pattern = format!(
+        r"^(?:[a-zA-Z0-9](?:[a-zA-Z0-9-]*[a-zA-Z0-9])?(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9-]*[a-zA-Z0-9])?)*(?::[0-9]+)?/)?{c}(?:/{c})*(?::[A-Za-z0-9_][A-Za-z0-9_.-]{{0,127}})?$",
+        c = component
+    );
+    let regex = regex::Regex::new(&pattern)?;
+
+    if reference.len() > 255 || !regex.is_match(reference) {
+        return Err(anyhow::anyhow!(
+            "invalid image reference {}, expected e.g. registry.example.com/name:tag",
+            reference
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check a pull reference, a `validate_reference` optionally pinned to a
+/// digest, e.g. `postgres@sha256:...` or `postgres:16@sha256:...`
+pub fn validate_pull_reference(reference: &str) -> Result<()> {
+    let Some((name, digest)) = reference.split_once('@') else {
+        return validate_reference(reference);
+    };
+    validate_reference(name)?;
+
+    let regex = regex::Regex::new(r"^[a-z0-9]+(?:[.+_-][a-z0-9]+)*:[0-9a-fA-F]{32,}$")?;
+    if !regex.is_match(digest) {
+        return Err(anyhow::anyhow!(
+            "invalid digest {}, expected e.g. sha256:<64 hex digits>",
+            digest
+        ));
+    }
+
+    Ok(())
+}
+
+/// Platforms offered for pulls, docker picks the host's when none is given
+pub const PLATFORMS: [&str; 7] = [
+    "linux/amd64",
+    "linux/arm64",
+    "linux/arm/v7",
+    "linux/arm/v6",
+    "linux/386",
+    "linux/ppc64le",
+    "linux/s390x",
+];
+
+/// Check an `os/arch[/variant]` platform
+pub fn validate_platform(platform: &str) -> Result<()> {
+    let regex = regex::Regex::new(r"^[a-z0-9]+/[a-z0-9_]+(?:/[a-z0-9]+)?$")?;
+    if !regex.is_match(platform) {
+        return Err(anyhow::anyhow!(
+            "invalid platform {}, expected e.g. linux/arm64",
+            platform
+        ));
+    }
+
+    Ok(())
+}
+
+/// Ids of the images no container uses, once each. An image is listed once
+/// per repository it is tagged in, e.g. `app:1` and `registry.example.com/app:1`.
+fn unused_image_ids(
+    images: &[Image],
+    container_images: &HashMap<String, ContainerImage>,
+) -> Vec<String> {
+    images
+        .iter()
+        .map(|image| image.id.clone())
+        .filter(|id| !container_images.values().any(|image| image.id == *id))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
 }
 
 impl Image {
     pub fn get_all() -> Result<Vec<Image>> {
-        let output = Command::new("docker")
-            .arg("images")
-            .arg("--all")
+        Self::list(true)
+    }
+
+    /// Like `get_all` without the intermediate images of local builds, which
+    /// go away with the image built on them
+    pub fn get_top_level() -> Result<Vec<Image>> {
+        Self::list(false)
+    }
+
+    fn list(all: bool) -> Result<Vec<Image>> {
+        let mut cmd = Command::new("docker");
+        cmd.arg("images");
+        if all {
+            cmd.arg("--all");
+        }
+        let output = cmd
             .arg("--no-trunc")
             .arg("--format")
             .arg("'{{json .}}'")
@@ -49,8 +299,13 @@ impl Image {
             .split("\n")
             .map(|val| val.trim().trim_matches('\''))
             .filter(|val| !val.is_empty())
-            .map(|val| serde_json::from_str::<Image>(val))
-            .filter_map(|val| val.ok())
+            .filter_map(|val| match serde_json::from_str::<Image>(val) {
+                Ok(image) => Some(image),
+                Err(e) => {
+                    tracing::warn!("failed to parse image {}: {}", val, e);
+                    None
+                }
+            })
             .collect::<Vec<_>>();
 
         output.sort_by(|a, b| a.repository.cmp(&b.repository));
@@ -58,24 +313,371 @@ impl Image {
         Ok(output)
     }
 
-    pub async fn prune(event_name: &str, tx: &broadcast::Sender<SseEvent>) -> Result<()> {
-        tx.send(SseEvent {
-            event: event_name.into(),
-            data: "docker image prune --all --force\n".into(),
-        })
-        .context("update: stdout send error")?;
+    /// Untagged images, e.g. left behind when a tag was pulled again
+    pub fn is_dangling(&self) -> bool {
+        self.tag == "<none>"
+    }
 
-        let cmd = tokio::process::Command::new("docker")
+    /// `repository:tag`, `None` for dangling images
+    pub fn reference(&self) -> Option<String> {
+        (!self.is_dangling() && self.repository != "<none>")
+            .then(|| format!("{}:{}", self.repository, self.tag))
+    }
+
+    /// Which containers use each of the images, by image id. Images without
+    /// containers are unused.
+    pub fn usage(images: &[Image]) -> Result<HashMap<String, ImageUsage>> {
+        let containers = Container::get_all()?;
+        let container_images = Container::images(&containers)?;
+        Ok(usage_by_image(images, containers, &container_images))
+    }
+
+    /// `docker image history`, the layers and the instructions that made them
+    pub fn history(id: &str) -> Result<Vec<ImageHistory>> {
+        let output = Command::new("docker")
             .arg("image")
-            .arg("prune")
+            .arg("history")
+            .arg("--no-trunc")
+            .arg("--format")
+            .arg("{{json .}}")
+            .arg(id)
+            .output()?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "docker image history: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        String::from_utf8(output.stdout)?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("failed to parse image history {}", line))
+            })
+            .collect()
+    }
+
+    /// Names of the containers created from the image or one built on top
+    /// of it, running or not
+    pub fn containers_using(id: &str) -> Result<Vec<String>> {
+        let output = Command::new("docker")
+            .arg("ps")
             .arg("--all")
-            .arg("--force")
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
+            .arg("--filter")
+            .arg(format!("ancestor={}", id))
+            .arg("--format")
+            .arg("{{.Names}}")
+            .output()?;
+
+        Ok(String::from_utf8(output.stdout)?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect())
+    }
+
+    /// `docker image rm`, refused while containers use the image unless forced
+    pub async fn remove(id: String, force: bool, tx: &broadcast::Sender<SseEvent>) -> Result<()> {
+        let containers = Self::containers_using(&id)?;
+        if !containers.is_empty() && !force {
+            return util::refuse(
+                &id,
+                format!(
+                    "the image is used by {}, remove the containers first or force the removal",
+                    containers.join(", ")
+                ),
+                tx,
+            );
+        }
+
+        let mut args = vec!["image", "rm"];
+        if force {
+            args.push("--force");
+        }
+        args.push(&id);
+        util::docker(&id, &args, tx).await
+    }
+
+    /// Remove one of the image's tags, the image itself is kept
+    pub async fn untag(id: String, tag: String, tx: &broadcast::Sender<SseEvent>) -> Result<()> {
+        let inspect = ImageInspect::get(&id)?;
+        if !inspect.repo_tags.contains(&tag) {
+            return util::refuse(&id, format!("{} is not a tag of the image", tag), tx);
+        }
+        if inspect.repo_tags.len() == 1 {
+            return util::refuse(
+                &id,
+                format!(
+                    "{} is the image's only tag, removing it would remove the image",
+                    tag
+                ),
+                tx,
+            );
+        }
+
+        util::docker(&id, &["image", "rm", &tag], tx).await
+    }
+
+    /// `docker image tag`, add a repository:tag to the image
+    pub async fn tag(id: String, tag: String, tx: &broadcast::Sender<SseEvent>) -> Result<()> {
+        if let Err(e) = validate_reference(&tag) {
+            return util::refuse(&id, e.to_string(), tx);
+        }
+
+        util::docker(&id, &["image", "tag", &id, &tag], tx).await
+    }
+
+    /// `docker pull` of an image no project references, e.g. to have it
+    /// ready before a deploy
+    pub async fn pull(
+        event_name: &str,
+        reference: String,
+        platform: Option<String>,
+        tx: &broadcast::Sender<SseEvent>,
+    ) -> Result<()> {
+        if let Err(e) = validate_pull_reference(&reference) {
+            return util::refuse(event_name, e.to_string(), tx);
+        }
+
+        let mut args = vec!["pull"];
+        if let Some(platform) = &platform {
+            if let Err(e) = validate_platform(platform) {
+                return util::refuse(event_name, e.to_string(), tx);
+            }
+            args.extend(["--platform", platform]);
+        }
+        args.push(&reference);
+        util::docker(event_name, &args, tx).await
+    }
+
+    /// The images a prune would remove, the ones no container uses
+    /// (running or stopped), largest first
+    pub fn prune_preview() -> Result<Vec<PruneCandidate>> {
+        let containers = Container::get_all()?;
+        let container_images = Container::images(&containers)?;
+
+        let ids = unused_image_ids(&Self::get_top_level()?, &container_images);
+        let mut candidates = ImageInspect::get_many(&ids)?
+            .into_iter()
+            .map(|inspect| PruneCandidate::new(inspect, &containers, &container_images))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|c| std::cmp::Reverse(c.inspect.size));
+
+        Ok(candidates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::test_container;
+
+    // `docker images --all --no-trunc --format '{{json .}}'` from docker 25
+    const IMAGES_LINES: [&str; 2] = [
+        r#"{"Containers":"N/A","CreatedAt":"2024-02-13 20:26:49 +0100 CET","CreatedSince":"3 weeks ago","Digest":"\u003cnone\u003e","ID":"sha256:b8f2a5d6e3c1f0a9b8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f2a1b0c9d8e7f6a5","Repository":"postgres","SharedSize":"N/A","Size":"243MB","Tag":"16-alpine","UniqueSize":"N/A","VirtualSize":"243.4MB"}"#,
+        r#"{"Containers":"2","CreatedAt":"2024-01-05 10:00:00 +0000 UTC","CreatedSince":"2 months ago","Digest":"sha256:1f2e3d4c5b6a79881f2e3d4c5b6a79881f2e3d4c5b6a79881f2e3d4c5b6a7988","ID":"sha256:aa11bb22cc33dd44ee55ff66aa11bb22cc33dd44ee55ff66aa11bb22cc33dd44","Repository":"\u003cnone\u003e","SharedSize":"7.38MB","Size":"1.2GB","Tag":"\u003cnone\u003e","UniqueSize":"12.5kB","VirtualSize":"1.2GB"}"#,
+    ];
+
+    #[test]
+    fn history() {
+        let line = r#"{"Comment":"buildkit.dockerfile.v0","CreatedAt":"2024-02-13T20:26:49+01:00","CreatedBy":"ENTRYPOINT [\"docker-entrypoint.sh\"]","CreatedSince":"3 weeks ago","ID":"\u003cmissing\u003e","Size":"0B"}"#;
+        let layer: ImageHistory = serde_json::from_str(line).unwrap();
+
+        assert_eq!(layer.created_by, r#"ENTRYPOINT ["docker-entrypoint.sh"]"#);
+        assert_eq!(layer.size, 0);
+    }
+
+    #[test]
+    fn references() {
+        for valid in [
+            "nginx",
+            "nginx:1.25-alpine",
+            "library/postgres:16",
+            "ghcr.io/owner/app:v1.2.3",
+            "localhost:5000/app",
+            "registry.example.com:443/team/app_name:latest",
+        ] {
+            assert!(validate_reference(valid).is_ok(), "{}", valid);
+        }
+        for invalid in [
+            "",
+            "Nginx",
+            "nginx:",
+            "nginx:-tag",
+            "-nginx",
+            "nginx latest",
+            "nginx;rm",
+            "app/:tag",
+        ] {
+            assert!(validate_reference(invalid).is_err(), "{}", invalid);
+        }
+
+        let digest = "sha256:1f2e3d4c5b6a79881f2e3d4c5b6a79881f2e3d4c5b6a79881f2e3d4c5b6a7988";
+        for valid in [
+            "nginx:1.25".to_string(),
+            format!("postgres@{}", digest),
+            format!("ghcr.io/owner/app:v1@{}", digest),
+        ] {
+            assert!(validate_pull_reference(&valid).is_ok(), "{}", valid);
+        }
+        for invalid in [
+            "postgres@".to_string(),
+            "postgres@sha256:xyz".to_string(),
+            format!("Postgres@{}", digest),
+            format!("postgres@{}@{}", digest, digest),
+        ] {
+            assert!(validate_pull_reference(&invalid).is_err(), "{}", invalid);
+        }
+
+        assert!(PLATFORMS.iter().all(|p| validate_platform(p).is_ok()));
+        assert!(validate_platform("linux").is_err());
+        assert!(validate_platform("linux/amd64 --quiet").is_err());
+    }
+
+    #[test]
+    fn tagged_image() {
+        let image: Image = serde_json::from_str(IMAGES_LINES[0]).unwrap();
+
+        assert_eq!(image.repository, "postgres");
+        assert_eq!(image.tag, "16-alpine");
+        assert_eq!(image.digest, None);
+        assert_eq!(image.containers, None);
+        assert_eq!(image.size, 243_000_000);
+        assert_eq!(image.virtual_size, Some(243_400_000));
+        assert_eq!(image.shared_size, None);
+        assert_eq!(image.created_at.to_rfc3339(), "2024-02-13T20:26:49+01:00");
+    }
+
+    #[test]
+    fn usage() {
+        let images = IMAGES_LINES
+            .iter()
+            .map(|line| serde_json::from_str::<Image>(line).unwrap())
+            .collect::<Vec<_>>();
+        let container = |id: &str, project| test_container(id, id, "x", project, "running");
+        let containers = vec![
+            container("db", Some("app")),
+            container("old", Some("app")),
+            container("gone", None),
+        ];
+        let container_images = HashMap::from([
+            (
+                "db".to_string(),
+                ContainerImage {
+                    id: images[0].id.clone(),
+                    reference: "docker.io/library/postgres:16-alpine".to_string(),
+                },
+            ),
+            (
+                "old".to_string(),
+                ContainerImage {
+                    id: images[1].id.clone(),
+                    reference: "postgres".to_string(),
+                },
+            ),
+        ]);
+
+        let usage = usage_by_image(&images, containers, &container_images);
+
+        let current = &usage[&images[0].id];
+        assert_eq!(current.containers.len(), 1);
+        assert!(!current.is_superseded());
+        assert_eq!(current.projects(), BTreeSet::from(["app".to_string()]));
+
+        let old = &usage[&images[1].id];
+        assert_eq!(old.containers[0].names, "old");
+        assert_eq!(
+            old.superseded_by,
+            BTreeSet::from(["postgres:latest".to_string()])
+        );
+    }
+
+    #[test]
+    fn prune_candidate() {
+        assert_eq!(repository("postgres:16"), "postgres");
+        assert_eq!(
+            repository("docker.io/library/postgres@sha256:1f2e"),
+            "postgres"
+        );
+        assert_eq!(repository("localhost:5000/app"), "localhost:5000/app");
+        assert_eq!(repository("localhost:5000/app:v1"), "localhost:5000/app");
+
+        let containers = vec![test_container(
+            "web",
+            "app-web-1",
+            "nginx:1.25",
+            Some("app"),
+            "exited",
+        )];
+        let container_images = HashMap::from([(
+            "web".to_string(),
+            ContainerImage {
+                id: "sha256:new".to_string(),
+                reference: "nginx:1.25".to_string(),
+            },
+        )]);
+        let inspect = |tags: &[&str], digests: &[&str]| ImageInspect {
+            id: "sha256:old".to_string(),
+            repo_tags: tags.iter().map(|t| t.to_string()).collect(),
+            repo_digests: digests.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        };
+
+        // a previous pull of the tag, only the digest is left
+        let old = PruneCandidate::new(
+            inspect(&[], &["nginx@sha256:1f2e"]),
+            &containers,
+            &container_images,
+        );
+        assert_eq!(old.last_used_by[0].names, "app-web-1");
+        assert_eq!(old.references(), vec!["sha256:old"]);
+
+        let other = PruneCandidate::new(
+            inspect(&["redis:7", "redis:latest"], &[]),
+            &containers,
+            &container_images,
+        );
+        assert!(other.last_used_by.is_empty());
+        assert_eq!(other.references(), vec!["redis:7", "redis:latest"]);
+    }
+
+    #[test]
+    fn unused_image_ids() {
+        let retagged = IMAGES_LINES[0].replace(
+            r#""Repository":"postgres""#,
+            r#""Repository":"registry.example.com/postgres""#,
+        );
+        let images = [IMAGES_LINES[0], &retagged, IMAGES_LINES[1], IMAGES_LINES[0]]
+            .iter()
+            .map(|line| serde_json::from_str::<Image>(line).unwrap())
+            .collect::<Vec<_>>();
+        let container_images = HashMap::from([(
+            "db".to_string(),
+            ContainerImage {
+                id: images[2].id.clone(),
+                reference: "app".to_string(),
+            },
+        )]);
+
+        assert_eq!(
+            super::unused_image_ids(&images, &container_images),
+            vec![images[0].id.clone()]
+        );
+    }
 
-        util::execute_command(event_name, cmd, tx).await?;
+    #[test]
+    fn dangling_image_with_usage() {
+        let image: Image = serde_json::from_str(IMAGES_LINES[1]).unwrap();
 
-        Ok(())
+        assert_eq!(image.repository, "<none>");
+        assert!(image.is_dangling());
+        assert!(image.digest.unwrap().starts_with("sha256:"));
+        assert_eq!(image.containers, Some(2));
+        assert_eq!(image.size, 1_200_000_000);
+        assert_eq!(image.shared_size, Some(7_380_000));
+        assert_eq!(image.unique_size, Some(12_500));
     }
 }
@@ -0,0 +1,955 @@
+use std::{collections::HashMap, process::Command};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+/// The parts of `docker inspect <container>` that mgdocker uses
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerInspect {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub created: String,
+    /// id of the image the container was created from
+    pub image: String,
+    #[serde(default)]
+    pub restart_count: i64,
+    pub config: InspectConfig,
+    pub host_config: HostConfig,
+    pub network_settings: NetworkSettings,
+    #[serde(default)]
+    pub mounts: Vec<InspectMount>,
+    pub state: InspectState,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct InspectState {
+    pub status: String,
+    pub running: bool,
+    pub started_at: String,
+    pub finished_at: String,
+    pub exit_code: i64,
+    #[serde(rename = "OOMKilled")]
+    pub oom_killed: bool,
+    /// only set when the container has a healthcheck
+    pub health: Option<Health>,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct Health {
+    pub status: String,
+    pub failing_streak: i64,
+    /// the last few probes, oldest first
+    pub log: Vec<HealthLog>,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct HealthLog {
+    pub start: String,
+    pub end: String,
+    pub exit_code: i64,
+    pub output: String,
+}
+
+/// Durations are in nanoseconds, zero when inherited from the defaults
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct Healthcheck {
+    pub test: Option<Vec<String>>,
+    pub interval: i64,
+    pub timeout: i64,
+    pub start_period: i64,
+    pub retries: i64,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct InspectConfig {
+    pub hostname: String,
+    pub user: String,
+    pub env: Option<Vec<String>>,
+    pub cmd: Option<Vec<String>>,
+    pub entrypoint: Option<Vec<String>>,
+    /// image reference the container was created with, e.g. `nginx:latest`
+    pub image: String,
+    pub working_dir: String,
+    pub labels: Option<HashMap<String, String>>,
+    pub tty: bool,
+    pub open_stdin: bool,
+    pub healthcheck: Option<Healthcheck>,
+    /// e.g. `80/tcp`, the values are empty objects
+    pub exposed_ports: Option<HashMap<String, Value>>,
+    pub stop_signal: Option<String>,
+    /// seconds
+    pub stop_timeout: Option<i64>,
+}
+
+impl InspectConfig {
+    /// Environment as name and value pairs, with the values of secrets masked
+    pub fn env(&self) -> Vec<(String, String)> {
+        self.env
+            .iter()
+            .flatten()
+            .map(|env| {
+                let (name, value) = env.split_once('=').unwrap_or((env, ""));
+                let value = if is_secret_env(name) {
+                    MASKED_VALUE
+                } else {
+                    value
+                };
+                (name.to_string(), value.to_string())
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct HostConfig {
+    pub network_mode: String,
+    pub port_bindings: Option<HashMap<String, Option<Vec<PortBinding>>>>,
+    pub restart_policy: RestartPolicy,
+    pub privileged: bool,
+    /// resource limits, zero or null when unlimited
+    pub memory: i64,
+    pub memory_reservation: i64,
+    pub memory_swap: i64,
+    pub nano_cpus: i64,
+    pub cpu_shares: i64,
+    pub cpuset_cpus: String,
+    pub pids_limit: Option<i64>,
+    pub cap_add: Option<Vec<String>>,
+    pub cap_drop: Option<Vec<String>>,
+    pub devices: Option<Vec<DeviceMapping>>,
+    pub dns: Option<Vec<String>>,
+    pub dns_search: Option<Vec<String>>,
+    pub dns_options: Option<Vec<String>>,
+    /// `host:ip`
+    pub extra_hosts: Option<Vec<String>>,
+    pub log_config: LogConfig,
+    pub security_opt: Option<Vec<String>>,
+    pub init: Option<bool>,
+    /// mount point and options of `--tmpfs`, these are not listed in the mounts
+    pub tmpfs: Option<HashMap<String, String>>,
+    pub readonly_rootfs: bool,
+    pub shm_size: i64,
+    pub ulimits: Option<Vec<Ulimit>>,
+    pub sysctls: Option<HashMap<String, String>>,
+    pub group_add: Option<Vec<String>>,
+    pub pid_mode: String,
+    pub ipc_mode: String,
+    pub userns_mode: String,
+    pub runtime: String,
+    // not reproduced by `ContainerInspect::create_args`, see `unsupported`
+    pub device_requests: Option<Vec<Value>>,
+    pub volumes_from: Option<Vec<String>>,
+    pub links: Option<Vec<String>>,
+    pub cgroup_parent: String,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct DeviceMapping {
+    pub path_on_host: String,
+    pub path_in_container: String,
+    pub cgroup_permissions: String,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct LogConfig {
+    #[serde(rename = "Type")]
+    pub kind: String,
+    pub config: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct Ulimit {
+    pub name: String,
+    pub soft: i64,
+    pub hard: i64,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct PortBinding {
+    pub host_ip: String,
+    pub host_port: String,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct RestartPolicy {
+    pub name: String,
+    pub maximum_retry_count: i64,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct NetworkSettings {
+    pub networks: HashMap<String, EndpointSettings>,
+    /// published ports of the running container
+    pub ports: Option<HashMap<String, Option<Vec<PortBinding>>>>,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct EndpointSettings {
+    pub aliases: Option<Vec<String>>,
+    #[serde(rename = "IPAddress")]
+    pub ip_address: String,
+    #[serde(rename = "GlobalIPv6Address")]
+    pub global_ipv6_address: String,
+    pub gateway: String,
+    pub mac_address: String,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct InspectMount {
+    #[serde(rename = "Type")]
+    pub kind: String,
+    pub name: Option<String>,
+    pub source: String,
+    pub destination: String,
+    /// `z`, `ro` or `nocopy` as given after the last colon of `--volume`
+    pub mode: String,
+    #[serde(rename = "RW")]
+    pub rw: bool,
+    /// bind propagation, `rprivate` unless set
+    pub propagation: String,
+}
+
+/// `docker top` of a running container
+#[derive(Debug, Clone, Default)]
+pub struct Top {
+    pub titles: Vec<String>,
+    pub processes: Vec<Vec<String>>,
+}
+
+/// Environment variable names whose values are hidden on the detail page
+const SECRET_ENV_PATTERNS: [&str; 8] = [
+    "PASSWORD",
+    "PASSWD",
+    "SECRET",
+    "TOKEN",
+    "KEY",
+    "CREDENTIAL",
+    "PRIVATE",
+    "AUTH",
+];
+
+pub const MASKED_VALUE: &str = "********";
+
+pub fn is_secret_env(name: &str) -> bool {
+    let name = name.to_uppercase();
+    SECRET_ENV_PATTERNS
+        .iter()
+        .any(|pattern| name.contains(pattern))
+}
+
+/// `docker volume inspect`
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct VolumeInspect {
+    pub name: String,
+    pub driver: String,
+    pub mountpoint: String,
+    pub created_at: String,
+    pub scope: String,
+    pub labels: Option<HashMap<String, String>>,
+    /// driver options of `docker volume create --opt`
+    pub options: Option<HashMap<String, String>>,
+}
+
+/// `docker image inspect`, the config also tells which container settings
+/// came from the image and which were given to `docker run`
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ImageInspect {
+    pub id: String,
+    pub repo_tags: Vec<String>,
+    pub repo_digests: Vec<String>,
+    pub created: String,
+    pub architecture: String,
+    pub variant: Option<String>,
+    pub os: String,
+    pub size: u64,
+    pub config: InspectConfig,
+}
+
+impl ContainerInspect {
+    pub fn get(name: &str) -> Result<ContainerInspect> {
+        Ok(Self::get_with_raw(name)?.0)
+    }
+
+    /// The inspect data along with the full json docker returned
+    pub fn get_with_raw(name: &str) -> Result<(ContainerInspect, Value)> {
+        let output = Command::new("docker")
+            .arg("container")
+            .arg("inspect")
+            .arg(name)
+            .output()?;
+
+        let mut output: Vec<Value> = serde_json::from_slice(&output.stdout)
+            .with_context(|| format!("failed to inspect container {}", name))?;
+
+        let raw = output.pop().context("container not found")?;
+        let inspect = serde_json::from_value(raw.clone())
+            .with_context(|| format!("failed to inspect container {}", name))?;
+
+        Ok((inspect, raw))
+    }
+
+    /// `docker top`, the last column (the command) may contain spaces
+    pub fn top(&self) -> Result<Top> {
+        let output = Command::new("docker")
+            .arg("container")
+            .arg("top")
+            .arg(&self.id)
+            .output()?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "docker top: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let output = String::from_utf8(output.stdout)?;
+        let mut lines = output.lines().filter(|line| !line.trim().is_empty());
+
+        let titles = lines
+            .next()
+            .map(|line| {
+                line.split_whitespace()
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let processes = lines
+            .map(|line| {
+                let mut columns = vec![];
+                let mut rest = line.trim_start();
+                for _ in 1..titles.len() {
+                    let (column, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    columns.push(column.to_string());
+                    rest = tail.trim_start();
+                }
+                columns.push(rest.to_string());
+                columns
+            })
+            .collect();
+
+        Ok(Top { titles, processes })
+    }
+
+    /// See `InspectConfig::env`
+    pub fn env(&self) -> Vec<(String, String)> {
+        self.config.env()
+    }
+
+    /// Container name without the leading slash docker adds
+    pub fn name(&self) -> &str {
+        self.name.trim_start_matches('/')
+    }
+
+    /// Networks other than the one in `network_mode`, these are connected after create
+    pub fn extra_networks(&self) -> Vec<String> {
+        let mode = self.host_config.network_mode.as_str();
+        let mut networks = self
+            .network_settings
+            .networks
+            .keys()
+            .filter(|name| name.as_str() != mode)
+            // the default network mode is the bridge network
+            .filter(|name| !(matches!(mode, "" | "default") && name.as_str() == "bridge"))
+            .cloned()
+            .collect::<Vec<_>>();
+        networks.sort();
+        networks
+    }
+
+    /// `docker create` arguments that reproduce the run configuration of this
+    /// container: env, mounts, ports, networks, restart policy, the host
+    /// config and the command/entrypoint/user/working dir/stop signal when
+    /// they differ from the image. What can't be reproduced is listed by
+    /// `unsupported`.
+    /// Settings inherited from the old image are left out so the new image's
+    /// defaults apply.
+    pub fn create_args(&self, image: &ImageInspect) -> Vec<String> {
+        let mut args = vec!["--name".to_string(), self.name().to_string()];
+
+        let image_env = image.config.env.clone().unwrap_or_default();
+        for env in self.config.env.iter().flatten() {
+            if !image_env.contains(env) {
+                args.push("--env".into());
+                args.push(env.clone());
+            }
+        }
+
+        let image_labels = image.config.labels.clone().unwrap_or_default();
+        let mut labels = self
+            .config
+            .labels
+            .iter()
+            .flatten()
+            .filter(|(k, v)| image_labels.get(*k) != Some(*v))
+            .collect::<Vec<_>>();
+        labels.sort();
+        for (k, v) in labels {
+            args.push("--label".into());
+            args.push(format!("{}={}", k, v));
+        }
+
+        // other mount types are refused, see `unsupported`
+        for mount in self.mounts.iter() {
+            let source = match mount.kind.as_str() {
+                "volume" => mount.name.clone().unwrap_or_default(),
+                "bind" => mount.source.clone(),
+                _ => continue,
+            };
+            let mut options = mount
+                .mode
+                .split(',')
+                .filter(|option| !option.is_empty())
+                .map(String::from)
+                .collect::<Vec<_>>();
+            if !mount.rw && !options.iter().any(|option| option == "ro") {
+                options.push("ro".into());
+            }
+            if !matches!(mount.propagation.as_str(), "" | "rprivate") {
+                options.push(mount.propagation.clone());
+            }
+            let mut volume = format!("{}:{}", source, mount.destination);
+            if !options.is_empty() {
+                volume.push(':');
+                volume.push_str(&options.join(","));
+            }
+            args.push("--volume".into());
+            args.push(volume);
+        }
+
+        let mut tmpfs = self
+            .host_config
+            .tmpfs
+            .iter()
+            .flatten()
+            .map(|(path, options)| match options.as_str() {
+                "" => path.clone(),
+                options => format!("{}:{}", path, options),
+            })
+            .collect::<Vec<_>>();
+        tmpfs.sort();
+        push_each(&mut args, "--tmpfs", &tmpfs);
+
+        let mut ports = self
+            .host_config
+            .port_bindings
+            .iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        ports.sort_by(|a, b| a.0.cmp(b.0));
+        for (container_port, bindings) in ports {
+            for binding in bindings.iter().flatten() {
+                let publish = match (binding.host_ip.as_str(), binding.host_port.as_str()) {
+                    ("", "") => container_port.clone(),
+                    ("", host_port) => format!("{}:{}", host_port, container_port),
+                    (host_ip, host_port) if host_ip.contains(':') => {
+                        format!("[{}]:{}:{}", host_ip, host_port, container_port)
+                    }
+                    (host_ip, host_port) => {
+                        format!("{}:{}:{}", host_ip, host_port, container_port)
+                    }
+                };
+                args.push("--publish".into());
+                args.push(publish);
+            }
+        }
+
+        if !matches!(self.host_config.network_mode.as_str(), "" | "default") {
+            args.push("--network".into());
+            args.push(self.host_config.network_mode.clone());
+
+            let aliases = self
+                .network_settings
+                .networks
+                .get(&self.host_config.network_mode)
+                .and_then(|network| network.aliases.clone())
+                .unwrap_or_default();
+            for alias in aliases.iter().filter(|alias| !self.id.starts_with(*alias)) {
+                args.push("--network-alias".into());
+                args.push(alias.clone());
+            }
+        }
+
+        let restart = &self.host_config.restart_policy;
+        match restart.name.as_str() {
+            "" | "no" => {}
+            "on-failure" if restart.maximum_retry_count > 0 => {
+                args.push("--restart".into());
+                args.push(format!("on-failure:{}", restart.maximum_retry_count));
+            }
+            name => {
+                args.push("--restart".into());
+                args.push(name.to_string());
+            }
+        }
+
+        // docker defaults the hostname to the short container id
+        if !self.config.hostname.is_empty() && !self.id.starts_with(&self.config.hostname) {
+            args.push("--hostname".into());
+            args.push(self.config.hostname.clone());
+        }
+
+        if self.config.user != image.config.user {
+            args.push("--user".into());
+            args.push(self.config.user.clone());
+        }
+
+        if self.config.working_dir != image.config.working_dir {
+            args.push("--workdir".into());
+            args.push(self.config.working_dir.clone());
+        }
+
+        if self.config.tty {
+            args.push("--tty".into());
+        }
+
+        if self.config.open_stdin {
+            args.push("--interactive".into());
+        }
+
+        if self.host_config.privileged {
+            args.push("--privileged".into());
+        }
+
+        self.push_host_config(&mut args);
+
+        if self.config.stop_signal != image.config.stop_signal {
+            if let Some(signal) = &self.config.stop_signal {
+                args.push("--stop-signal".into());
+                args.push(signal.clone());
+            }
+        }
+
+        if let Some(timeout) = self.config.stop_timeout {
+            args.push("--stop-timeout".into());
+            args.push(timeout.to_string());
+        }
+
+        let entrypoint_changed = self.config.entrypoint != image.config.entrypoint;
+        if entrypoint_changed {
+            args.push("--entrypoint".into());
+            args.push(
+                self.config
+                    .entrypoint
+                    .as_ref()
+                    .and_then(|entrypoint| entrypoint.first().cloned())
+                    .unwrap_or_default(),
+            );
+        }
+
+        args.push(self.config.image.clone());
+
+        // the rest of a multi part entrypoint has to go in front of the command
+        if entrypoint_changed {
+            args.extend(self.config.entrypoint.iter().flatten().skip(1).cloned());
+        }
+
+        if entrypoint_changed || self.config.cmd != image.config.cmd {
+            args.extend(self.config.cmd.iter().flatten().cloned());
+        }
+
+        args
+    }
+
+    /// The host config settings of `create_args` besides ports, mounts,
+    /// networks and the restart policy: limits, capabilities, devices, dns,
+    /// logging and security options
+    fn push_host_config(&self, args: &mut Vec<String>) {
+        let host = &self.host_config;
+
+        push_each(args, "--cap-add", host.cap_add.iter().flatten());
+        push_each(args, "--cap-drop", host.cap_drop.iter().flatten());
+        let devices = host.devices.iter().flatten().map(|device| {
+            format!(
+                "{}:{}:{}",
+                device.path_on_host, device.path_in_container, device.cgroup_permissions
+            )
+        });
+        push_each(args, "--device", devices);
+        push_each(args, "--dns", host.dns.iter().flatten());
+        push_each(args, "--dns-search", host.dns_search.iter().flatten());
+        push_each(args, "--dns-option", host.dns_options.iter().flatten());
+        push_each(args, "--add-host", host.extra_hosts.iter().flatten());
+        push_each(args, "--security-opt", host.security_opt.iter().flatten());
+        push_each(args, "--group-add", host.group_add.iter().flatten());
+
+        let ulimits = host
+            .ulimits
+            .iter()
+            .flatten()
+            .map(|ulimit| format!("{}={}:{}", ulimit.name, ulimit.soft, ulimit.hard));
+        push_each(args, "--ulimit", ulimits);
+        let mut sysctls = host
+            .sysctls
+            .iter()
+            .flatten()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>();
+        sysctls.sort();
+        push_each(args, "--sysctl", &sysctls);
+
+        // the daemon's default driver may differ from the container's
+        if !host.log_config.kind.is_empty() {
+            args.push("--log-driver".into());
+            args.push(host.log_config.kind.clone());
+            let mut options = host
+                .log_config
+                .config
+                .iter()
+                .flatten()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>();
+            options.sort();
+            push_each(args, "--log-opt", &options);
+        }
+
+        let limits = [
+            ("--memory", host.memory),
+            ("--memory-reservation", host.memory_reservation),
+            ("--cpu-shares", host.cpu_shares),
+            ("--pids-limit", host.pids_limit.unwrap_or_default()),
+        ];
+        for (flag, value) in limits {
+            if value > 0 {
+                args.push(flag.into());
+                args.push(value.to_string());
+            }
+        }
+        // -1 is unlimited swap
+        if host.memory_swap != 0 {
+            args.push("--memory-swap".into());
+            args.push(host.memory_swap.to_string());
+        }
+        if host.nano_cpus > 0 {
+            args.push("--cpus".into());
+            args.push((host.nano_cpus as f64 / 1e9).to_string());
+        }
+        if !host.cpuset_cpus.is_empty() {
+            args.push("--cpuset-cpus".into());
+            args.push(host.cpuset_cpus.clone());
+        }
+        // 64MB is the default
+        if host.shm_size > 0 && host.shm_size != 64 * 1024 * 1024 {
+            args.push("--shm-size".into());
+            args.push(host.shm_size.to_string());
+        }
+
+        // private or shareable ipc, depending on the daemon version, is the default
+        let modes: [(&str, &str, &[&str]); 4] = [
+            ("--pid", &host.pid_mode, &[]),
+            ("--ipc", &host.ipc_mode, &["private", "shareable"]),
+            ("--userns", &host.userns_mode, &[]),
+            ("--runtime", &host.runtime, &["runc"]),
+        ];
+        for (flag, mode, defaults) in modes {
+            if !mode.is_empty() && !defaults.contains(&mode) {
+                args.push(flag.into());
+                args.push(mode.to_string());
+            }
+        }
+
+        if host.readonly_rootfs {
+            args.push("--read-only".into());
+        }
+        if host.init == Some(true) {
+            args.push("--init".into());
+        }
+    }
+
+    /// Settings `create_args` can't reproduce, a re-created container would
+    /// run without them
+    pub fn unsupported(&self) -> Vec<String> {
+        let host = &self.host_config;
+        let mut unsupported = self
+            .mounts
+            .iter()
+            .filter(|mount| !matches!(mount.kind.as_str(), "volume" | "bind"))
+            .map(|mount| format!("{} mount at {}", mount.kind, mount.destination))
+            .collect::<Vec<_>>();
+
+        let set = [
+            (
+                "device requests (gpus)",
+                host.device_requests.iter().flatten().count() > 0,
+            ),
+            (
+                "volumes from",
+                host.volumes_from.iter().flatten().count() > 0,
+            ),
+            ("links", host.links.iter().flatten().count() > 0),
+            ("cgroup parent", !host.cgroup_parent.is_empty()),
+        ];
+        unsupported.extend(
+            set.into_iter()
+                .filter(|(_, set)| *set)
+                .map(|(name, _)| name.to_string()),
+        );
+        unsupported
+    }
+}
+
+/// `flag value` for each value
+fn push_each(
+    args: &mut Vec<String>,
+    flag: &str,
+    values: impl IntoIterator<Item = impl AsRef<str>>,
+) {
+    for value in values {
+        args.push(flag.to_string());
+        args.push(value.as_ref().to_string());
+    }
+}
+
+/// Mask the secret environment variables in the raw inspect json
+pub fn mask_raw_env(raw: &mut Value) {
+    if let Some(env) = raw["Config"]["Env"].as_array_mut() {
+        for value in env.iter_mut() {
+            let masked = value
+                .as_str()
+                .and_then(|env| env.split_once('='))
+                .filter(|(name, _)| is_secret_env(name))
+                .map(|(name, _)| format!("{}={}", name, MASKED_VALUE));
+            if let Some(masked) = masked {
+                *value = Value::String(masked);
+            }
+        }
+    }
+}
+
+impl ImageInspect {
+    pub fn get(name: &str) -> Result<ImageInspect> {
+        Ok(Self::get_with_raw(name)?.0)
+    }
+
+    /// The inspect data along with the full json docker returned
+    pub fn get_with_raw(name: &str) -> Result<(ImageInspect, Value)> {
+        let output = Command::new("docker")
+            .arg("image")
+            .arg("inspect")
+            .arg(name)
+            .output()?;
+
+        let mut output: Vec<Value> = serde_json::from_slice(&output.stdout)
+            .with_context(|| format!("failed to inspect image {}", name))?;
+
+        let raw = output.pop().context("image not found")?;
+        let inspect = serde_json::from_value(raw.clone())
+            .with_context(|| format!("failed to inspect image {}", name))?;
+
+        Ok((inspect, raw))
+    }
+
+    /// Several images in one call, images that are gone are left out
+    pub fn get_many(ids: &[String]) -> Result<Vec<ImageInspect>> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let output = Command::new("docker")
+            .arg("image")
+            .arg("inspect")
+            .args(ids)
+            .output()?;
+
+        serde_json::from_slice(&output.stdout).context("failed to inspect images")
+    }
+
+    /// e.g. `linux/arm64/v8`
+    pub fn platform(&self) -> String {
+        match &self.variant {
+            Some(variant) => format!("{}/{}/{}", self.os, self.architecture, variant),
+            None => format!("{}/{}", self.os, self.architecture),
+        }
+    }
+}
+
+impl VolumeInspect {
+    /// The inspect data along with the full json docker returned
+    pub fn get_with_raw(name: &str) -> Result<(VolumeInspect, Value)> {
+        let output = Command::new("docker")
+            .arg("volume")
+            .arg("inspect")
+            .arg(name)
+            .output()?;
+
+        let mut output: Vec<Value> = serde_json::from_slice(&output.stdout)
+            .with_context(|| format!("failed to inspect volume {}", name))?;
+
+        let raw = output.pop().context("volume not found")?;
+        let inspect = serde_json::from_value(raw.clone())
+            .with_context(|| format!("failed to inspect volume {}", name))?;
+
+        Ok((inspect, raw))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the parts of `docker image inspect` that `create_args` compares, from docker 25
+    const IMAGE: &str = r#"{"Id":"sha256:4f1c","RepoTags":["registry.example.com/app:1.4"],"RepoDigests":[],"Created":"2024-03-01T10:00:00Z","Architecture":"amd64","Os":"linux","Size":52000000,"Config":{"Hostname":"","User":"app","Env":["PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin","APP_HOME=/srv/app"],"Cmd":["serve"],"Entrypoint":["/entrypoint.sh"],"Image":"","WorkingDir":"/srv/app","Labels":{"org.opencontainers.image.version":"1.4","maintainer":"ops"},"StopSignal":"SIGTERM"}}"#;
+
+    // `docker container inspect` of a container started with `docker run`, from docker 25
+    const CONTAINER: &str = r#"{"Id":"8c2e4a1b9f0d3e5c7a6b8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a","Name":"/app","Created":"2024-03-02T08:00:00Z","Image":"sha256:4f1c","RestartCount":0,
+        "Config":{"Hostname":"8c2e4a1b9f0d","User":"app","Env":["APP_ENV=production","PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin","APP_HOME=/srv/app"],"Cmd":["serve","--port","8080"],"Entrypoint":["/entrypoint.sh"],"Image":"registry.example.com/app:1.4","WorkingDir":"/srv/app","Labels":{"org.opencontainers.image.version":"1.4","maintainer":"me","team":"web"},"Tty":false,"OpenStdin":false,"StopSignal":"SIGTERM"},
+        "HostConfig":{"NetworkMode":"proxy","PortBindings":{"8080/tcp":[{"HostIp":"","HostPort":"8080"},{"HostIp":"::1","HostPort":"8081"}],"9090/udp":[{"HostIp":"127.0.0.1","HostPort":"9090"}]},"RestartPolicy":{"Name":"on-failure","MaximumRetryCount":5},"Privileged":false,"Memory":536870912,"MemoryReservation":0,"MemorySwap":1073741824,"NanoCpus":1500000000,"CpuShares":0,"CpusetCpus":"","PidsLimit":null,"CapAdd":["NET_ADMIN"],"CapDrop":["MKNOD"],"Devices":[{"PathOnHost":"/dev/fuse","PathInContainer":"/dev/fuse","CgroupPermissions":"rwm"}],"Dns":["1.1.1.1"],"DnsSearch":[],"DnsOptions":[],"ExtraHosts":["host.docker.internal:host-gateway"],"LogConfig":{"Type":"json-file","Config":{"max-size":"10m","max-file":"3"}},"SecurityOpt":["no-new-privileges"],"Init":true,"Tmpfs":{"/run":"rw,size=64m","/tmp":""},"ReadonlyRootfs":true,"ShmSize":67108864,"Ulimits":[{"Name":"nofile","Soft":1024,"Hard":4096}],"Sysctls":{"net.core.somaxconn":"1024"},"IpcMode":"private","PidMode":"","UsernsMode":"","Runtime":"runc","CgroupParent":"","DeviceRequests":null,"VolumesFrom":null,"Links":null},
+        "NetworkSettings":{"Networks":{"proxy":{"Aliases":["8c2e4a1b9f0d","app","api"],"IPAddress":"172.20.0.5","GlobalIPv6Address":"","Gateway":"172.20.0.1","MacAddress":"02:42:ac:14:00:05"},"monitoring":{"Aliases":null,"IPAddress":"172.21.0.3","GlobalIPv6Address":"","Gateway":"172.21.0.1","MacAddress":"02:42:ac:15:00:03"}},"Ports":{}},
+        "Mounts":[{"Type":"volume","Name":"app_data","Source":"/var/lib/docker/volumes/app_data/_data","Destination":"/srv/app/data","Driver":"local","Mode":"z","RW":true,"Propagation":""},{"Type":"bind","Source":"/etc/app","Destination":"/etc/app","Mode":"ro","RW":false,"Propagation":"rslave"}],
+        "State":{"Status":"running","Running":true,"StartedAt":"2024-03-02T08:00:01Z","FinishedAt":"0001-01-01T00:00:00Z","ExitCode":0,"OOMKilled":false}}"#;
+
+    fn fixture() -> (ContainerInspect, ImageInspect) {
+        (
+            serde_json::from_str(CONTAINER).unwrap(),
+            serde_json::from_str(IMAGE).unwrap(),
+        )
+    }
+
+    /// The values of a flag, in order
+    fn values<'a>(args: &'a [String], flag: &str) -> Vec<&'a str> {
+        args.windows(2)
+            .filter(|pair| pair[0] == flag)
+            .map(|pair| pair[1].as_str())
+            .collect()
+    }
+
+    #[test]
+    fn create_args_env_and_labels() {
+        let (container, image) = fixture();
+        let args = container.create_args(&image);
+
+        // only what differs from the image
+        assert_eq!(values(&args, "--env"), vec!["APP_ENV=production"]);
+        assert_eq!(values(&args, "--label"), vec!["maintainer=me", "team=web"]);
+        assert!(values(&args, "--user").is_empty());
+        assert!(values(&args, "--workdir").is_empty());
+        // the default hostname is the short id
+        assert!(values(&args, "--hostname").is_empty());
+    }
+
+    #[test]
+    fn create_args_ports_and_networks() {
+        let (container, image) = fixture();
+        let args = container.create_args(&image);
+
+        assert_eq!(
+            values(&args, "--publish"),
+            vec![
+                "8080:8080/tcp",
+                "[::1]:8081:8080/tcp",
+                "127.0.0.1:9090:9090/udp"
+            ]
+        );
+        assert_eq!(values(&args, "--network"), vec!["proxy"]);
+        // the short id alias is added by docker
+        assert_eq!(values(&args, "--network-alias"), vec!["app", "api"]);
+        assert_eq!(container.extra_networks(), vec!["monitoring"]);
+        assert_eq!(values(&args, "--restart"), vec!["on-failure:5"]);
+    }
+
+    #[test]
+    fn create_args_mounts_and_host_config() {
+        let (container, image) = fixture();
+        let args = container.create_args(&image);
+
+        assert_eq!(
+            values(&args, "--volume"),
+            vec!["app_data:/srv/app/data:z", "/etc/app:/etc/app:ro,rslave"]
+        );
+        assert_eq!(values(&args, "--tmpfs"), vec!["/run:rw,size=64m", "/tmp"]);
+        assert_eq!(values(&args, "--cap-add"), vec!["NET_ADMIN"]);
+        assert_eq!(values(&args, "--cap-drop"), vec!["MKNOD"]);
+        assert_eq!(values(&args, "--device"), vec!["/dev/fuse:/dev/fuse:rwm"]);
+        assert_eq!(values(&args, "--dns"), vec!["1.1.1.1"]);
+        assert_eq!(
+            values(&args, "--add-host"),
+            vec!["host.docker.internal:host-gateway"]
+        );
+        assert_eq!(values(&args, "--log-driver"), vec!["json-file"]);
+        assert_eq!(
+            values(&args, "--log-opt"),
+            vec!["max-file=3", "max-size=10m"]
+        );
+        assert_eq!(values(&args, "--security-opt"), vec!["no-new-privileges"]);
+        assert_eq!(values(&args, "--ulimit"), vec!["nofile=1024:4096"]);
+        assert_eq!(values(&args, "--sysctl"), vec!["net.core.somaxconn=1024"]);
+        assert_eq!(values(&args, "--memory"), vec!["536870912"]);
+        assert_eq!(values(&args, "--memory-swap"), vec!["1073741824"]);
+        assert_eq!(values(&args, "--cpus"), vec!["1.5"]);
+        assert!(args.contains(&"--init".to_string()));
+        assert!(args.contains(&"--read-only".to_string()));
+        // defaults are left to the daemon
+        for flag in [
+            "--shm-size",
+            "--ipc",
+            "--runtime",
+            "--stop-signal",
+            "--pids-limit",
+        ] {
+            assert!(values(&args, flag).is_empty(), "{}", flag);
+        }
+        assert!(container.unsupported().is_empty());
+    }
+
+    #[test]
+    fn create_args_command() {
+        let (mut container, image) = fixture();
+
+        // only the command differs, the image's entrypoint applies
+        let args = container.create_args(&image);
+        assert!(values(&args, "--entrypoint").is_empty());
+        let image_at = args
+            .iter()
+            .position(|arg| arg == "registry.example.com/app:1.4")
+            .unwrap();
+        assert_eq!(args[image_at + 1..], ["serve", "--port", "8080"]);
+
+        // a multi part entrypoint goes in front of the command
+        container.config.entrypoint = Some(vec!["tini".into(), "--".into(), "/run.sh".into()]);
+        container.config.cmd = Some(vec!["serve".into()]);
+        let args = container.create_args(&image);
+        assert_eq!(values(&args, "--entrypoint"), vec!["tini"]);
+        assert_eq!(args[args.len() - 3..], ["--", "/run.sh", "serve"]);
+
+        // same as the image, nothing to repeat
+        container.config.entrypoint = image.config.entrypoint.clone();
+        container.config.cmd = image.config.cmd.clone();
+        let args = container.create_args(&image);
+        assert_eq!(args.last().unwrap(), "registry.example.com/app:1.4");
+    }
+
+    #[test]
+    fn unsupported() {
+        let (mut container, _) = fixture();
+        container.mounts.push(InspectMount {
+            kind: "tmpfs".into(),
+            destination: "/cache".into(),
+            ..Default::default()
+        });
+        container.host_config.links = Some(vec!["/db:/app/db".into()]);
+
+        assert_eq!(
+            container.unsupported(),
+            vec!["tmpfs mount at /cache", "links"]
+        );
+    }
+}
@@ -0,0 +1,365 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use leptos::*;
+
+use crate::{
+    components::{
+        container::{
+            standalone_summary, ContainerButtonsComponent, ContainerInfoComponent,
+            ContainerStatusComponent, StandaloneContainersComponent,
+        },
+        containers::EmptyNoticeComponent,
+        project::{ProjectComponent, ServiceComponent, ServiceContainersComponent},
+        shared::summary::SummaryComponent,
+    },
+    config::Config,
+    container::Container,
+    fields::ContainerSize,
+    filter::ContainerFilter,
+    project::{Project, Service},
+    util::dom_id,
+};
+
+fn render<F, N>(f: F) -> String
+where
+    F: FnOnce() -> N + 'static,
+    N: IntoView,
+{
+    ssr::render_to_string(f).to_string()
+}
+
+/// Status, buttons and info of a container, by element id
+fn container_fragments(c: &Container) -> Vec<(String, String)> {
+    let (status, buttons, info) = (c.clone(), c.clone(), c.clone());
+    vec![
+        (
+            dom_id(&["container-status", &c.names]),
+            render(move || view! { <ContainerStatusComponent c=status oob=true /> }),
+        ),
+        (
+            dom_id(&["container-actions", &c.names]),
+            render(move || view! { <ContainerButtonsComponent c=buttons oob=true /> }),
+        ),
+        (
+            dom_id(&["container-info", &c.names]),
+            render(move || view! { <ContainerInfoComponent c=info oob=true /> }),
+        ),
+    ]
+}
+
+fn summary_fragment(id: String, summary: String) -> (String, String) {
+    let fragment = {
+        let id = id.clone();
+        render(move || view! { <SummaryComponent id=id summary=summary oob=true /> })
+    };
+    (id, fragment)
+}
+
+/// What one browser's containers list shows, so it is only sent out of band
+/// swaps for the parts that changed. Only the fragments are replaced, which
+/// keeps open details and the output of running tasks.
+///
+/// Projects and services stay on the page once shown and are emptied when
+/// their containers are gone, e.g. while an update takes a project down and
+/// up again.
+///
+/// The inventory lists the containers without their sizes, which are slow to
+/// get. The sizes a page was rendered with are kept for the containers
+/// listed without one, so the info fragments and the size order stay.
+pub struct LiveView {
+    filter: ContainerFilter,
+    config: Config,
+    /// last known size of each container, by id
+    sizes: HashMap<String, ContainerSize>,
+    /// project, service and container names on the page
+    projects: BTreeMap<String, BTreeMap<String, Vec<String>>>,
+    standalone: Vec<String>,
+    /// html of each fragment as last rendered, by element id
+    fragments: HashMap<String, String>,
+}
+
+impl LiveView {
+    /// The view of a page rendered from `containers`
+    pub fn new(filter: ContainerFilter, config: Config, containers: Vec<Container>) -> LiveView {
+        let mut view = LiveView {
+            filter,
+            config,
+            sizes: HashMap::new(),
+            projects: BTreeMap::new(),
+            standalone: vec![],
+            fragments: HashMap::new(),
+        };
+        view.update(containers);
+        view
+    }
+
+    /// Remember a fragment, returning it when it differs from the last one
+    fn swap(&mut self, (id, fragment): (String, String)) -> Option<String> {
+        match self.fragments.insert(id, fragment.clone()) {
+            Some(previous) if previous == fragment => None,
+            _ => Some(fragment),
+        }
+    }
+
+    fn swap_containers(&mut self, containers: &[Container]) -> String {
+        containers
+            .iter()
+            .flat_map(container_fragments)
+            .filter_map(|fragment| self.swap(fragment))
+            .collect()
+    }
+
+    /// Out of band swaps for everything that changed since the last update,
+    /// empty when nothing did
+    pub fn update(&mut self, mut containers: Vec<Container>) -> String {
+        for c in containers.iter_mut() {
+            match c.size {
+                Some(size) => {
+                    self.sizes.insert(c.id.clone(), size);
+                }
+                None => c.size = self.sizes.get(&c.id).copied(),
+            }
+        }
+        let (projects, standalone) = self.filter.group(containers);
+        let empty = projects.is_empty() && standalone.is_empty();
+
+        let mut html = String::new();
+        html.extend(self.swap((
+            "containers-empty".to_string(),
+            render(move || view! { <EmptyNoticeComponent empty=empty oob=true /> }),
+        )));
+
+        let mut projects = projects
+            .into_iter()
+            .map(|p| (p.name.clone(), p))
+            .collect::<BTreeMap<_, _>>();
+        let names = self
+            .projects
+            .keys()
+            .chain(projects.keys())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        for name in names {
+            let project = projects.remove(&name).unwrap_or_else(|| Project {
+                name: name.clone(),
+                services: vec![],
+            });
+            html.push_str(&self.update_project(project));
+        }
+
+        let names = standalone
+            .iter()
+            .map(|c| c.names.clone())
+            .collect::<Vec<_>>();
+        let summary = summary_fragment(
+            "standalone-summary".to_string(),
+            standalone_summary(&standalone),
+        );
+        if names != self.standalone {
+            let containers = standalone.clone();
+            html.push_str(&render(move || {
+                view! { <StandaloneContainersComponent containers=containers oob=true /> }
+            }));
+            self.swap(summary);
+            self.swap_containers(&standalone);
+            self.standalone = names;
+        } else if !standalone.is_empty() {
+            html.extend(self.swap(summary));
+            html.push_str(&self.swap_containers(&standalone));
+        }
+
+        html
+    }
+
+    fn update_project(&mut self, project: Project) -> String {
+        let summary = summary_fragment(
+            dom_id(&["project-summary", &project.name]),
+            project.summary(),
+        );
+
+        let Some(shown) = self.projects.get(&project.name).cloned() else {
+            // new projects are added at the end until the page is reloaded
+            self.swap(summary);
+            let mut services = BTreeMap::new();
+            for s in project.services.iter() {
+                self.swap_service(&project.name, s);
+                services.insert(s.name.clone(), names(&s.containers));
+            }
+            self.projects.insert(project.name.clone(), services);
+
+            let update = project.update_options(&self.config.project(&project.name));
+            let view = render(move || view! { <ProjectComponent p=project update=update /> });
+            return format!(r#"<div hx-swap-oob="beforeend:#projects">{}</div>"#, view);
+        };
+
+        let mut html = String::new();
+        html.extend(self.swap(summary));
+
+        let mut services = project
+            .services
+            .into_iter()
+            .map(|s| (s.name.clone(), s))
+            .collect::<BTreeMap<_, _>>();
+        let service_names = shown
+            .keys()
+            .chain(services.keys())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        for name in service_names {
+            let service = services.remove(&name).unwrap_or_else(|| Service {
+                name: name.clone(),
+                containers: vec![],
+            });
+            let containers = names(&service.containers);
+
+            match shown.get(&name) {
+                None => {
+                    self.swap_service(&project.name, &service);
+                    let (project, selector) = (
+                        project.name.clone(),
+                        dom_id(&["project-services", &project.name]),
+                    );
+                    let view =
+                        render(move || view! { <ServiceComponent project=project s=service /> });
+                    html.push_str(&format!(
+                        r#"<div hx-swap-oob="beforeend:#{}">{}</div>"#,
+                        selector, view
+                    ));
+                }
+                Some(shown) if *shown != containers => {
+                    html.extend(self.swap(summary_fragment(
+                        dom_id(&["service-summary", &project.name, &service.name]),
+                        service.summary(),
+                    )));
+                    self.swap_containers(&service.containers);
+                    let project = project.name.clone();
+                    html.push_str(&render(move || {
+                        view! {
+                            <ServiceContainersComponent
+                                project=project
+                                service=service.name
+                                containers=service.containers
+                                oob=true
+                            />
+                        }
+                    }));
+                }
+                Some(_) => {
+                    html.extend(self.swap(summary_fragment(
+                        dom_id(&["service-summary", &project.name, &service.name]),
+                        service.summary(),
+                    )));
+                    html.push_str(&self.swap_containers(&service.containers));
+                }
+            }
+
+            if let Some(shown) = self.projects.get_mut(&project.name) {
+                shown.insert(name, containers);
+            }
+        }
+
+        html
+    }
+
+    /// Remember the fragments of a service that is rendered as a whole
+    fn swap_service(&mut self, project: &str, s: &Service) {
+        self.swap(summary_fragment(
+            dom_id(&["service-summary", project, &s.name]),
+            s.summary(),
+        ));
+        self.swap_containers(&s.containers);
+    }
+}
+
+fn names(containers: &[Container]) -> Vec<String> {
+    containers.iter().map(|c| c.names.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::test_container;
+
+    fn containers() -> Vec<Container> {
+        vec![
+            test_container("1", "app-web-1", "nginx", Some("app"), "running"),
+            test_container("2", "app-db-1", "postgres", Some("app"), "running"),
+            test_container("3", "loose", "alpine", None, "exited"),
+        ]
+    }
+
+    fn view() -> LiveView {
+        LiveView::new(ContainerFilter::default(), Config::default(), containers())
+    }
+
+    #[test]
+    fn no_change() {
+        let mut view = view();
+        assert_eq!(view.update(containers()), "");
+    }
+
+    #[test]
+    fn state_change() {
+        let mut view = view();
+        let mut containers = containers();
+        containers[0].state = "exited".to_string();
+
+        let html = view.update(containers.clone());
+
+        assert!(html.contains(r#"id="container-info--app-web-1""#));
+        assert!(html.contains(r#"id="project-summary--app""#));
+        assert!(!html.contains("app-db-1"));
+        assert!(!html.contains("beforeend"));
+        assert_eq!(view.update(containers), "");
+    }
+
+    #[test]
+    fn new_project() {
+        let mut view = view();
+        let mut containers = containers();
+        containers.push(test_container(
+            "4",
+            "blog-web-1",
+            "ghost",
+            Some("blog"),
+            "running",
+        ));
+
+        let html = view.update(containers);
+
+        assert!(html.contains(r#"<div hx-swap-oob="beforeend:#projects">"#));
+        assert!(html.contains("blog-web-1"));
+        assert!(!html.contains("app-web-1"));
+    }
+
+    #[test]
+    fn service_gone() {
+        let mut view = view();
+        let mut containers = containers();
+        containers.remove(1);
+
+        let html = view.update(containers.clone());
+
+        // the service stays on the page, emptied
+        assert!(html.contains(r#"id="service-containers--app--app-db-1""#));
+        assert!(!html.contains(r#"id="service-containers--app--app-web-1""#));
+        assert_eq!(view.update(containers), "");
+    }
+
+    #[test]
+    fn keeps_sizes() {
+        let mut sized = containers();
+        for c in sized.iter_mut() {
+            c.size = Some(ContainerSize {
+                rw: 1000,
+                virtual_size: None,
+            });
+        }
+        let mut view = LiveView::new(ContainerFilter::default(), Config::default(), sized);
+
+        // the inventory lists the containers without sizes
+        assert_eq!(view.update(containers()), "");
+    }
+}
@@ -0,0 +1,578 @@
+use std::{pin::Pin, process::Stdio, str::FromStr};
+
+use anyhow::{Context, Result};
+use futures::{stream, Stream, StreamExt};
+use regex::Regex;
+use serde_json::{Map, Value};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio_stream::wrappers::LinesStream;
+
+use crate::{container::Container, project::Project, util::empty_as_none};
+
+/// What to show the logs of
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogSource {
+    Container(String),
+    /// every container of a compose project, prefixed with their service
+    Project(String),
+}
+
+impl LogSource {
+    /// from the `/logs/:kind/:name` path
+    pub fn from_parts(kind: &str, name: String) -> Option<Self> {
+        match kind {
+            "container" => Some(Self::Container(name)),
+            "project" => Some(Self::Project(name)),
+            _ => None,
+        }
+    }
+
+    pub fn kind(&self) -> &str {
+        match self {
+            Self::Container(_) => "container",
+            Self::Project(_) => "project",
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Container(name) | Self::Project(name) => name,
+        }
+    }
+
+    /// e.g. `/logs/project/my-project`
+    pub fn path(&self) -> String {
+        format!("/logs/{}/{}", self.kind(), self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+impl LogStream {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        }
+    }
+}
+
+/// Options from the log viewer form, passed along in the query string
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct LogOptions {
+    /// number of lines to show from the end of the logs
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub tail: Option<u32>,
+    /// timestamp (2024-01-02T15:04) or relative (42m)
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub since: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub until: Option<String>,
+    /// checkbox, `on` when checked
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub timestamps: Option<String>,
+    /// `stdout`, `stderr` or both when not set
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub stream: Option<String>,
+    /// regex the lines have to match
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub filter: Option<String>,
+    /// comma separated json fields shown as columns, e.g. `level,msg,trace_id`
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub columns: Option<String>,
+    /// minimum level of json lines
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub level: Option<LogLevel>,
+    /// comma separated `field=value` pairs json lines have to match
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub fields: Option<String>,
+}
+
+/// Level of a json log line, from the `level`, `lvl` or `severity` field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 6] = [
+        Self::Trace,
+        Self::Debug,
+        Self::Info,
+        Self::Warn,
+        Self::Error,
+        Self::Fatal,
+    ];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Trace => "trace",
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+            Self::Fatal => "fatal",
+        }
+    }
+
+    /// Numeric levels as used by pino and bunyan
+    fn from_number(n: i64) -> Option<Self> {
+        match n {
+            ..=10 => Some(Self::Trace),
+            11..=20 => Some(Self::Debug),
+            21..=30 => Some(Self::Info),
+            31..=40 => Some(Self::Warn),
+            41..=50 => Some(Self::Error),
+            _ => Some(Self::Fatal),
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "trace" => Ok(Self::Trace),
+            "debug" => Ok(Self::Debug),
+            "info" | "information" | "notice" => Ok(Self::Info),
+            "warn" | "warning" => Ok(Self::Warn),
+            "error" | "err" => Ok(Self::Error),
+            "fatal" | "critical" | "crit" | "panic" | "emergency" | "alert" => Ok(Self::Fatal),
+            _ => Err(anyhow::anyhow!("unknown log level {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub container: String,
+    /// compose service, only set for project logs
+    pub service: Option<String>,
+    pub stream: LogStream,
+    pub timestamp: Option<String>,
+    pub text: String,
+    /// fields of the line when it is a json object
+    pub fields: Option<Map<String, Value>>,
+}
+
+/// A json field name and its value as text
+pub type FieldValue = (String, String);
+
+type LineStream = Pin<Box<dyn Stream<Item = Result<LogLine>> + Send>>;
+
+impl LogOptions {
+    pub const DEFAULT_TAIL: u32 = 200;
+    pub const DEFAULT_COLUMNS: &'static str = "level,msg,trace_id";
+
+    /// Check the options before connecting, so errors can be shown instead of the viewer
+    pub fn validate(&self) -> Result<()> {
+        self.filter_regex().map(|_| ())
+    }
+
+    pub fn timestamps(&self) -> bool {
+        self.timestamps.is_some()
+    }
+
+    fn filter_regex(&self) -> Result<Option<Regex>> {
+        self.filter
+            .as_deref()
+            .map(|filter| Regex::new(filter).context("invalid filter"))
+            .transpose()
+    }
+
+    fn docker_args(&self, container: &str, follow: bool) -> Vec<String> {
+        let mut args = vec!["logs".to_string()];
+
+        if follow {
+            args.push("--follow".into());
+        }
+
+        args.push("--tail".into());
+        args.push(
+            self.tail
+                .map(|tail| tail.to_string())
+                .unwrap_or_else(|| Self::DEFAULT_TAIL.to_string()),
+        );
+
+        if let Some(since) = &self.since {
+            args.push(format!("--since={}", since));
+        }
+
+        if let Some(until) = &self.until {
+            args.push(format!("--until={}", until));
+        }
+
+        if self.timestamps() {
+            args.push("--timestamps".into());
+        }
+
+        args.push(container.to_string());
+        args
+    }
+
+    /// json fields to show as columns
+    pub fn columns(&self) -> Vec<String> {
+        split_list(self.columns.as_deref().unwrap_or(Self::DEFAULT_COLUMNS))
+            .map(|column| column.to_string())
+            .collect()
+    }
+
+    fn field_filters(&self) -> Vec<(&str, &str)> {
+        split_list(self.fields.as_deref().unwrap_or_default())
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(k, v)| (k.trim(), v.trim()))
+            .collect()
+    }
+
+    /// The json filters only match json lines, plain text lines are left out when set.
+    /// `filter` is the compiled `filter` option, see `filter_regex`.
+    fn wants(&self, line: &LogLine, filter: Option<&Regex>) -> bool {
+        let stream = match self.stream.as_deref() {
+            Some(wanted) => wanted == line.stream.to_str(),
+            None => true,
+        };
+
+        let level = match self.level {
+            Some(min) => line.level().is_some_and(|level| level >= min),
+            None => true,
+        };
+
+        let fields = self
+            .field_filters()
+            .iter()
+            .all(|(k, v)| line.field(k).is_some_and(|value| value == *v));
+
+        let text = filter.is_none_or(|re| re.is_match(&line.text));
+
+        stream && level && fields && text
+    }
+}
+
+fn split_list(s: &str) -> impl Iterator<Item = &str> {
+    s.split(',')
+        .map(|val| val.trim())
+        .filter(|val| !val.is_empty())
+}
+
+/// Stream the log lines of a source. With `follow` the stream only ends when
+/// dropped, which also kills the `docker logs` processes.
+pub fn lines(
+    source: &LogSource,
+    options: &LogOptions,
+    follow: bool,
+) -> Result<impl Stream<Item = Result<LogLine>>> {
+    let filter = options.filter_regex()?;
+
+    let containers = match source {
+        LogSource::Container(name) => vec![(name.clone(), None)],
+        LogSource::Project(name) => Project::get(name)?
+            .containers()
+            .map(|c: &Container| (c.names.clone(), c.service()))
+            .collect(),
+    };
+
+    let streams = containers
+        .into_iter()
+        .map(|(container, service)| container_lines(container, service, options, follow))
+        .collect::<Result<Vec<_>>>()?;
+
+    let options = options.clone();
+
+    Ok(stream::select_all(streams).filter(move |line| {
+        let keep = match line {
+            Ok(line) => options.wants(line, filter.as_ref()),
+            Err(_) => true,
+        };
+        async move { keep }
+    }))
+}
+
+fn container_lines(
+    container: String,
+    service: Option<String>,
+    options: &LogOptions,
+    follow: bool,
+) -> Result<LineStream> {
+    let mut child = tokio::process::Command::new("docker")
+        .args(options.docker_args(&container, follow))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let stdout = child.stdout.take().context("logs: stdout take error")?;
+    let stderr = child.stderr.take().context("logs: stderr take error")?;
+
+    let stdout =
+        LinesStream::new(BufReader::new(stdout).lines()).map(|line| (LogStream::Stdout, line));
+    let stderr =
+        LinesStream::new(BufReader::new(stderr).lines()).map(|line| (LogStream::Stderr, line));
+
+    let timestamps = options.timestamps();
+
+    let lines = async_stream::try_stream! {
+        // keep the child alive for as long as the stream is
+        let _child = child;
+        let mut merged = stream::select(stdout, stderr);
+
+        while let Some((kind, line)) = merged.next().await {
+            let line = line.context("logs: read error")?;
+
+            let (timestamp, text) = match line.split_once(' ') {
+                Some((timestamp, text)) if timestamps => (Some(timestamp.to_string()), text.to_string()),
+                _ => (None, line),
+            };
+
+            let fields = parse_json_fields(&text);
+
+            yield LogLine {
+                container: container.clone(),
+                service: service.clone(),
+                stream: kind,
+                timestamp,
+                text,
+                fields,
+            };
+        }
+    };
+
+    Ok(Box::pin(lines))
+}
+
+fn parse_json_fields(text: &str) -> Option<Map<String, Value>> {
+    if !text.trim_start().starts_with('{') {
+        return None;
+    }
+
+    match serde_json::from_str(text) {
+        Ok(Value::Object(fields)) => Some(fields),
+        _ => None,
+    }
+}
+
+impl LogLine {
+    /// A json field as text. `level` and `msg` fall back to their common
+    /// aliases and dotted names look into nested objects, e.g. `http.status`.
+    pub fn field(&self, name: &str) -> Option<String> {
+        self.field_entry(name).map(|(_, value)| value)
+    }
+
+    /// Like `field`, along with the key that matched
+    fn field_entry(&self, name: &str) -> Option<(String, String)> {
+        let fields = self.fields.as_ref()?;
+
+        let aliases: &[&str] = match name {
+            "level" => &["level", "lvl", "severity", "log.level"],
+            "msg" => &["msg", "message"],
+            _ => &[name],
+        };
+
+        aliases.iter().find_map(|key| {
+            let value = fields.get(*key).or_else(|| {
+                let (first, rest) = key.split_once('.')?;
+                rest.split('.')
+                    .try_fold(fields.get(first)?, |value, key| value.get(key))
+            })?;
+
+            let value = match value {
+                Value::String(s) => s.clone(),
+                value => value.to_string(),
+            };
+
+            Some((key.to_string(), value))
+        })
+    }
+
+    /// The values of the columns, and the remaining fields as `(key, value)`
+    pub fn split_columns(&self, columns: &[String]) -> (Vec<FieldValue>, Vec<FieldValue>) {
+        let mut used = vec![];
+        let mut shown = vec![];
+
+        for column in columns {
+            if let Some((key, value)) = self.field_entry(column) {
+                used.push(key);
+                shown.push((column.clone(), value));
+            }
+        }
+
+        let rest = self
+            .fields
+            .iter()
+            .flatten()
+            .filter(|(key, _)| !used.contains(key))
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    value => value.to_string(),
+                };
+                (key.clone(), value)
+            })
+            .collect();
+
+        (shown, rest)
+    }
+
+    pub fn level(&self) -> Option<LogLevel> {
+        let level = self.field("level")?;
+        level
+            .parse()
+            .ok()
+            .or_else(|| level.parse::<i64>().ok().and_then(LogLevel::from_number))
+    }
+
+    /// plain text line for the download
+    pub fn to_text(&self) -> String {
+        let mut parts = vec![];
+        if let Some(timestamp) = &self.timestamp {
+            parts.push(timestamp.clone());
+        }
+        if let Some(service) = &self.service {
+            parts.push(format!("[{}]", service));
+        }
+        if self.stream == LogStream::Stderr {
+            parts.push("[stderr]".into());
+        }
+        parts.push(self.text.clone());
+        parts.join(" ")
+    }
+}
+
+/// Stable color for a service name, so every service gets its own color in project logs
+pub fn service_color(service: &str) -> &'static str {
+    const COLORS: [&str; 8] = [
+        "#1f77b4", "#ff7f0e", "#2ca02c", "#9467bd", "#8c564b", "#e377c2", "#17becf", "#bcbd22",
+    ];
+    let hash = service.bytes().fold(0usize, |hash, b| {
+        hash.wrapping_mul(31).wrapping_add(b as usize)
+    });
+    COLORS[hash % COLORS.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str) -> LogLine {
+        LogLine {
+            container: "web".to_string(),
+            service: None,
+            stream: LogStream::Stdout,
+            timestamp: None,
+            text: text.to_string(),
+            fields: super::parse_json_fields(text),
+        }
+    }
+
+    fn options(query: &str) -> LogOptions {
+        serde_urlencoded::from_str(query).unwrap()
+    }
+
+    #[test]
+    fn log_level() {
+        assert_eq!("WARNING".parse::<LogLevel>().unwrap(), LogLevel::Warn);
+        assert_eq!("notice".parse::<LogLevel>().unwrap(), LogLevel::Info);
+        assert_eq!("crit".parse::<LogLevel>().unwrap(), LogLevel::Fatal);
+        assert!("verbose".parse::<LogLevel>().is_err());
+        assert!(LogLevel::Debug < LogLevel::Error);
+
+        // pino
+        assert_eq!(line(r#"{"level":10}"#).level(), Some(LogLevel::Trace));
+        assert_eq!(line(r#"{"level":30}"#).level(), Some(LogLevel::Info));
+        assert_eq!(line(r#"{"level":50}"#).level(), Some(LogLevel::Error));
+        assert_eq!(line(r#"{"level":60}"#).level(), Some(LogLevel::Fatal));
+
+        assert_eq!(
+            line(r#"{"severity":"ERROR"}"#).level(),
+            Some(LogLevel::Error)
+        );
+        assert_eq!(
+            line(r#"{"log":{"level":"debug"}}"#).level(),
+            Some(LogLevel::Debug)
+        );
+        assert_eq!(line(r#"{"level":"chatty"}"#).level(), None);
+        assert_eq!(line("ERROR plain text").level(), None);
+    }
+
+    #[test]
+    fn parse_json_fields() {
+        assert!(super::parse_json_fields(r#"  {"msg":"hi"}"#).is_some());
+        assert!(super::parse_json_fields("plain text").is_none());
+        assert!(super::parse_json_fields("{not json").is_none());
+        assert!(super::parse_json_fields("[1, 2]").is_none());
+    }
+
+    #[test]
+    fn field() {
+        let line = line(
+            r#"{"lvl":"info","message":"request","http":{"status":502,"path":"/api"},"ok":false}"#,
+        );
+
+        assert_eq!(line.field("level"), Some("info".to_string()));
+        assert_eq!(line.field("msg"), Some("request".to_string()));
+        assert_eq!(line.field("http.status"), Some("502".to_string()));
+        assert_eq!(line.field("http.path"), Some("/api".to_string()));
+        assert_eq!(line.field("ok"), Some("false".to_string()));
+        assert_eq!(line.field("http.method"), None);
+        assert_eq!(line.field("trace_id"), None);
+    }
+
+    #[test]
+    fn wants() {
+        let error = line(r#"{"level":"error","msg":"db down","service":"api"}"#);
+        let info = line(r#"{"level":"info","msg":"db up","service":"worker"}"#);
+        let plain = line("db restarted");
+
+        let level = options("level=warn");
+        assert!(level.wants(&error, None));
+        assert!(!level.wants(&info, None));
+        assert!(!level.wants(&plain, None));
+
+        let fields = options("fields=service%3Dapi%2C+level%3Derror");
+        assert!(fields.wants(&error, None));
+        assert!(!fields.wants(&info, None));
+
+        let regex = options("filter=db+(down%7Crestarted)");
+        let filter = regex.filter_regex().unwrap();
+        assert!(regex.wants(&error, filter.as_ref()));
+        assert!(!regex.wants(&info, filter.as_ref()));
+        assert!(regex.wants(&plain, filter.as_ref()));
+
+        let stderr = options("stream=stderr");
+        assert!(!stderr.wants(&plain, None));
+
+        assert!(options("filter=(unclosed").validate().is_err());
+    }
+
+    #[test]
+    fn split_columns() {
+        let line = line(r#"{"lvl":"warn","msg":"slow","duration_ms":1200,"user":{"id":7}}"#);
+        let options = options("columns=level%2Cmsg%2Ctrace_id");
+
+        let (shown, rest) = line.split_columns(&options.columns());
+
+        assert_eq!(
+            shown,
+            vec![
+                ("level".to_string(), "warn".to_string()),
+                ("msg".to_string(), "slow".to_string()),
+            ]
+        );
+        assert_eq!(
+            rest,
+            vec![
+                ("duration_ms".to_string(), "1200".to_string()),
+                ("user".to_string(), r#"{"id":7}"#.to_string()),
+            ]
+        );
+    }
+}
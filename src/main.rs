@@ -1,32 +1,101 @@
+mod archive;
 mod args;
 mod components;
+mod compose;
+mod config;
 mod container;
+mod disk;
+mod events;
+mod exec;
+mod explorer;
+mod fields;
+mod filter;
 mod image;
+mod inspect;
+mod live;
+mod logs;
 mod model;
+mod network;
+mod project;
+mod prune;
+mod retention;
+mod stats;
+mod store;
 mod util;
+mod volume;
 
 use crate::model::{AppState, SseEvent};
 use anyhow::Context;
+use archive::Compression;
 use axum::{
-    extract::{Path, State},
-    response::{sse::Event, Html, Sse},
-    routing::get,
+    body::Body,
+    extract::{ws::WebSocketUpgrade, ConnectInfo, DefaultBodyLimit, Path, Query, RawQuery, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive},
+        Html, IntoResponse, Response, Sse,
+    },
+    routing::{get, post},
 };
 use clap::Parser;
 use components::{
-    container::{ContainerListComponent, ContainerListComponentProps},
-    images::{ImagesComponent, ImagesComponentProps},
+    container::{ContainerDetailComponent, ContainerDetailComponentProps},
+    containers::{
+        ContainerListComponent, ContainerListComponentProps, ContainersComponent,
+        ContainersComponentProps,
+    },
+    disk::{DiskComponent, DiskComponentProps},
+    explorer::{
+        ImageLayersComponent, ImageLayersComponentProps, LayerTreeComponent,
+        LayerTreeComponentProps,
+    },
+    images::{
+        ImageDetailComponent, ImageDetailComponentProps, ImageImportComponent,
+        ImageImportComponentProps, ImageTableComponent, ImageTableComponentProps, ImagesComponent,
+        ImagesComponentProps,
+    },
     index::{IndexComponent, IndexComponentProps},
+    logs::{
+        LogErrorComponent, LogErrorComponentProps, LogLineComponent, LogLineComponentProps,
+        LogViewerComponent, LogViewerComponentProps, LogsComponent, LogsComponentProps,
+    },
+    metrics::{
+        MetricsChartsComponent, MetricsChartsComponentProps, MetricsComponent,
+        MetricsComponentProps,
+    },
+    project::{ComposeConfigComponent, ComposeConfigComponentProps},
+    prune::{
+        PruneComponent, PruneComponentProps, PruneSectionComponent, PruneSectionComponentProps,
+    },
+    retention::{RetentionComponent, RetentionComponentProps},
     shared::sse::{SseResultsComponent, SseResultsComponentProps},
+    stats::{StatsComponent, StatsComponentProps},
+    terminal::{TerminalComponent, TerminalComponentProps},
+    volumes::{
+        VolumeDetailComponent, VolumeDetailComponentProps, VolumesComponent, VolumesComponentProps,
+    },
 };
 use container::Container;
-use futures::stream::Stream;
+use disk::{DiskUsage, DiskUsageDetail, FreeSpace, ProjectDiskUsage};
+use events::Inventory;
+use exec::{ExecParams, ExecSession};
+use explorer::TreeCache;
+use filter::{ContainerFilter, ContainerSort, ImageFilter};
+use futures::stream::{Stream, StreamExt};
 use image::Image;
+use inspect::{ContainerInspect, ImageInspect, VolumeInspect};
 use leptos::*;
-use model::{AppPage, SseTask};
-use std::sync::Arc;
-use tokio::sync::broadcast;
+use live::LiveView;
+use logs::{LogOptions, LogSource};
+use model::{AppPage, SseParams, SseTask};
+use project::Project;
+use prune::{Confirmations, PruneKind};
+use stats::Stats;
+use std::{collections::BTreeSet, net::SocketAddr, sync::Arc};
+use store::{MetricsRange, Store};
+use tokio::sync::{broadcast, RwLock};
 use util::AppError;
+use volume::Volume;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -34,8 +103,36 @@ async fn main() -> anyhow::Result<()> {
 
     tracing_subscriber::fmt::init();
 
+    let config = config::Config::load(args.config.as_deref())?;
+
     let (tx, _) = broadcast::channel::<SseEvent>(1000);
-    let app_state = Arc::new(AppState { tx });
+    let (stats_tx, _) = broadcast::channel(16);
+    let stats = Arc::new(RwLock::new(Stats::default()));
+    let store = Store::open(config.metrics.database.as_deref())?;
+    tokio::spawn(Stats::run(
+        stats.clone(),
+        stats_tx.clone(),
+        store.clone(),
+        config.metrics.clone(),
+    ));
+    let (inventory_tx, _) = broadcast::channel(16);
+    let inventory = Arc::new(RwLock::new(Inventory::new()));
+    tokio::spawn(Inventory::run(inventory.clone(), inventory_tx.clone()));
+    if config.retention.enabled {
+        tokio::spawn(retention::run(config.retention.clone(), store.clone()));
+    }
+
+    let app_state = Arc::new(AppState {
+        tx,
+        config,
+        stats,
+        stats_tx,
+        store,
+        inventory,
+        inventory_tx,
+        trees: TreeCache::default(),
+        confirmations: Confirmations::default(),
+    });
 
     let app = axum::Router::new()
         .route(
@@ -47,55 +144,770 @@ async fn main() -> anyhow::Result<()> {
                 )
             }),
         )
+        .route(
+            "/terminal.js",
+            get(|| async {
+                (
+                    [("content-type", "text/javascript")],
+                    include_str!("./scripts/terminal.js"),
+                )
+            }),
+        )
+        .route(
+            "/import.js",
+            get(|| async {
+                (
+                    [("content-type", "text/javascript")],
+                    include_str!("./scripts/import.js"),
+                )
+            }),
+        )
         .route("/components/containers", get(get_containers))
-        .route("/components/shared/sse/:name/:task", get(get_sse_task))
+        .route("/components/containers/list", get(get_container_list))
+        .route("/components/projects/:name/config", get(get_project_config))
+        .route(
+            "/components/shared/sse/:name/:task",
+            get(get_sse_task).post(post_sse_task),
+        )
         .route(
             "/components/shared/sse/connect/:name/:task",
             get(sse_connect_handler),
         )
         .route("/components/images", get(get_images))
+        .route("/components/images/list", get(get_image_list))
+        .route("/volumes", get(get_volumes_page))
+        .route("/components/volumes", get(get_volumes))
+        .route("/volumes/:name", get(get_volume_page))
+        .route("/components/volumes/:name", get(get_volume))
+        .route("/disk", get(get_disk_page))
+        .route("/components/disk", get(get_disk))
+        .route("/prune", get(get_prune_page))
+        .route("/components/prune", get(get_prune))
+        .route("/components/prune/:kind", get(get_prune_section))
+        .route("/retention", get(get_retention_page))
+        .route("/components/retention", get(get_retention))
+        .route("/stats/stream", get(stats_stream_handler))
+        .route("/events/stream", get(events_stream_handler))
         .route("/", get(get_index_page))
         .route("/images", get(get_images_page))
+        .route("/logs/:kind/:name", get(get_logs_page))
+        .route("/logs/:kind/:name/stream", get(logs_stream_handler))
+        .route("/logs/:kind/:name/download", get(logs_download_handler))
+        .route("/components/logs/:kind/:name", get(get_logs))
+        .route("/components/logs/:kind/:name/viewer", get(get_logs_viewer))
+        .route("/metrics/:kind/:name", get(get_metrics_page))
+        .route("/components/metrics/:kind/:name", get(get_metrics))
+        .route(
+            "/components/metrics/:kind/:name/charts",
+            get(get_metrics_charts),
+        )
+        .route("/images/export", get(image_export_handler))
+        .route(
+            "/images/import",
+            // archives are streamed into docker load, never held in memory
+            post(image_import_handler).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/images/:id", get(get_image_page))
+        .route("/components/images/:id", get(get_image))
+        .route("/images/:id/layers", get(get_image_layers_page))
+        .route("/components/images/:id/layers", get(get_image_layers))
+        .route(
+            "/components/images/:id/layers/tree",
+            get(get_image_layer_tree),
+        )
+        .route("/containers/:id", get(get_container_page))
+        .route("/components/containers/:id", get(get_container))
+        .route("/containers/:name/terminal", get(get_terminal_page))
+        .route("/containers/:name/exec", get(exec_handler))
+        .route("/components/containers/:name/terminal", get(get_terminal))
         .with_state(app_state);
 
     let listener = tokio::net::TcpListener::bind(format!("{}:{}", args.host, args.port)).await?;
 
     tracing::debug!("listening on {}", listener.local_addr()?);
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
 
-async fn get_containers() -> Result<Html<String>, AppError> {
-    let containers = Container::get_all()?;
-    let containers = ContainerListComponentProps { containers };
-    let view = ssr::render_to_string(|| ContainerListComponent(containers));
+/// Containers for the filter, the sizes are only listed when sorting by them
+fn filtered_containers(
+    filter: &ContainerFilter,
+) -> anyhow::Result<(Vec<String>, Vec<Project>, Vec<Container>)> {
+    let containers = match filter.sort {
+        ContainerSort::Size => Container::get_all_with_size()?,
+        _ => Container::get_all()?,
+    };
+    let project_names = containers
+        .iter()
+        .filter_map(|c| c.project())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let (projects, standalone) = filter.group(containers);
+    Ok((project_names, projects, standalone))
+}
+
+async fn get_containers(
+    State(app_state): State<Arc<AppState>>,
+    Query(filter): Query<ContainerFilter>,
+) -> Result<Html<String>, AppError> {
+    let (project_names, projects, standalone) = filtered_containers(&filter)?;
+    let props = ContainersComponentProps {
+        filter,
+        project_names,
+        projects,
+        standalone,
+        config: app_state.config.clone(),
+    };
+    let view = ssr::render_to_string(|| ContainersComponent(props));
+    Ok(Html(view.into()))
+}
+
+/// The containers for the filter form, the page url follows the filter
+async fn get_container_list(
+    State(app_state): State<Arc<AppState>>,
+    Query(filter): Query<ContainerFilter>,
+) -> Result<impl IntoResponse, AppError> {
+    let (_, projects, standalone) = filtered_containers(&filter)?;
+    let props = ContainerListComponentProps {
+        filter: filter.clone(),
+        projects,
+        standalone,
+        config: app_state.config.clone(),
+    };
+    let view = ssr::render_to_string(|| ContainerListComponent(props));
+    Ok((
+        [("HX-Replace-Url", format!("/{}", filter.query()))],
+        Html(view.to_string()),
+    ))
+}
+
+async fn get_project_config(
+    State(app_state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Html<String>, AppError> {
+    let files = Project::get_config(&name, &app_state.config).await?;
+    let props = ComposeConfigComponentProps { name, files };
+    let view = ssr::render_to_string(|| ComposeConfigComponent(props));
     Ok(Html(view.into()))
 }
 
-async fn get_images() -> Result<Html<String>, AppError> {
+async fn get_images(Query(filter): Query<ImageFilter>) -> Result<Html<String>, AppError> {
     let images = Image::get_all()?;
-    let props = ImagesComponentProps { images };
+    let usage = Image::usage(&images)?;
+    let images = filter.apply(images);
+    let props = ImagesComponentProps {
+        images,
+        usage,
+        filter,
+    };
     let view = ssr::render_to_string(|| ImagesComponent(props));
     Ok(Html(view.into()))
 }
 
-async fn get_index_page() -> Result<Html<String>, AppError> {
+/// The images for the filter form, the page url follows the filter
+async fn get_image_list(Query(filter): Query<ImageFilter>) -> Result<impl IntoResponse, AppError> {
+    let images = Image::get_all()?;
+    let usage = Image::usage(&images)?;
+    let images = filter.apply(images);
+    let props = ImageTableComponentProps { images, usage };
+    let view = ssr::render_to_string(|| ImageTableComponent(props));
+    Ok((
+        [("HX-Replace-Url", format!("/images{}", filter.query()))],
+        Html(view.to_string()),
+    ))
+}
+
+async fn get_volumes_page() -> Result<Html<String>, AppError> {
+    let props = IndexComponentProps {
+        app_page: AppPage::Volumes,
+    };
+    let view = ssr::render_to_string(|| IndexComponent(props));
+    Ok(render_index(view.to_string()))
+}
+
+async fn get_volumes() -> Result<Html<String>, AppError> {
+    let props = VolumesComponentProps {
+        volumes: Volume::get_all()?,
+        containers: Container::get_all()?,
+    };
+    let view = ssr::render_to_string(|| VolumesComponent(props));
+    Ok(Html(view.into()))
+}
+
+async fn get_volume_page(Path(name): Path<String>) -> Result<Html<String>, AppError> {
+    let props = IndexComponentProps {
+        app_page: AppPage::Volume(name),
+    };
+    let view = ssr::render_to_string(|| IndexComponent(props));
+    Ok(render_index(view.to_string()))
+}
+
+async fn get_volume(Path(name): Path<String>) -> Result<Html<String>, AppError> {
+    let (inspect, raw) = VolumeInspect::get_with_raw(&name)?;
+    let props = VolumeDetailComponentProps {
+        raw: serde_json::to_string_pretty(&raw)?,
+        volume: Volume::get_all()?
+            .into_iter()
+            .find(|volume| volume.name == inspect.name),
+        containers: Container::get_all()?,
+        inspect,
+    };
+    let view = ssr::render_to_string(|| VolumeDetailComponent(props));
+    Ok(Html(view.into()))
+}
+
+async fn get_disk_page() -> Result<Html<String>, AppError> {
+    let props = IndexComponentProps {
+        app_page: AppPage::Disk,
+    };
+    let view = ssr::render_to_string(|| IndexComponent(props));
+    Ok(render_index(view.to_string()))
+}
+
+async fn get_disk() -> Result<Html<String>, AppError> {
+    let detail = DiskUsageDetail::get()?;
+    let usage = Image::usage(&detail.images)?;
+    let props = DiskComponentProps {
+        usage: DiskUsage::get_all()?,
+        projects: ProjectDiskUsage::from_detail(&detail, &usage),
+        detail,
+        free: FreeSpace::get().map_err(|e| format!("{:#}", e)),
+    };
+    let view = ssr::render_to_string(|| DiskComponent(props));
+    Ok(Html(view.into()))
+}
+
+async fn get_prune_page() -> Result<Html<String>, AppError> {
+    let props = IndexComponentProps {
+        app_page: AppPage::Prune,
+    };
+    let view = ssr::render_to_string(|| IndexComponent(props));
+    Ok(render_index(view.to_string()))
+}
+
+async fn get_prune() -> Result<Html<String>, AppError> {
+    let previews = PruneKind::ALL
+        .into_iter()
+        .map(|kind| (kind, kind.preview().map_err(|e| format!("{:#}", e))))
+        .collect();
+    let props = PruneComponentProps { previews };
+    let view = ssr::render_to_string(|| PruneComponent(props));
+    Ok(Html(view.into()))
+}
+
+async fn get_prune_section(Path(kind): Path<String>) -> Result<Html<String>, AppError> {
+    let kind = PruneKind::from_str(&kind).context("invalid prune kind")?;
+    let props = PruneSectionComponentProps {
+        kind,
+        items: kind.preview().map_err(|e| format!("{:#}", e)),
+    };
+    let view = ssr::render_to_string(|| PruneSectionComponent(props));
+    Ok(Html(view.into()))
+}
+
+async fn get_retention_page() -> Result<Html<String>, AppError> {
+    let props = IndexComponentProps {
+        app_page: AppPage::Retention,
+    };
+    let view = ssr::render_to_string(|| IndexComponent(props));
+    Ok(render_index(view.to_string()))
+}
+
+async fn get_retention(State(app_state): State<Arc<AppState>>) -> Result<Html<String>, AppError> {
+    let config = app_state.config.retention.clone();
+    let preview_config = config.clone();
+    let props = RetentionComponentProps {
+        items: app_state
+            .store
+            .run(move |store| retention::preview(&preview_config, store))
+            .await
+            .map_err(|e| format!("{:#}", e)),
+        history: app_state
+            .store
+            .run(|store| store.retention_history(100))
+            .await
+            .map_err(|e| format!("{:#}", e)),
+        config,
+    };
+    let view = ssr::render_to_string(|| RetentionComponent(props));
+    Ok(Html(view.into()))
+}
+
+async fn get_index_page(Query(filter): Query<ContainerFilter>) -> Result<Html<String>, AppError> {
+    let props = IndexComponentProps {
+        app_page: AppPage::Index(filter),
+    };
+    let view = ssr::render_to_string(|| IndexComponent(props));
+    Ok(render_index(view.to_string()))
+}
+
+async fn get_images_page(Query(filter): Query<ImageFilter>) -> Result<Html<String>, AppError> {
     let props = IndexComponentProps {
-        app_page: AppPage::Index,
+        app_page: AppPage::Images(filter),
     };
     let view = ssr::render_to_string(|| IndexComponent(props));
     Ok(render_index(view.to_string()))
 }
 
-async fn get_images_page() -> Result<Html<String>, AppError> {
+async fn get_logs_page(
+    Path((kind, name)): Path<(String, String)>,
+) -> Result<Html<String>, AppError> {
+    let source = LogSource::from_parts(&kind, name).context("invalid log source")?;
     let props = IndexComponentProps {
-        app_page: AppPage::Images,
+        app_page: AppPage::Logs(source),
     };
     let view = ssr::render_to_string(|| IndexComponent(props));
     Ok(render_index(view.to_string()))
 }
 
+async fn get_logs(Path((kind, name)): Path<(String, String)>) -> Result<Html<String>, AppError> {
+    let source = LogSource::from_parts(&kind, name).context("invalid log source")?;
+    let props = LogsComponentProps { source };
+    let view = ssr::render_to_string(|| LogsComponent(props));
+    Ok(Html(view.into()))
+}
+
+async fn get_logs_viewer(
+    Path((kind, name)): Path<(String, String)>,
+    Query(options): Query<LogOptions>,
+    RawQuery(query): RawQuery,
+) -> Result<Html<String>, AppError> {
+    let source = LogSource::from_parts(&kind, name).context("invalid log source")?;
+
+    let view = match options.validate() {
+        Ok(_) => {
+            let props = LogViewerComponentProps { source, query };
+            ssr::render_to_string(|| LogViewerComponent(props))
+        }
+        Err(e) => {
+            let props = LogErrorComponentProps {
+                error: format!("{:#}", e),
+            };
+            ssr::render_to_string(|| LogErrorComponent(props))
+        }
+    };
+
+    Ok(Html(view.into()))
+}
+
+/// Follow the logs for as long as the browser stays connected
+async fn logs_stream_handler(
+    Path((kind, name)): Path<(String, String)>,
+    Query(options): Query<LogOptions>,
+) -> Result<Sse<impl Stream<Item = Result<Event, anyhow::Error>>>, AppError> {
+    let source = LogSource::from_parts(&kind, name).context("invalid log source")?;
+    let lines = logs::lines(&source, &options, true);
+    let columns = options.columns();
+
+    let stream = async_stream::stream! {
+        let render_error = |e: anyhow::Error| {
+            let props = LogErrorComponentProps { error: format!("{:#}", e) };
+            ssr::render_to_string(|| LogErrorComponent(props)).to_string()
+        };
+
+        match lines {
+            Ok(lines) => {
+                let mut lines = std::pin::pin!(lines);
+                while let Some(line) = lines.next().await {
+                    let data = match line {
+                        Ok(line) => {
+                            let props = LogLineComponentProps { line, columns: columns.clone() };
+                            ssr::render_to_string(|| LogLineComponent(props)).to_string()
+                        }
+                        Err(e) => render_error(e),
+                    };
+                    let res: Result<_, anyhow::Error> = Ok(Event::default().data(data).event("log"));
+                    yield res;
+                }
+            }
+            Err(e) => {
+                let res: Result<_, anyhow::Error> = Ok(Event::default().data(render_error(e)).event("log"));
+                yield res;
+            }
+        }
+
+        // keep the connection open once the logs end,
+        // otherwise the browser reconnects and replays them
+        futures::future::pending::<()>().await;
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Stats of every container and project after each sample, as
+/// `stats-<container>` and `stats-project-<project>` events
+async fn stats_stream_handler(
+    State(app_state): State<Arc<AppState>>,
+) -> Sse<impl Stream<Item = Result<Event, anyhow::Error>>> {
+    let mut rx = app_state.stats_tx.subscribe();
+
+    let stream = async_stream::stream! {
+        loop {
+            let events = {
+                let stats = app_state.stats.read().await;
+                let containers = stats
+                    .containers
+                    .iter()
+                    .map(|(name, history)| (format!("stats-{}", name), history.clone()));
+                let projects = stats
+                    .projects
+                    .iter()
+                    .map(|(name, history)| (format!("stats-project-{}", name), history.clone()));
+                containers.chain(projects).collect::<Vec<_>>()
+            };
+
+            for (event, history) in events {
+                let props = StatsComponentProps { history };
+                let data = ssr::render_to_string(|| StatsComponent(props)).to_string();
+                let res: Result<_, anyhow::Error> = Ok(Event::default().data(data).event(event));
+                yield res;
+            }
+
+            match rx.recv().await {
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Changes to the containers shown for the filter, as out of band swaps
+async fn events_stream_handler(
+    State(app_state): State<Arc<AppState>>,
+    Query(filter): Query<ContainerFilter>,
+) -> Sse<impl Stream<Item = Result<Event, anyhow::Error>>> {
+    let mut rx = app_state.inventory_tx.subscribe();
+    // the page sorted by size was rendered with the sizes, see `LiveView`
+    let sizes = match filter.sort {
+        ContainerSort::Size => tokio::task::spawn_blocking(Container::get_all_with_size)
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|containers| containers)
+            .map_err(|e| tracing::warn!("failed to list container sizes: {:#}", e))
+            .ok(),
+        _ => None,
+    };
+    let containers = match sizes {
+        Some(containers) => containers,
+        None => app_state.inventory.read().await.containers.clone(),
+    };
+    let mut view = LiveView::new(filter, app_state.config.clone(), containers);
+
+    let stream = async_stream::stream! {
+        loop {
+            match rx.recv().await {
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+
+            let containers = app_state.inventory.read().await.containers.clone();
+            let data = view.update(containers);
+            if !data.is_empty() {
+                let res: Result<_, anyhow::Error> = Ok(Event::default().data(data).event("inventory"));
+                yield res;
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// The selected range of the logs as a text file
+async fn logs_download_handler(
+    Path((kind, name)): Path<(String, String)>,
+    Query(options): Query<LogOptions>,
+) -> Result<impl IntoResponse, AppError> {
+    let source = LogSource::from_parts(&kind, name).context("invalid log source")?;
+    let lines = logs::lines(&source, &options, false)?;
+    let body =
+        Body::from_stream(lines.map(|line| line.map(|line| format!("{}\n", line.to_text()))));
+
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                "text/plain; charset=utf-8".to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.log\"", source.name()),
+            ),
+        ],
+        body,
+    ))
+}
+
+/// `docker save` of the selected images, compressed as asked
+async fn image_export_handler(RawQuery(query): RawQuery) -> Result<impl IntoResponse, AppError> {
+    // one `image` parameter per checked image
+    let pairs =
+        serde_urlencoded::from_str::<Vec<(String, String)>>(query.as_deref().unwrap_or_default())?;
+    let references = pairs
+        .iter()
+        .filter(|(key, _)| key == "image")
+        .map(|(_, value)| value.clone())
+        .collect::<Vec<_>>();
+    let compression = match pairs.iter().find(|(key, _)| key == "compression") {
+        Some((_, value)) => Compression::from_str(value).context("invalid compression")?,
+        None => Compression::default(),
+    };
+
+    archive::validate_export(&references, &Image::get_all()?)?;
+    let file_name = archive::file_name(&references, compression);
+    let body = Body::from_stream(archive::export(references, compression)?);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, compression.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ],
+        body,
+    ))
+}
+
+/// `docker load` of an uploaded archive, the request body is the archive
+/// Browsers send cross-site form posts without a preflight, the upload has to
+/// come from a page of this host, see `exec::check_origin`
+async fn image_import_handler(
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, AppError> {
+    let value = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Err(e) = exec::check_origin(value(header::ORIGIN), value(header::HOST)) {
+        tracing::warn!(target: "audit", client = %client, "image import denied: {:#}", e);
+        return Ok((StatusCode::FORBIDDEN, format!("{:#}", e)).into_response());
+    }
+
+    let props = ImageImportComponentProps {
+        result: archive::import(body.into_data_stream())
+            .await
+            .map_err(|e| format!("{:#}", e)),
+    };
+    let view = ssr::render_to_string(|| ImageImportComponent(props));
+    Ok(Html(view.to_string()).into_response())
+}
+
+async fn get_container_page(Path(id): Path<String>) -> Result<Html<String>, AppError> {
+    let props = IndexComponentProps {
+        app_page: AppPage::Container(id),
+    };
+    let view = ssr::render_to_string(|| IndexComponent(props));
+    Ok(render_index(view.to_string()))
+}
+
+async fn get_container(Path(id): Path<String>) -> Result<Html<String>, AppError> {
+    let (inspect, mut raw) = ContainerInspect::get_with_raw(&id)?;
+    inspect::mask_raw_env(&mut raw);
+
+    let top = inspect
+        .state
+        .running
+        .then(|| inspect.top().map_err(|e| format!("{:#}", e)));
+
+    let props = ContainerDetailComponentProps {
+        raw: serde_json::to_string_pretty(&raw)?,
+        inspect,
+        top,
+    };
+    let view = ssr::render_to_string(|| ContainerDetailComponent(props));
+    Ok(Html(view.into()))
+}
+
+async fn get_image_page(Path(id): Path<String>) -> Result<Html<String>, AppError> {
+    let props = IndexComponentProps {
+        app_page: AppPage::Image(id),
+    };
+    let view = ssr::render_to_string(|| IndexComponent(props));
+    Ok(render_index(view.to_string()))
+}
+
+async fn get_image(Path(id): Path<String>) -> Result<Html<String>, AppError> {
+    let (inspect, mut raw) = ImageInspect::get_with_raw(&id)?;
+    inspect::mask_raw_env(&mut raw);
+
+    let props = ImageDetailComponentProps {
+        raw: serde_json::to_string_pretty(&raw)?,
+        history: Image::history(&inspect.id).map_err(|e| format!("{:#}", e)),
+        usage: Image::usage(&Image::get_all()?)?
+            .remove(&inspect.id)
+            .unwrap_or_default(),
+        inspect,
+    };
+    let view = ssr::render_to_string(|| ImageDetailComponent(props));
+    Ok(Html(view.into()))
+}
+
+async fn get_image_layers_page(Path(id): Path<String>) -> Result<Html<String>, AppError> {
+    let props = IndexComponentProps {
+        app_page: AppPage::ImageLayers(id),
+    };
+    let view = ssr::render_to_string(|| IndexComponent(props));
+    Ok(render_index(view.to_string()))
+}
+
+async fn get_image_layers(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, AppError> {
+    // saved by id, which also keeps anything that looks like an option out of docker save
+    let inspect = ImageInspect::get(&id)?;
+    let props = ImageLayersComponentProps {
+        name: inspect
+            .repo_tags
+            .first()
+            .cloned()
+            .unwrap_or(inspect.id.clone()),
+        tree: app_state
+            .trees
+            .get(&inspect.id)
+            .await
+            .map_err(|e| format!("{:#}", e)),
+        id: inspect.id,
+    };
+    let view = ssr::render_to_string(|| ImageLayersComponent(props));
+    Ok(Html(view.into()))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct LayerTreeQuery {
+    layer: usize,
+    #[serde(default)]
+    path: String,
+}
+
+async fn get_image_layer_tree(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<LayerTreeQuery>,
+) -> Result<Html<String>, AppError> {
+    let inspect = ImageInspect::get(&id)?;
+    let props = LayerTreeComponentProps {
+        tree: app_state.trees.get(&inspect.id).await?,
+        layer: query.layer,
+        path: query.path,
+    };
+    let view = ssr::render_to_string(|| LayerTreeComponent(props));
+    Ok(Html(view.into()))
+}
+
+async fn get_metrics_page(
+    Path((kind, name)): Path<(String, String)>,
+) -> Result<Html<String>, AppError> {
+    let source = LogSource::from_parts(&kind, name).context("invalid metrics source")?;
+    let props = IndexComponentProps {
+        app_page: AppPage::Metrics(source),
+    };
+    let view = ssr::render_to_string(|| IndexComponent(props));
+    Ok(render_index(view.to_string()))
+}
+
+async fn get_metrics(Path((kind, name)): Path<(String, String)>) -> Result<Html<String>, AppError> {
+    let source = LogSource::from_parts(&kind, name).context("invalid metrics source")?;
+    let props = MetricsComponentProps { source };
+    let view = ssr::render_to_string(|| MetricsComponent(props));
+    Ok(Html(view.into()))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct MetricsQuery {
+    range: Option<String>,
+}
+
+async fn get_metrics_charts(
+    State(app_state): State<Arc<AppState>>,
+    Path((kind, name)): Path<(String, String)>,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Html<String>, AppError> {
+    let source = LogSource::from_parts(&kind, name).context("invalid metrics source")?;
+    let range = match query.range {
+        Some(range) => MetricsRange::from_str(&range).context("invalid range")?,
+        None => MetricsRange::default(),
+    };
+
+    // container charts also show the tasks that ran on their project
+    let mut sources = vec![source.clone()];
+    if let LogSource::Container(name) = &source {
+        let project = Container::get_all()?
+            .into_iter()
+            .find(|c| &c.names == name)
+            .and_then(|c| c.project());
+        sources.extend(project.map(LogSource::Project));
+    }
+
+    let (samples, annotations) = app_state
+        .store
+        .run(move |store| {
+            Ok((
+                store.series(&source, range)?,
+                store.annotations(&sources, range)?,
+            ))
+        })
+        .await?;
+
+    let props = MetricsChartsComponentProps {
+        range,
+        samples,
+        annotations,
+        now: store::unix_secs(std::time::SystemTime::now()),
+    };
+    let view = ssr::render_to_string(|| MetricsChartsComponent(props));
+    Ok(Html(view.into()))
+}
+
+async fn get_terminal_page(Path(name): Path<String>) -> Result<Html<String>, AppError> {
+    let props = IndexComponentProps {
+        app_page: AppPage::Terminal(name),
+    };
+    let view = ssr::render_to_string(|| IndexComponent(props));
+    Ok(render_index(view.to_string()))
+}
+
+async fn get_terminal(
+    State(app_state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Html<String>, AppError> {
+    let props = TerminalComponentProps {
+        name,
+        config: app_state.config.exec.clone(),
+    };
+    let view = ssr::render_to_string(|| TerminalComponent(props));
+    Ok(Html(view.into()))
+}
+
+/// Interactive exec session, see `ExecSession::run`
+async fn exec_handler(
+    State(app_state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(params): Query<ExecParams>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+    let value = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Err(e) = exec::check_origin(value(header::ORIGIN), value(header::HOST)) {
+        tracing::warn!(target: "audit", client = %client, container = %name, "exec session denied: {:#}", e);
+        return Ok((StatusCode::FORBIDDEN, format!("{:#}", e)).into_response());
+    }
+
+    let session = ExecSession::new(name, params, client, &app_state.config.exec).inspect_err(
+        |e| tracing::warn!(target: "audit", client = %client, "exec session denied: {:#}", e),
+    )?;
+
+    Ok(ws.on_upgrade(|socket| session.run(socket)).into_response())
+}
+
 fn render_index(view: String) -> Html<String> {
     // add doctype here because leptos strips it
     Html(format!("<!DOCTYPE html>{}", view))
@@ -103,18 +915,64 @@ fn render_index(view: String) -> Html<String> {
 
 async fn get_sse_task(
     Path((name, task)): Path<(String, String)>,
+    Query(params): Query<SseParams>,
+    RawQuery(query): RawQuery,
 ) -> Result<Html<String>, AppError> {
     let props = SseResultsComponentProps {
+        event: params.event_name(&name),
         name,
         task: SseTask::from_str(&task).context("get_containers_task: invalid task")?,
+        query,
     };
     let view = ssr::render_to_string(|| SseResultsComponent(props));
     Ok(Html(view.into()))
 }
 
+/// The task of a posted form, the prune forms are posted. A confirmation of
+/// the checked volumes is exchanged for a token, see `prune::Confirmations`.
+async fn post_sse_task(
+    State(app_state): State<Arc<AppState>>,
+    Path((name, task)): Path<(String, String)>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Response, AppError> {
+    let value = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Err(e) = exec::check_origin(value(header::ORIGIN), value(header::HOST)) {
+        tracing::warn!(target: "audit", client = %client, task = %task, "task post denied: {:#}", e);
+        return Ok((StatusCode::FORBIDDEN, format!("{:#}", e)).into_response());
+    }
+
+    let task = SseTask::from_str(&task).context("post_sse_task: invalid task")?;
+    let mut pairs = serde_urlencoded::from_str::<Vec<(String, String)>>(&body)?;
+    if pairs.iter().any(|(k, v)| k == "confirm" && v == "true") {
+        let volumes = pairs
+            .iter()
+            .filter(|(k, _)| k == PruneKind::Volumes.param())
+            .map(|(_, value)| value.clone())
+            .collect();
+        let token = app_state.confirmations.issue(volumes)?;
+        pairs.retain(|(k, _)| k != "confirm");
+        pairs.push(("confirm".to_string(), token));
+    }
+    let query = serde_urlencoded::to_string(&pairs)?;
+    let params = serde_urlencoded::from_str::<SseParams>(&query)?;
+
+    let props = SseResultsComponentProps {
+        event: params.event_name(&name),
+        name,
+        task,
+        query: Some(query),
+    };
+    let view = ssr::render_to_string(|| SseResultsComponent(props));
+    Ok(Html(view.to_string()).into_response())
+}
+
 async fn sse_connect_handler(
     State(app_state): State<Arc<AppState>>,
     Path((name, task)): Path<(String, String)>,
+    Query(params): Query<SseParams>,
+    RawQuery(query): RawQuery,
 ) -> Result<Sse<impl Stream<Item = Result<Event, anyhow::Error>>>, AppError> {
     let mut rx = app_state.tx.subscribe();
 
@@ -142,10 +1000,46 @@ async fn sse_connect_handler(
 
     let task = SseTask::from_str(&task);
 
+    // the checkboxes of the prune previews, one parameter per checked item
+    let pairs =
+        serde_urlencoded::from_str::<Vec<(String, String)>>(query.as_deref().unwrap_or_default())?;
+    let selected = |key: &str| {
+        pairs
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, value)| value.clone())
+            .collect::<Vec<_>>()
+    };
+
+    // tasks that change what is running are marked on the metrics charts
+    let annotation = match &task {
+        Some(task @ (SseTask::Update | SseTask::Pull)) => {
+            Some((LogSource::Project(name.clone()), task.to_string()))
+        }
+        Some(task @ (SseTask::ServicePull | SseTask::ServiceRecreate)) => Some((
+            LogSource::Project(name.clone()),
+            format!("{} {}", task, params.service.clone().unwrap_or_default()),
+        )),
+        Some(task @ SseTask::ContainerRecreate) => {
+            Some((LogSource::Container(name.clone()), task.to_string()))
+        }
+        _ => None,
+    };
+    if let Some((source, task)) = annotation {
+        let (name, annotated) = (source.name().to_string(), task.clone());
+        let result = app_state
+            .store
+            .run(move |store| store.annotate(&source, &annotated))
+            .await;
+        if let Err(e) = result {
+            tracing::warn!("failed to record {} on {}: {:#}", task, name, e);
+        }
+    }
+
     match task {
         Some(SseTask::Update) => {
             tokio::spawn(async move {
-                match Container::update(name, &app_state.tx).await {
+                match Project::update(name, &app_state.config, &app_state.tx).await {
                     Ok(_) => {}
                     Err(e) => tracing::error!("sse_handler update error: {}", e),
                 }
@@ -153,25 +1047,148 @@ async fn sse_connect_handler(
         }
         Some(SseTask::Pull) => {
             tokio::spawn(async move {
-                match Container::pull(name, &app_state.tx).await {
+                match Project::pull(name, &app_state.config, &app_state.tx).await {
                     Ok(_) => {}
                     Err(e) => tracing::error!("sse_handler pull error: {}", e),
                 }
             });
         }
-        Some(SseTask::GetConfig) => {
+        Some(
+            task @ (SseTask::PruneContainers
+            | SseTask::PruneNetworks
+            | SseTask::PruneVolumes
+            | SseTask::PruneImages
+            | SseTask::PruneBuildCache),
+        ) => {
+            let kind = PruneKind::from_task(&task).context("sse_handler: invalid prune task")?;
+            let ids = selected(kind.param());
+            let confirmed = params
+                .confirm
+                .as_deref()
+                .map(|token| app_state.confirmations.take(token))
+                .unwrap_or_default();
+            tokio::spawn(async move {
+                match kind.prune(&name, ids, &confirmed, &app_state.tx).await {
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("sse_handler prune error: {}", e),
+                }
+            });
+        }
+        Some(SseTask::PruneSystem) => {
+            let kinds = selected("kind");
+            let selected = PruneKind::ALL
+                .into_iter()
+                .filter(|kind| kinds.iter().any(|k| k == kind.to_str()))
+                .map(|kind| (kind, selected(kind.param())))
+                .collect();
+            let confirmed = params
+                .confirm
+                .as_deref()
+                .map(|token| app_state.confirmations.take(token))
+                .unwrap_or_default();
             tokio::spawn(async move {
-                match Container::get_config(name, &app_state.tx).await {
+                match prune::system_prune(&name, selected, &confirmed, &app_state.tx).await {
                     Ok(_) => {}
-                    Err(e) => tracing::error!("sse_handler get config error: {}", e),
+                    Err(e) => tracing::error!("sse_handler system prune error: {}", e),
                 }
             });
         }
-        Some(SseTask::PruneImages) => {
+        Some(SseTask::RetentionRun) => {
             tokio::spawn(async move {
-                match Image::prune(SseTask::PruneImages.to_str(), &app_state.tx).await {
+                let config = &app_state.config.retention;
+                match retention::run_now(&name, config, &app_state.store, &app_state.tx).await {
                     Ok(_) => {}
-                    Err(e) => tracing::error!("sse_handler prune images error: {}", e),
+                    Err(e) => tracing::error!("sse_handler retention error: {}", e),
+                }
+            });
+        }
+        Some(
+            task @ (SseTask::ServicePull
+            | SseTask::ServiceRecreate
+            | SseTask::ServiceRestart
+            | SseTask::ServiceStop
+            | SseTask::ServiceStart
+            | SseTask::ServiceLogs),
+        ) => {
+            let service = params.service.context("sse_handler: missing service")?;
+            tokio::spawn(async move {
+                match Project::service_task(name, service, task, &app_state.config, &app_state.tx)
+                    .await
+                {
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("sse_handler service task error: {}", e),
+                }
+            });
+        }
+        Some(
+            task @ (SseTask::ContainerStart
+            | SseTask::ContainerStop
+            | SseTask::ContainerRestart
+            | SseTask::ContainerKill
+            | SseTask::ContainerPause
+            | SseTask::ContainerUnpause
+            | SseTask::ContainerRemove),
+        ) => {
+            tokio::spawn(async move {
+                match Container::lifecycle(name, task, params.signal, &app_state.tx).await {
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("sse_handler container task error: {}", e),
+                }
+            });
+        }
+        Some(SseTask::ContainerRecreate) => {
+            tokio::spawn(async move {
+                match Container::recreate(name, &app_state.tx).await {
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("sse_handler recreate error: {}", e),
+                }
+            });
+        }
+        Some(SseTask::ImageRemove) => {
+            tokio::spawn(async move {
+                match Image::remove(name, params.force, &app_state.tx).await {
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("sse_handler image remove error: {}", e),
+                }
+            });
+        }
+        Some(SseTask::ImagePull) => {
+            let reference = params.reference.context("sse_handler: missing reference")?;
+            tokio::spawn(async move {
+                match Image::pull(&name, reference, params.platform, &app_state.tx).await {
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("sse_handler image pull error: {}", e),
+                }
+            });
+        }
+        Some(SseTask::VolumeCreate) => {
+            let volume = params.volume.context("sse_handler: missing volume")?;
+            tokio::spawn(async move {
+                match Volume::create(&name, volume, params.driver, params.labels, &app_state.tx)
+                    .await
+                {
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("sse_handler volume create error: {}", e),
+                }
+            });
+        }
+        Some(SseTask::VolumeRemove) => {
+            tokio::spawn(async move {
+                match Volume::remove(name, &app_state.tx).await {
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("sse_handler volume remove error: {}", e),
+                }
+            });
+        }
+        Some(task @ (SseTask::ImageUntag | SseTask::ImageTag)) => {
+            let tag = params.tag.context("sse_handler: missing tag")?;
+            tokio::spawn(async move {
+                let result = match task {
+                    SseTask::ImageUntag => Image::untag(name, tag, &app_state.tx).await,
+                    _ => Image::tag(name, tag, &app_state.tx).await,
+                };
+                if let Err(e) = result {
+                    tracing::error!("sse_handler image {} error: {}", task, e);
                 }
             });
         }
@@ -180,5 +1197,5 @@ async fn sse_connect_handler(
         }
     }
 
-    return Ok(Sse::new(stream));
+    Ok(Sse::new(stream))
 }
@@ -1,9 +1,26 @@
 use std::{
     convert,
     fmt::{self, Display, Formatter},
+    sync::Arc,
 };
 
-use tokio::sync::broadcast;
+use serde::{
+    de::{value::StrDeserializer, IntoDeserializer},
+    Deserialize,
+};
+use tokio::sync::{broadcast, RwLock};
+
+use crate::{
+    config::Config,
+    events::Inventory,
+    explorer::TreeCache,
+    filter::{ContainerFilter, ImageFilter},
+    logs::LogSource,
+    prune::Confirmations,
+    stats::Stats,
+    store::Store,
+    util::empty_as_none,
+};
 
 #[derive(Debug, Clone, serde::Deserialize)]
 pub struct SseEvent {
@@ -13,20 +30,98 @@ pub struct SseEvent {
 
 pub struct AppState {
     pub tx: broadcast::Sender<SseEvent>,
+    pub config: Config,
+    pub stats: Arc<RwLock<Stats>>,
+    /// notified after each `docker stats` sample, see `Stats::run`
+    pub stats_tx: broadcast::Sender<()>,
+    pub store: Store,
+    pub inventory: Arc<RwLock<Inventory>>,
+    /// notified after the containers were listed again on a docker event, see `Inventory::run`
+    pub inventory_tx: broadcast::Sender<()>,
+    /// images read by the layer explorer
+    pub trees: TreeCache,
+    /// volume prunes confirmed by a form post
+    pub confirmations: Confirmations,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum AppPage {
-    Index,
-    Images,
+    Index(ContainerFilter),
+    Images(ImageFilter),
+    Logs(LogSource),
+    /// stats history of a container or project
+    Metrics(LogSource),
+    /// detail page of a container, by id or name
+    Container(String),
+    /// exec terminal for the named container
+    Terminal(String),
+    /// detail page of an image, by id or reference
+    Image(String),
+    /// layers and filesystem of an image, by id or reference
+    ImageLayers(String),
+    Volumes,
+    /// detail page of a volume, by name
+    Volume(String),
+    /// previews and prunes of the unused resources
+    Prune,
+    /// what takes up docker's disk space
+    Disk,
+    /// the retention policy's next run and history
+    Retention,
+}
+
+/// How a compose project is brought up to date by the Update task
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdateStrategy {
+    /// docker compose down && docker compose up -d
+    #[default]
+    DownUp,
+    /// docker compose pull && docker compose up -d
+    /// only recreates the services whose image or config changed
+    PullUp,
+}
+
+impl UpdateStrategy {
+    /// Parse a label value through the derived `Deserialize`, so labels take
+    /// the same names as the config file
+    pub fn from_str(s: &str) -> Option<Self> {
+        let value: StrDeserializer<'_, serde::de::value::Error> = s.into_deserializer();
+        Self::deserialize(value).ok()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum SseTask {
     Update,
     Pull,
-    GetConfig,
     PruneImages,
+    ServicePull,
+    ServiceRecreate,
+    ServiceRestart,
+    ServiceStop,
+    ServiceStart,
+    ServiceLogs,
+    ContainerStart,
+    ContainerStop,
+    ContainerRestart,
+    ContainerRemove,
+    ContainerRecreate,
+    ContainerKill,
+    ContainerPause,
+    ContainerUnpause,
+    ImageRemove,
+    ImageUntag,
+    ImageTag,
+    ImagePull,
+    VolumeCreate,
+    VolumeRemove,
+    PruneContainers,
+    PruneNetworks,
+    PruneVolumes,
+    PruneBuildCache,
+    PruneSystem,
+    RetentionRun,
 }
 
 impl SseTask {
@@ -34,8 +129,33 @@ impl SseTask {
         match self {
             Self::Update => "update",
             Self::Pull => "pull",
-            Self::GetConfig => "get_config",
             Self::PruneImages => "prune_images",
+            Self::ServicePull => "service_pull",
+            Self::ServiceRecreate => "service_recreate",
+            Self::ServiceRestart => "service_restart",
+            Self::ServiceStop => "service_stop",
+            Self::ServiceStart => "service_start",
+            Self::ServiceLogs => "service_logs",
+            Self::ContainerStart => "container_start",
+            Self::ContainerStop => "container_stop",
+            Self::ContainerRestart => "container_restart",
+            Self::ContainerRemove => "container_remove",
+            Self::ContainerRecreate => "container_recreate",
+            Self::ContainerKill => "container_kill",
+            Self::ContainerPause => "container_pause",
+            Self::ContainerUnpause => "container_unpause",
+            Self::ImageRemove => "image_remove",
+            Self::ImageUntag => "image_untag",
+            Self::ImageTag => "image_tag",
+            Self::ImagePull => "image_pull",
+            Self::VolumeCreate => "volume_create",
+            Self::VolumeRemove => "volume_remove",
+            Self::PruneContainers => "prune_containers",
+            Self::PruneNetworks => "prune_networks",
+            Self::PruneVolumes => "prune_volumes",
+            Self::PruneBuildCache => "prune_build_cache",
+            Self::PruneSystem => "prune_system",
+            Self::RetentionRun => "retention_run",
         }
     }
 
@@ -43,8 +163,33 @@ impl SseTask {
         match s {
             "update" => Some(Self::Update),
             "pull" => Some(Self::Pull),
-            "get_config" => Some(Self::GetConfig),
             "prune_images" => Some(Self::PruneImages),
+            "service_pull" => Some(Self::ServicePull),
+            "service_recreate" => Some(Self::ServiceRecreate),
+            "service_restart" => Some(Self::ServiceRestart),
+            "service_stop" => Some(Self::ServiceStop),
+            "service_start" => Some(Self::ServiceStart),
+            "service_logs" => Some(Self::ServiceLogs),
+            "container_start" => Some(Self::ContainerStart),
+            "container_stop" => Some(Self::ContainerStop),
+            "container_restart" => Some(Self::ContainerRestart),
+            "container_remove" => Some(Self::ContainerRemove),
+            "container_recreate" => Some(Self::ContainerRecreate),
+            "container_kill" => Some(Self::ContainerKill),
+            "container_pause" => Some(Self::ContainerPause),
+            "container_unpause" => Some(Self::ContainerUnpause),
+            "image_remove" => Some(Self::ImageRemove),
+            "image_untag" => Some(Self::ImageUntag),
+            "image_tag" => Some(Self::ImageTag),
+            "image_pull" => Some(Self::ImagePull),
+            "volume_create" => Some(Self::VolumeCreate),
+            "volume_remove" => Some(Self::VolumeRemove),
+            "prune_containers" => Some(Self::PruneContainers),
+            "prune_networks" => Some(Self::PruneNetworks),
+            "prune_volumes" => Some(Self::PruneVolumes),
+            "prune_build_cache" => Some(Self::PruneBuildCache),
+            "prune_system" => Some(Self::PruneSystem),
+            "retention_run" => Some(Self::RetentionRun),
             _ => None,
         }
     }
@@ -61,3 +206,63 @@ impl Display for SseTask {
         write!(f, "{}", self.to_str())
     }
 }
+
+/// Task arguments passed along in the sse query string
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct SseParams {
+    /// compose service for the service scoped tasks
+    pub service: Option<String>,
+    /// signal for the kill task, see `container::KILL_SIGNALS`
+    pub signal: Option<String>,
+    /// repository:tag to remove or add for the image untag and tag tasks
+    pub tag: Option<String>,
+    /// remove an image even though containers use it
+    #[serde(default)]
+    pub force: bool,
+    /// `repo:tag` or `repo@digest` for the image pull task
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub reference: Option<String>,
+    /// e.g. `linux/arm64`, the host's platform when not set
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub platform: Option<String>,
+    /// name of the volume to create
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub volume: Option<String>,
+    /// volume driver, `local` when not set
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub driver: Option<String>,
+    /// `key=value` labels of the volume to create, one per line
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub labels: Option<String>,
+    /// token of a confirmed volume prune, see `prune::Confirmations`
+    pub confirm: Option<String>,
+}
+
+impl SseParams {
+    /// Name of the sse event that the task output is broadcast on.
+    /// Service tasks get their own event so they don't mix with the project output.
+    pub fn event_name(&self, name: &str) -> String {
+        match &self.service {
+            Some(service) => format!("{}.{}", name, service),
+            None => name.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_strategy() {
+        assert_eq!(
+            UpdateStrategy::from_str("pull-up"),
+            Some(UpdateStrategy::PullUp)
+        );
+        assert_eq!(
+            UpdateStrategy::from_str("down-up"),
+            Some(UpdateStrategy::DownUp)
+        );
+        assert_eq!(UpdateStrategy::from_str("PullUp"), None);
+    }
+}
@@ -0,0 +1,62 @@
+use std::{collections::BTreeMap, process::Command};
+
+use anyhow::Result;
+
+use crate::{container::PROJECT_LABEL, fields::de};
+
+/// Networks every docker host has, they can't be removed
+const BUILTIN_NETWORKS: [&str; 3] = ["bridge", "host", "none"];
+
+/// A line of `docker network ls --format json`
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Network {
+    #[serde(alias = "ID")]
+    pub id: String,
+    #[serde(alias = "Name")]
+    pub name: String,
+    #[serde(alias = "Driver")]
+    pub driver: String,
+    /// `local`, or `swarm` for overlay networks
+    #[serde(alias = "Scope")]
+    pub scope: String,
+    #[serde(alias = "Labels", deserialize_with = "de::labels", default)]
+    pub labels: BTreeMap<String, String>,
+    #[serde(alias = "CreatedAt", default)]
+    pub created_at: String,
+}
+
+impl Network {
+    pub fn get_all() -> Result<Vec<Network>> {
+        let output = Command::new("docker")
+            .arg("network")
+            .arg("ls")
+            .arg("--no-trunc")
+            .arg("--format")
+            .arg("{{json .}}")
+            .output()?;
+
+        let mut networks = String::from_utf8(output.stdout)?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| match serde_json::from_str::<Network>(line) {
+                Ok(network) => Some(network),
+                Err(e) => {
+                    tracing::warn!("failed to parse network {}: {}", line, e);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        networks.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(networks)
+    }
+
+    pub fn is_builtin(&self) -> bool {
+        BUILTIN_NETWORKS.contains(&self.name.as_str())
+    }
+
+    /// compose project that created the network
+    pub fn project(&self) -> Option<String> {
+        self.labels.get(PROJECT_LABEL).cloned()
+    }
+}
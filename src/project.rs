@@ -0,0 +1,395 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    process::Stdio,
+};
+
+use anyhow::{Context, Result};
+use tokio::sync::broadcast;
+
+use crate::{
+    compose::{ComposeContext, ComposeFile},
+    config::{Config, ProjectConfig},
+    container::Container,
+    model::{SseParams, SseTask, UpdateStrategy},
+    util, SseEvent,
+};
+
+/// A docker compose project and the services it is made of
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub name: String,
+    pub services: Vec<Service>,
+}
+
+pub const UPDATE_STRATEGY_LABEL: &str = "mgdocker.update-strategy";
+pub const UPDATE_REMOVE_ORPHANS_LABEL: &str = "mgdocker.update-remove-orphans";
+pub const UPDATE_FORCE_RECREATE_LABEL: &str = "mgdocker.update-force-recreate";
+
+/// The resolved update settings of a project, see `Project::update_options`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UpdateOptions {
+    pub strategy: UpdateStrategy,
+    pub remove_orphans: bool,
+    pub force_recreate: bool,
+}
+
+impl UpdateOptions {
+    /// The compose commands the update runs, in order
+    pub fn commands(&self) -> Vec<Vec<&str>> {
+        let mut up = vec!["up", "-d"];
+        if self.force_recreate {
+            up.push("--force-recreate");
+        }
+
+        match self.strategy {
+            UpdateStrategy::DownUp => {
+                let mut down = vec!["down"];
+                if self.remove_orphans {
+                    down.push("--remove-orphans");
+                }
+                vec![down, up]
+            }
+            UpdateStrategy::PullUp => {
+                if self.remove_orphans {
+                    up.push("--remove-orphans");
+                }
+                vec![vec!["pull"], up]
+            }
+        }
+    }
+
+    /// e.g. "docker compose pull && docker compose up -d"
+    pub fn description(&self) -> String {
+        self.commands()
+            .iter()
+            .map(|args| format!("docker compose {}", args.join(" ")))
+            .collect::<Vec<_>>()
+            .join(" && ")
+    }
+}
+
+/// A compose service and its containers (more than one when scaled)
+#[derive(Debug, Clone)]
+pub struct Service {
+    pub name: String,
+    pub containers: Vec<Container>,
+}
+
+impl Project {
+    pub fn get_all() -> Result<Vec<Project>> {
+        Ok(Self::group(Container::get_all()?))
+    }
+
+    /// Group containers by their compose project and service labels
+    pub fn group(containers: Vec<Container>) -> Vec<Project> {
+        let mut projects: BTreeMap<String, BTreeMap<String, Vec<Container>>> = BTreeMap::new();
+
+        for c in containers {
+            if c.is_standalone() {
+                continue;
+            }
+            let project = c.project().unwrap_or_default();
+            let service = c.service().unwrap_or_else(|| c.names.clone());
+            projects
+                .entry(project)
+                .or_default()
+                .entry(service)
+                .or_default()
+                .push(c);
+        }
+
+        projects
+            .into_iter()
+            .map(|(name, services)| Project {
+                name,
+                services: services
+                    .into_iter()
+                    .map(|(name, containers)| Service { name, containers })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    pub fn get(name: &str) -> Result<Project> {
+        Self::get_all()?
+            .into_iter()
+            .find(|p| p.name == name)
+            .with_context(|| format!("project {} not found", name))
+    }
+
+    pub fn containers(&self) -> impl Iterator<Item = &Container> {
+        self.services.iter().flat_map(|s| s.containers.iter())
+    }
+
+    /// e.g. "2 services, 3/4 running"
+    pub fn summary(&self) -> String {
+        let total = self.containers().count();
+        let running = self.containers().filter(|c| c.state == "running").count();
+        format!(
+            "{} services, {}/{} running",
+            self.services.len(),
+            running,
+            total
+        )
+    }
+
+    /// First value of a label set on any of the project's containers
+    fn label(&self, key: &str) -> Option<String> {
+        self.containers().find_map(|c| c.label(key))
+    }
+
+    /// Resolve the update settings. The config file takes precedence over
+    /// the `mgdocker.*` labels, which take precedence over the defaults.
+    pub fn update_options(&self, config: &ProjectConfig) -> UpdateOptions {
+        let label_bool = |key| self.label(key).map(|val| val == "true");
+
+        UpdateOptions {
+            strategy: config
+                .update_strategy
+                .or_else(|| {
+                    self.label(UPDATE_STRATEGY_LABEL)
+                        .and_then(|val| UpdateStrategy::from_str(&val))
+                })
+                .unwrap_or_default(),
+            remove_orphans: config
+                .remove_orphans
+                .or_else(|| label_bool(UPDATE_REMOVE_ORPHANS_LABEL))
+                .unwrap_or_default(),
+            force_recreate: config
+                .force_recreate
+                .or_else(|| label_bool(UPDATE_FORCE_RECREATE_LABEL))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// container ids of each service, used to tell which services were recreated
+    fn container_ids(&self) -> BTreeMap<String, BTreeSet<String>> {
+        self.services
+            .iter()
+            .map(|s| {
+                let ids = s.containers.iter().map(|c| c.id.clone()).collect();
+                (s.name.clone(), ids)
+            })
+            .collect()
+    }
+
+    /// Run `docker compose <args>` in the project working dir
+    /// and broadcast the stdout and stderr results on `event`
+    async fn compose(
+        context: &ComposeContext,
+        event: &str,
+        args: &[&str],
+        tx: &broadcast::Sender<SseEvent>,
+    ) -> Result<()> {
+        tx.send(SseEvent {
+            event: event.into(),
+            data: format!("docker compose {}\n", util::escape_html(&args.join(" "))),
+        })
+        .context("compose: stdout send error")?;
+
+        let cmd = tokio::process::Command::new("docker")
+            .arg("compose")
+            .args(context.args())
+            .args(args)
+            .current_dir(&context.working_dir)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        util::execute_command(event, cmd, tx).await?;
+
+        Ok(())
+    }
+
+    /// docker compose pull
+    /// broadcast the stdout and stderr results to the sender
+    pub async fn pull(
+        name: String,
+        config: &Config,
+        tx: &broadcast::Sender<SseEvent>,
+    ) -> Result<()> {
+        let context = ComposeContext::get(&name, &config.project(&name))?;
+        Self::compose(&context, &name, &["pull"], tx).await
+    }
+
+    /// Update the project using its configured `UpdateStrategy`
+    /// and report which services ended up with new containers
+    pub async fn update(
+        name: String,
+        config: &Config,
+        tx: &broadcast::Sender<SseEvent>,
+    ) -> Result<()> {
+        let project_config = config.project(&name);
+        let context = ComposeContext::get(&name, &project_config)?;
+        let project = Self::get(&name)?;
+        let options = project.update_options(&project_config);
+        let before = project.container_ids();
+
+        for args in options.commands() {
+            Self::compose(&context, &name, &args, tx).await?;
+        }
+
+        let after = Self::get(&name)
+            .map(|p| p.container_ids())
+            .unwrap_or_default();
+
+        let recreated = after
+            .iter()
+            .filter(|(service, ids)| before.get(*service) != Some(ids))
+            .map(|(service, _)| service.as_str())
+            .collect::<Vec<_>>();
+
+        let data = if recreated.is_empty() {
+            "\nno services were recreated\n".to_string()
+        } else {
+            format!("\nrecreated services: {}\n", recreated.join(", "))
+        };
+
+        tx.send(SseEvent {
+            event: name.clone(),
+            data,
+        })
+        .context("update: stdout send error")?;
+
+        Ok(())
+    }
+
+    /// Run a compose command scoped to a single service of the project.
+    /// Output is broadcast on the service event, see `SseParams::event_name`.
+    pub async fn service_task(
+        name: String,
+        service: String,
+        task: SseTask,
+        config: &Config,
+        tx: &broadcast::Sender<SseEvent>,
+    ) -> Result<()> {
+        let context = ComposeContext::get(&name, &config.project(&name))?;
+        let event = SseParams {
+            service: Some(service.clone()),
+            ..Default::default()
+        }
+        .event_name(&name);
+
+        // the service comes from the query string, anything else could be an option
+        if !context.services()?.contains(&service) {
+            return util::refuse(
+                &event,
+                format!("{} is not a service of project {}", service, name),
+                tx,
+            );
+        }
+
+        let args: &[&str] = match task {
+            SseTask::ServicePull => &["pull", "--", &service],
+            SseTask::ServiceRecreate => &["up", "-d", "--no-deps", "--", &service],
+            SseTask::ServiceRestart => &["restart", "--", &service],
+            SseTask::ServiceStop => &["stop", "--", &service],
+            SseTask::ServiceStart => &["start", "--", &service],
+            SseTask::ServiceLogs => &["logs", "--tail", "200", "--", &service],
+            _ => return Err(anyhow::anyhow!("service_task: invalid task {}", task)),
+        };
+
+        Self::compose(&context, &event, args, tx).await
+    }
+
+    /// The compose files the project was brought up with
+    pub async fn get_config(name: &str, config: &Config) -> Result<Vec<ComposeFile>> {
+        let context = ComposeContext::get(name, &config.project(name))?;
+        Ok(context.read_config_files().await)
+    }
+}
+
+impl Service {
+    /// e.g. "2 running, 1 exited"
+    pub fn summary(&self) -> String {
+        let mut states: BTreeMap<&str, usize> = BTreeMap::new();
+        for c in self.containers.iter() {
+            *states.entry(c.state.as_str()).or_default() += 1;
+        }
+
+        states
+            .into_iter()
+            .map(|(state, count)| format!("{} {}", count, state))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::test_container;
+
+    fn project(labels: &[(&str, &str)]) -> Project {
+        let mut c = test_container("1", "app-web-1", "nginx", Some("app"), "running");
+        for (key, value) in labels {
+            c.labels.insert(key.to_string(), value.to_string());
+        }
+        Project::group(vec![c]).remove(0)
+    }
+
+    #[test]
+    fn commands() {
+        let options = |strategy| UpdateOptions {
+            strategy,
+            remove_orphans: true,
+            force_recreate: true,
+        };
+
+        assert_eq!(
+            options(UpdateStrategy::PullUp).commands(),
+            [
+                vec!["pull"],
+                vec!["up", "-d", "--force-recreate", "--remove-orphans"]
+            ]
+        );
+        assert_eq!(
+            options(UpdateStrategy::DownUp).commands(),
+            [
+                vec!["down", "--remove-orphans"],
+                vec!["up", "-d", "--force-recreate"]
+            ]
+        );
+        assert_eq!(
+            UpdateOptions::default().description(),
+            "docker compose down && docker compose up -d"
+        );
+    }
+
+    #[test]
+    fn update_options() {
+        assert_eq!(
+            project(&[]).update_options(&ProjectConfig::default()),
+            UpdateOptions::default()
+        );
+
+        let labeled = project(&[
+            (UPDATE_STRATEGY_LABEL, "pull-up"),
+            (UPDATE_REMOVE_ORPHANS_LABEL, "true"),
+            (UPDATE_FORCE_RECREATE_LABEL, "true"),
+        ]);
+        assert_eq!(
+            labeled.update_options(&ProjectConfig::default()),
+            UpdateOptions {
+                strategy: UpdateStrategy::PullUp,
+                remove_orphans: true,
+                force_recreate: true,
+            }
+        );
+
+        // the config file overrides the labels, unset keys fall back to them
+        let config = ProjectConfig {
+            update_strategy: Some(UpdateStrategy::DownUp),
+            force_recreate: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(
+            labeled.update_options(&config),
+            UpdateOptions {
+                strategy: UpdateStrategy::DownUp,
+                remove_orphans: true,
+                force_recreate: false,
+            }
+        );
+    }
+}
@@ -0,0 +1,513 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::File,
+    io::Read,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset};
+use tokio::sync::broadcast;
+
+use crate::{
+    container::Container,
+    disk::{BuildCacheRecord, DiskUsage, DiskUsageDetail},
+    fields::parse_timestamp,
+    image::{Image, PruneCandidate},
+    model::{SseEvent, SseTask},
+    network::Network,
+    util::{self, format_bytes},
+    volume::Volume,
+};
+
+/// The resources unused items of which can be pruned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PruneKind {
+    Containers,
+    Networks,
+    Volumes,
+    Images,
+    BuildCache,
+}
+
+impl PruneKind {
+    /// In the order of a system prune, removing the stopped containers first
+    /// frees the networks, volumes and images they used
+    pub const ALL: [PruneKind; 5] = [
+        Self::Containers,
+        Self::Networks,
+        Self::Volumes,
+        Self::Images,
+        Self::BuildCache,
+    ];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Containers => "containers",
+            Self::Networks => "networks",
+            Self::Volumes => "volumes",
+            Self::Images => "images",
+            Self::BuildCache => "build-cache",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.to_str() == s)
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::Containers => "Stopped containers",
+            Self::Networks => "Unused networks",
+            Self::Volumes => "Unused volumes",
+            Self::Images => "Unused images",
+            Self::BuildCache => "Build cache",
+        }
+    }
+
+    pub fn task(self) -> SseTask {
+        match self {
+            Self::Containers => SseTask::PruneContainers,
+            Self::Networks => SseTask::PruneNetworks,
+            Self::Volumes => SseTask::PruneVolumes,
+            Self::Images => SseTask::PruneImages,
+            Self::BuildCache => SseTask::PruneBuildCache,
+        }
+    }
+
+    pub fn from_task(task: &SseTask) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.task() == *task)
+    }
+
+    /// Query parameter of the items selected in the preview, repeated for
+    /// each item, so it can't be one of the fields of `SseParams`
+    pub fn param(self) -> &'static str {
+        match self {
+            Self::Containers => "container",
+            Self::Networks => "network",
+            Self::Volumes => "volume_name",
+            Self::Images => "image",
+            Self::BuildCache => "build_cache",
+        }
+    }
+
+    /// Row of `docker system df`, networks take no space
+    fn disk_usage_kind(self) -> Option<&'static str> {
+        match self {
+            Self::Containers => Some("Containers"),
+            Self::Networks => None,
+            Self::Volumes => Some("Local Volumes"),
+            Self::Images => Some("Images"),
+            Self::BuildCache => Some("Build Cache"),
+        }
+    }
+
+    /// What a prune would remove
+    pub fn preview(self) -> Result<Vec<PruneItem>> {
+        match self {
+            Self::Containers => Ok(Container::get_all_with_size()?
+                .into_iter()
+                .filter(|c| matches!(c.state.as_str(), "created" | "exited" | "dead"))
+                .map(PruneItem::container)
+                .collect()),
+            Self::Networks => {
+                let used = Container::get_all()?
+                    .into_iter()
+                    .flat_map(|c| c.networks)
+                    .collect::<BTreeSet<_>>();
+                Ok(Network::get_all()?
+                    .into_iter()
+                    .filter(|n| !n.is_builtin() && n.scope == "local" && !used.contains(&n.name))
+                    .map(PruneItem::network)
+                    .collect())
+            }
+            Self::Volumes => Ok(Volume::get_all()?
+                .into_iter()
+                .filter(|v| v.is_unused())
+                .map(PruneItem::volume)
+                .collect()),
+            Self::Images => Ok(Image::prune_preview()?
+                .into_iter()
+                .map(PruneItem::image)
+                .collect()),
+            Self::BuildCache => Ok(DiskUsageDetail::get()?
+                .build_cache
+                .into_iter()
+                .filter(|record| record.in_use != "true")
+                .map(PruneItem::build_cache)
+                .collect()),
+        }
+    }
+
+    /// Why the selected items can't be pruned at all. Volumes hold data,
+    /// each one has to be among the `confirmed` volumes.
+    fn refusal(self, ids: &[String], confirmed: &[String]) -> Option<String> {
+        if ids.is_empty() {
+            return Some("nothing selected".to_string());
+        }
+        if self == Self::Volumes && ids.iter().any(|id| !confirmed.contains(id)) {
+            return Some(
+                "removing volumes deletes their data, confirm it to prune them".to_string(),
+            );
+        }
+        None
+    }
+
+    /// Remove the items selected in the preview and report the space that
+    /// was freed. Items that are in use by now are skipped. Volumes hold
+    /// data, removing them has to be `confirmed` separately, see
+    /// `Confirmations`.
+    pub async fn prune(
+        self,
+        event_name: &str,
+        ids: Vec<String>,
+        confirmed: &[String],
+        tx: &broadcast::Sender<SseEvent>,
+    ) -> Result<()> {
+        if let Some(reason) = self.refusal(&ids, confirmed) {
+            return util::refuse(event_name, reason, tx);
+        }
+
+        let before = self.disk_usage()?;
+        let mut failed = 0;
+
+        let (items, gone) = select(self.preview()?, &ids);
+        for id in gone {
+            util::message(
+                event_name,
+                &format!("skipping {}, it is in use or gone", id),
+                tx,
+            )?;
+        }
+        for item in items {
+            if let Err(e) = util::docker(event_name, &item.remove, tx).await {
+                tracing::warn!("prune: {:#}", e);
+                failed += 1;
+            }
+        }
+
+        if let (Some(before), Some(after)) = (before, self.disk_usage()?) {
+            let reclaimed = format_bytes(before.saturating_sub(after));
+            util::message(event_name, &format!("reclaimed {}", reclaimed), tx)?;
+        }
+
+        if failed > 0 {
+            return Err(anyhow::anyhow!(
+                "prune {}: {} failed",
+                self.to_str(),
+                failed
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn disk_usage(self) -> Result<Option<u64>> {
+        match self.disk_usage_kind() {
+            Some(kind) => Ok(Some(DiskUsage::size_of(&DiskUsage::get_all()?, kind))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Prune several kinds of resources one after the other, each only the
+/// items selected in its preview
+pub async fn system_prune(
+    event_name: &str,
+    selected: Vec<(PruneKind, Vec<String>)>,
+    confirmed: &[String],
+    tx: &broadcast::Sender<SseEvent>,
+) -> Result<()> {
+    if selected.is_empty() {
+        return util::refuse(event_name, "nothing selected".to_string(), tx);
+    }
+
+    let mut failed = vec![];
+    for (kind, ids) in selected {
+        util::message(event_name, &format!("\n{}", kind.title()), tx)?;
+        if let Err(e) = kind.prune(event_name, ids, confirmed, tx).await {
+            tracing::warn!("system prune: {:#}", e);
+            failed.push(kind.to_str());
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(anyhow::anyhow!(
+            "system prune: {} failed",
+            failed.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+/// Something a prune would remove
+#[derive(Debug, Clone)]
+pub struct PruneItem {
+    /// passed back when the item is selected
+    pub id: String,
+    pub name: String,
+    /// detail page of the item
+    pub href: Option<String>,
+    pub created: Option<DateTime<FixedOffset>>,
+    pub size: Option<u64>,
+    /// e.g. the project and image of a container
+    pub detail: String,
+    /// the containers an image was last used by, see `PruneCandidate`
+    pub containers: Vec<Container>,
+    /// docker arguments that remove the item
+    remove: Vec<String>,
+}
+
+impl PruneItem {
+    fn container(c: Container) -> PruneItem {
+        PruneItem {
+            id: c.id.clone(),
+            name: c.names.clone(),
+            href: Some(format!("/containers/{}", c.names)),
+            created: Some(c.created_at),
+            size: c.size.map(|size| size.rw),
+            detail: match c.project() {
+                Some(project) => format!("{}, {}, {}", project, c.image, c.status),
+                None => format!("{}, {}", c.image, c.status),
+            },
+            containers: vec![],
+            remove: vec!["container".to_string(), "rm".to_string(), c.id],
+        }
+    }
+
+    fn network(n: Network) -> PruneItem {
+        PruneItem {
+            detail: match n.project() {
+                Some(project) => format!("{}, {}", project, n.driver),
+                None => n.driver.clone(),
+            },
+            id: n.id.clone(),
+            name: n.name,
+            href: None,
+            created: parse_timestamp(&n.created_at),
+            size: None,
+            containers: vec![],
+            remove: vec!["network".to_string(), "rm".to_string(), n.id],
+        }
+    }
+
+    fn volume(v: Volume) -> PruneItem {
+        PruneItem {
+            id: v.name.clone(),
+            detail: match v.project() {
+                Some(project) => format!("{}, {}", project, v.driver),
+                None => v.driver.clone(),
+            },
+            href: Some(format!("/volumes/{}", v.name)),
+            created: None,
+            size: v.size,
+            containers: vec![],
+            remove: vec!["volume".to_string(), "rm".to_string(), v.name.clone()],
+            name: v.name,
+        }
+    }
+
+    fn image(candidate: PruneCandidate) -> PruneItem {
+        let inspect = &candidate.inspect;
+        PruneItem {
+            id: inspect.id.clone(),
+            name: if inspect.repo_tags.is_empty() {
+                inspect.id.clone()
+            } else {
+                inspect.repo_tags.join(", ")
+            },
+            href: Some(format!("/images/{}", inspect.id)),
+            created: parse_timestamp(&inspect.created),
+            size: Some(inspect.size),
+            detail: String::new(),
+            remove: [
+                vec!["image".to_string(), "rm".to_string()],
+                candidate.references(),
+            ]
+            .concat(),
+            containers: candidate.last_used_by,
+        }
+    }
+
+    fn build_cache(record: BuildCacheRecord) -> PruneItem {
+        PruneItem {
+            name: record.description.clone(),
+            detail: format!("{}, last used {}", record.kind, record.last_used_since),
+            href: None,
+            created: None,
+            size: record.size,
+            containers: vec![],
+            // without --all only the dangling records would be removed,
+            // not every record that isn't in use as previewed
+            remove: [
+                "builder",
+                "prune",
+                "--all",
+                "--force",
+                "--filter",
+                &format!("id={}", record.id),
+            ]
+            .map(String::from)
+            .to_vec(),
+            id: record.id,
+        }
+    }
+}
+
+/// The previewed items that were selected, in the order of `ids`, and the
+/// selected ids that aren't previewed any more
+fn select(mut items: Vec<PruneItem>, ids: &[String]) -> (Vec<PruneItem>, Vec<String>) {
+    let mut selected = vec![];
+    let mut gone = vec![];
+    for id in ids {
+        match items.iter().position(|item| item.id == *id) {
+            Some(i) => selected.push(items.swap_remove(i)),
+            None => gone.push(id.clone()),
+        }
+    }
+    (selected, gone)
+}
+
+/// A confirmation is only good for the sse connect right after the form post
+const CONFIRMATION_TTL: Duration = Duration::from_secs(60);
+
+/// Confirmed volume prunes. The prune forms are posted, and a confirmation
+/// of the checked volumes is exchanged for a token that the sse connect of
+/// the prune uses up. A link to or a prefetch of a connect url can't delete
+/// volumes that way.
+#[derive(Debug, Default)]
+pub struct Confirmations {
+    issued: Mutex<HashMap<String, (Instant, Vec<String>)>>,
+}
+
+impl Confirmations {
+    /// A token confirming the removal of `volumes`
+    pub fn issue(&self, volumes: Vec<String>) -> Result<String> {
+        let mut bytes = [0u8; 16];
+        File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+        let token = bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+
+        let mut issued = self.issued.lock().unwrap_or_else(|e| e.into_inner());
+        issued.retain(|_, (at, _)| at.elapsed() < CONFIRMATION_TTL);
+        issued.insert(token.clone(), (Instant::now(), volumes));
+        Ok(token)
+    }
+
+    /// The volumes a token confirmed, none once it was used or expired
+    pub fn take(&self, token: &str) -> Vec<String> {
+        let mut issued = self.issued.lock().unwrap_or_else(|e| e.into_inner());
+        match issued.remove(token) {
+            Some((at, volumes)) if at.elapsed() < CONFIRMATION_TTL => volumes,
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{container::test_container, inspect::ImageInspect};
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn volume(name: &str) -> PruneItem {
+        PruneItem::volume(
+            serde_json::from_str(&format!(
+                r#"{{"Driver":"local","Labels":"","Links":"0","Mountpoint":"/v/{0}","Name":"{0}","Scope":"local","Size":"1.5GB"}}"#,
+                name
+            ))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn select() {
+        let items = vec![volume("a"), volume("b"), volume("c")];
+
+        let (selected, gone) = super::select(items, &ids(&["c", "x", "a"]));
+
+        let selected = selected
+            .iter()
+            .map(|item| item.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(selected, ["c", "a"]);
+        assert_eq!(gone, ["x"]);
+    }
+
+    #[test]
+    fn remove() {
+        let container = PruneItem::container(test_container("c1", "old", "alpine", None, "exited"));
+        assert_eq!(container.remove, ["container", "rm", "c1"]);
+
+        let network = PruneItem::network(
+            serde_json::from_str(r#"{"CreatedAt":"2024-03-01 10:00:00 +0000 UTC","Driver":"bridge","ID":"n1","Labels":"","Name":"app_default","Scope":"local"}"#)
+                .unwrap(),
+        );
+        assert_eq!(network.remove, ["network", "rm", "n1"]);
+        assert_eq!(volume("app_db").remove, ["volume", "rm", "app_db"]);
+
+        let image = |tags: &[&str]| {
+            PruneItem::image(PruneCandidate {
+                inspect: ImageInspect {
+                    id: "sha256:111".to_string(),
+                    repo_tags: ids(tags),
+                    ..Default::default()
+                },
+                last_used_by: vec![],
+            })
+        };
+        assert_eq!(
+            image(&["app:1", "app:latest"]).remove,
+            ["image", "rm", "app:1", "app:latest"]
+        );
+        assert_eq!(image(&[]).remove, ["image", "rm", "sha256:111"]);
+
+        let record = PruneItem::build_cache(
+            serde_json::from_str(r#"{"CacheType":"regular","Description":"[2/3] RUN apk add curl","ID":"k3ex4fq","InUse":"false","LastUsedSince":"2 days ago","Size":"12.3MB"}"#)
+                .unwrap(),
+        );
+        assert_eq!(
+            record.remove,
+            [
+                "builder",
+                "prune",
+                "--all",
+                "--force",
+                "--filter",
+                "id=k3ex4fq"
+            ]
+        );
+    }
+
+    #[test]
+    fn refusal() {
+        let selected = ids(&["a", "b"]);
+
+        assert!(PruneKind::Volumes.refusal(&selected, &[]).is_some());
+        assert!(PruneKind::Volumes
+            .refusal(&selected, &ids(&["a"]))
+            .is_some());
+        assert!(PruneKind::Volumes.refusal(&selected, &selected).is_none());
+        assert!(PruneKind::Images.refusal(&selected, &[]).is_none());
+        assert!(PruneKind::Images.refusal(&[], &[]).is_some());
+    }
+
+    #[test]
+    fn confirmations() {
+        let confirmations = Confirmations::default();
+        let token = confirmations.issue(ids(&["a"])).unwrap();
+
+        assert_eq!(token.len(), 32);
+        assert_eq!(confirmations.take("true"), Vec::<String>::new());
+        assert_eq!(confirmations.take(&token), ["a"]);
+        // used up
+        assert_eq!(confirmations.take(&token), Vec::<String>::new());
+    }
+}
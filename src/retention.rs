@@ -0,0 +1,544 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Utc};
+use tokio::sync::broadcast;
+
+use crate::{
+    config::RetentionConfig,
+    container::{Container, ContainerImage},
+    fields::format_relative,
+    image::Image,
+    model::SseEvent,
+    store::{unix_secs, RetentionRecord, Store},
+    util,
+};
+
+/// Rule of the retention policy that removes an item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetentionRule {
+    StoppedContainers,
+    DanglingImages,
+    OldImages,
+}
+
+impl RetentionRule {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::StoppedContainers => "stopped-containers",
+            Self::DanglingImages => "dangling-images",
+            Self::OldImages => "old-images",
+        }
+    }
+}
+
+/// Something the next run of the retention policy removes
+#[derive(Debug, Clone)]
+pub struct RetentionItem {
+    pub rule: RetentionRule,
+    /// container or image id
+    pub id: String,
+    pub name: String,
+    /// detail page of the item
+    pub href: Option<String>,
+    /// why the rule selected it, e.g. `exited 9 days ago`
+    pub reason: String,
+    pub size: Option<u64>,
+    /// docker arguments that remove the item
+    remove: Vec<String>,
+}
+
+/// The image ids of each compose project's containers, running or not
+pub fn project_images(
+    containers: &[Container],
+    container_images: &HashMap<String, ContainerImage>,
+) -> BTreeMap<String, BTreeSet<String>> {
+    let mut projects = BTreeMap::<String, BTreeSet<String>>::new();
+    for c in containers {
+        if let (Some(project), Some(image)) = (c.project(), container_images.get(&c.id)) {
+            projects
+                .entry(project)
+                .or_default()
+                .insert(image.id.clone());
+        }
+    }
+    projects
+}
+
+/// What the policy removes from the containers and images. Images are only
+/// removed once no container uses them, counting out the containers removed
+/// in the same run. `finished_at` and `container_images` are keyed by
+/// container id, see `Container::finished_at` and `Container::images`.
+/// `project_images` are the images compose projects last ran, see
+/// `Store::project_images`, kept with `keep_project_images` also after a
+/// project was brought down.
+pub fn plan(
+    config: &RetentionConfig,
+    now: DateTime<Utc>,
+    containers: &[Container],
+    finished_at: &HashMap<String, DateTime<FixedOffset>>,
+    images: &[Image],
+    container_images: &HashMap<String, ContainerImage>,
+    project_images: &HashSet<String>,
+) -> Vec<RetentionItem> {
+    let older_than = |time: &DateTime<FixedOffset>, hours: u64| {
+        (now - time.with_timezone(&Utc)).num_hours() >= hours as i64
+    };
+    let mut items = vec![];
+
+    if let Some(days) = config.stopped_containers_days {
+        for c in containers {
+            if !matches!(c.state.as_str(), "created" | "exited" | "dead") {
+                continue;
+            }
+            if config.keep_project_images && c.project().is_some() {
+                continue;
+            }
+            // containers that never ran count from their creation
+            let (verb, since) = match finished_at.get(&c.id) {
+                Some(time) => ("exited", time),
+                None => ("created", &c.created_at),
+            };
+            if !older_than(since, days * 24) {
+                continue;
+            }
+            items.push(RetentionItem {
+                rule: RetentionRule::StoppedContainers,
+                id: c.id.clone(),
+                name: c.names.clone(),
+                href: Some(format!("/containers/{}", c.names)),
+                reason: format!("{}, {} {}", c.image, verb, format_relative(since, now)),
+                size: c.size.map(|size| size.rw),
+                remove: vec!["container".to_string(), "rm".to_string(), c.id.clone()],
+            });
+        }
+    }
+
+    let mut in_use = container_images
+        .iter()
+        .filter(|(id, _)| !items.iter().any(|item| item.id == **id))
+        .map(|(_, image)| image.id.as_str())
+        .collect::<HashSet<_>>();
+    if config.keep_project_images {
+        in_use.extend(project_images.iter().map(String::as_str));
+    }
+
+    if let Some(hours) = config.dangling_images_hours {
+        let mut seen = HashSet::new();
+        for image in images.iter().filter(|image| image.is_dangling()) {
+            if !seen.insert(&image.id) || in_use.contains(image.id.as_str()) {
+                continue;
+            }
+            if !older_than(&image.created_at, hours) {
+                continue;
+            }
+            items.push(RetentionItem {
+                rule: RetentionRule::DanglingImages,
+                id: image.id.clone(),
+                name: image.id.clone(),
+                href: Some(format!("/images/{}", image.id)),
+                reason: format!(
+                    "untagged, created {}",
+                    format_relative(&image.created_at, now)
+                ),
+                size: Some(image.size),
+                remove: vec!["image".to_string(), "rm".to_string(), image.id.clone()],
+            });
+        }
+    }
+
+    if let Some(keep) = config.keep_images_per_repository {
+        let mut repositories = BTreeMap::<&str, Vec<&Image>>::new();
+        for image in images.iter().filter(|image| image.reference().is_some()) {
+            repositories
+                .entry(&image.repository)
+                .or_default()
+                .push(image);
+        }
+
+        for (repository, mut tagged) in repositories {
+            tagged.sort_by_key(|image| std::cmp::Reverse(image.created_at));
+
+            // an image with several tags in the repository is one version
+            let mut versions = Vec::<(&Image, Vec<String>)>::new();
+            for image in tagged {
+                let reference = image.reference().unwrap_or_default();
+                match versions.iter_mut().find(|(v, _)| v.id == image.id) {
+                    Some((_, references)) => references.push(reference),
+                    None => versions.push((image, vec![reference])),
+                }
+            }
+
+            for (newer, (image, references)) in versions.into_iter().enumerate().skip(keep) {
+                if in_use.contains(image.id.as_str()) {
+                    continue;
+                }
+                // only the tags of this repository, the image stays when it
+                // is tagged in another one
+                items.push(RetentionItem {
+                    rule: RetentionRule::OldImages,
+                    id: image.id.clone(),
+                    name: references.join(", "),
+                    href: Some(format!("/images/{}", image.id)),
+                    reason: format!(
+                        "{} newer in {}, created {}",
+                        newer,
+                        repository,
+                        format_relative(&image.created_at, now)
+                    ),
+                    size: Some(image.size),
+                    remove: [vec!["image".to_string(), "rm".to_string()], references].concat(),
+                });
+            }
+        }
+    }
+
+    items
+}
+
+/// What the next run removes. The images of the compose projects that have
+/// containers are recorded first, so they are still known once the project
+/// is brought down.
+pub fn preview(config: &RetentionConfig, store: &Store) -> Result<Vec<RetentionItem>> {
+    if !config.has_rules() {
+        return Ok(vec![]);
+    }
+
+    let containers = Container::get_all_with_size()?;
+    let stopped = containers
+        .iter()
+        .filter(|c| c.state != "running")
+        .cloned()
+        .collect::<Vec<_>>();
+    let container_images = Container::images(&containers)?;
+    store.record_project_images(&project_images(&containers, &container_images))?;
+
+    Ok(plan(
+        config,
+        Utc::now(),
+        &containers,
+        &Container::finished_at(&stopped)?,
+        &Image::get_top_level()?,
+        &container_images,
+        &store.project_images()?,
+    ))
+}
+
+/// Remove what the policy selects now, each removal is recorded in the
+/// history whether it worked or not. The history older than `history_days`
+/// is dropped.
+pub async fn apply(config: &RetentionConfig, store: &Store) -> Result<Vec<RetentionRecord>> {
+    let days = config.history_days;
+    if let Err(e) = store
+        .run(move |store| store.prune_retention_history(days))
+        .await
+    {
+        tracing::warn!("failed to prune the retention history: {:#}", e);
+    }
+
+    let mut records = vec![];
+
+    let config = config.clone();
+    let items = store.run(move |store| preview(&config, store)).await?;
+    for item in items {
+        let output = tokio::process::Command::new("docker")
+            .args(&item.remove)
+            .output()
+            .await?;
+        let record = RetentionRecord {
+            time: unix_secs(SystemTime::now()),
+            rule: item.rule.to_str().to_string(),
+            name: item.name,
+            size: item.size,
+            error: (!output.status.success())
+                .then(|| String::from_utf8_lossy(&output.stderr).trim().to_string()),
+        };
+
+        let recorded = record.clone();
+        if let Err(e) = store
+            .run(move |store| store.record_retention(&recorded))
+            .await
+        {
+            tracing::warn!("failed to record the removal of {}: {:#}", record.name, e);
+        }
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Apply the policy every `interval_mins` for as long as mgdocker runs,
+/// starting one interval after startup
+pub async fn run(config: RetentionConfig, store: Store) {
+    let period = Duration::from_secs(config.interval_mins.max(1) * 60);
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        match apply(&config, &store).await {
+            Ok(records) if !records.is_empty() => {
+                let failed = records.iter().filter(|r| r.error.is_some()).count();
+                tracing::info!(
+                    "retention: removed {}, {} failed",
+                    records.len() - failed,
+                    failed
+                );
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("retention: {:#}", e),
+        }
+    }
+}
+
+/// Apply the policy now, from the retention page
+pub async fn run_now(
+    event_name: &str,
+    config: &RetentionConfig,
+    store: &Store,
+    tx: &broadcast::Sender<SseEvent>,
+) -> Result<()> {
+    if !config.has_rules() {
+        return util::refuse(
+            event_name,
+            "no retention rules, set them under [retention] in the config file".to_string(),
+            tx,
+        );
+    }
+
+    let records = apply(config, store).await?;
+    if records.is_empty() {
+        return util::message(event_name, "nothing to remove", tx);
+    }
+
+    let mut failed = 0;
+    for record in records {
+        let line = match &record.error {
+            Some(error) => {
+                failed += 1;
+                format!("failed to remove {}: {}", record.name, error)
+            }
+            None => format!("removed {} ({})", record.name, record.rule),
+        };
+        util::message(event_name, &line, tx)?;
+    }
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!("retention: {} removals failed", failed));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::test_container;
+
+    fn container(id: &str, project: Option<&str>, state: &str) -> Container {
+        test_container(id, id, "app", project, state)
+    }
+
+    fn image(id: &str, repository: &str, tag: &str, created_at: &str) -> Image {
+        serde_json::from_str(&format!(
+            r#"{{"Containers":"N/A","CreatedAt":"{} +0000 UTC","CreatedSince":"","Digest":"<none>","ID":"{}","Repository":"{}","SharedSize":"N/A","Size":"100MB","Tag":"{}","UniqueSize":"N/A","VirtualSize":"100MB"}}"#,
+            created_at, id, repository, tag
+        ))
+        .unwrap()
+    }
+
+    fn uses(container: &str, image: &str) -> (String, ContainerImage) {
+        (
+            container.to_string(),
+            ContainerImage {
+                id: image.to_string(),
+                reference: "app".to_string(),
+            },
+        )
+    }
+
+    fn removed(items: &[RetentionItem]) -> Vec<(RetentionRule, &str)> {
+        items
+            .iter()
+            .map(|item| (item.rule, item.id.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn policy() {
+        let now = "2024-03-21T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let config = RetentionConfig {
+            keep_images_per_repository: Some(2),
+            stopped_containers_days: Some(7),
+            dangling_images_hours: Some(24),
+            ..Default::default()
+        };
+        let containers = vec![
+            container("old", None, "exited"),
+            container("recent", None, "exited"),
+            container("project", Some("app"), "exited"),
+            container("running", None, "running"),
+        ];
+        let finished_at = HashMap::from([
+            ("old".to_string(), "2024-03-01T10:00:00Z".parse().unwrap()),
+            (
+                "recent".to_string(),
+                "2024-03-20T10:00:00Z".parse().unwrap(),
+            ),
+            (
+                "project".to_string(),
+                "2024-03-01T10:00:00Z".parse().unwrap(),
+            ),
+        ]);
+        let images = vec![
+            image("sha256:v4", "app", "4", "2024-03-20 10:00:00"),
+            image("sha256:v3", "app", "3", "2024-03-15 10:00:00"),
+            image("sha256:v3", "app", "stable", "2024-03-15 10:00:00"),
+            image("sha256:v2", "app", "2", "2024-03-10 10:00:00"),
+            image("sha256:v1", "app", "1", "2024-03-05 10:00:00"),
+            image("sha256:v0", "app", "0", "2024-03-01 10:00:00"),
+            image("sha256:dangling", "<none>", "<none>", "2024-03-01 10:00:00"),
+            image("sha256:fresh", "<none>", "<none>", "2024-03-21 09:00:00"),
+        ];
+        let container_images = HashMap::from([
+            uses("old", "sha256:v1"),
+            uses("project", "sha256:v0"),
+            uses("running", "sha256:v2"),
+        ]);
+
+        let items = plan(
+            &config,
+            now,
+            &containers,
+            &finished_at,
+            &images,
+            &container_images,
+            &HashSet::new(),
+        );
+
+        // v1 goes along with the container that used it, v0 stays with the
+        // stopped project container and v2 is running
+        assert_eq!(
+            removed(&items),
+            vec![
+                (RetentionRule::StoppedContainers, "old"),
+                (RetentionRule::DanglingImages, "sha256:dangling"),
+                (RetentionRule::OldImages, "sha256:v1"),
+            ]
+        );
+        assert_eq!(items[2].remove, vec!["image", "rm", "app:1"]);
+        assert!(items[2].reason.starts_with("3 newer in app"));
+
+        let config = RetentionConfig {
+            keep_project_images: false,
+            ..config
+        };
+        let items = plan(
+            &config,
+            now,
+            &containers,
+            &finished_at,
+            &images,
+            &container_images,
+            &HashSet::new(),
+        );
+        assert!(removed(&items).contains(&(RetentionRule::StoppedContainers, "project")));
+        assert!(removed(&items).contains(&(RetentionRule::OldImages, "sha256:v0")));
+    }
+
+    #[test]
+    fn plan_keeps_images_of_projects_brought_down() {
+        let now = "2024-03-21T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let config = RetentionConfig {
+            keep_images_per_repository: Some(1),
+            ..Default::default()
+        };
+        let images = vec![
+            image("sha256:v2", "app", "2", "2024-03-20 10:00:00"),
+            image("sha256:v1", "app", "1", "2024-03-10 10:00:00"),
+            image("sha256:v0", "app", "0", "2024-03-01 10:00:00"),
+        ];
+        // `compose down` left no containers, the project last ran v1
+        let project_images = HashSet::from(["sha256:v1".to_string()]);
+
+        let items = plan(
+            &config,
+            now,
+            &[],
+            &HashMap::new(),
+            &images,
+            &HashMap::new(),
+            &project_images,
+        );
+        assert_eq!(
+            removed(&items),
+            vec![(RetentionRule::OldImages, "sha256:v0")]
+        );
+
+        let config = RetentionConfig {
+            keep_project_images: false,
+            ..config
+        };
+        let items = plan(
+            &config,
+            now,
+            &[],
+            &HashMap::new(),
+            &images,
+            &HashMap::new(),
+            &project_images,
+        );
+        assert_eq!(items.len(), 2);
+    }
+
+    #[test]
+    fn project_images() {
+        let containers = vec![
+            container("web", Some("app"), "running"),
+            container("worker", Some("app"), "exited"),
+            container("db", Some("data"), "running"),
+            container("standalone", None, "running"),
+        ];
+        let container_images = HashMap::from([
+            uses("web", "sha256:web"),
+            uses("worker", "sha256:web"),
+            uses("db", "sha256:postgres"),
+            uses("standalone", "sha256:other"),
+        ]);
+
+        let projects = super::project_images(&containers, &container_images);
+
+        assert_eq!(
+            projects,
+            BTreeMap::from([
+                (
+                    "app".to_string(),
+                    BTreeSet::from(["sha256:web".to_string()])
+                ),
+                (
+                    "data".to_string(),
+                    BTreeSet::from(["sha256:postgres".to_string()])
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn plan_without_rules() {
+        let images = vec![image("sha256:x", "<none>", "<none>", "2020-01-01 00:00:00")];
+        let items = plan(
+            &RetentionConfig::default(),
+            Utc::now(),
+            &[container("old", None, "exited")],
+            &HashMap::new(),
+            &images,
+            &HashMap::new(),
+            &HashSet::new(),
+        );
+        assert!(items.is_empty());
+    }
+}
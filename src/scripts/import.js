@@ -0,0 +1,45 @@
+// Uploads the image archive picked on the images page as the raw request body, which the server
+// streams into docker load, and shows how much of it was sent.
+function upload(form) {
+  const file = form.querySelector("input[type=file]").files[0];
+  const progress = form.querySelector("progress");
+  const button = form.querySelector("button");
+  const result = document.getElementById("image_task_container");
+  if (!file) {
+    return;
+  }
+
+  const done = (html) => {
+    result.innerHTML = html;
+    progress.hidden = true;
+    button.disabled = false;
+  };
+
+  const xhr = new XMLHttpRequest();
+  xhr.open("POST", form.dataset.import);
+  xhr.setRequestHeader("content-type", "application/octet-stream");
+  xhr.upload.onprogress = (e) => {
+    if (e.lengthComputable) {
+      progress.max = e.total;
+      progress.value = e.loaded;
+    }
+  };
+  xhr.upload.onload = () => {
+    result.textContent = "Uploaded, waiting for docker load...";
+  };
+  xhr.onload = () => done(xhr.responseText);
+  xhr.onerror = () => done("<p class=\"notice\">The upload failed.</p>");
+
+  result.textContent = "";
+  progress.value = 0;
+  progress.hidden = false;
+  button.disabled = true;
+  xhr.send(file);
+}
+
+document.addEventListener("submit", (e) => {
+  if (e.target.id === "image-import") {
+    e.preventDefault();
+    upload(e.target);
+  }
+});
@@ -0,0 +1,71 @@
+// Connects the xterm.js terminal on the container terminal page to the exec websocket.
+// Keystrokes and resizes are sent as json, the shell output comes back as binary messages.
+let session = null;
+
+function disconnect() {
+  if (session) {
+    session.close();
+    session = null;
+  }
+}
+
+function connect(form) {
+  disconnect();
+
+  const el = document.getElementById("terminal");
+  el.innerHTML = "";
+
+  const term = new Terminal({ cursorBlink: true });
+  const fit = new FitAddon.FitAddon();
+  term.loadAddon(fit);
+  term.open(el);
+  fit.fit();
+
+  const protocol = location.protocol === "https:" ? "wss:" : "ws:";
+  const params = new URLSearchParams(new FormData(form));
+  const ws = new WebSocket(`${protocol}//${location.host}${form.dataset.exec}?${params}`);
+  ws.binaryType = "arraybuffer";
+
+  const send = (message) => {
+    if (ws.readyState === WebSocket.OPEN) {
+      ws.send(JSON.stringify(message));
+    }
+  };
+  const resize = () => {
+    fit.fit();
+    send({ type: "resize", cols: term.cols, rows: term.rows });
+  };
+
+  ws.onopen = () => {
+    resize();
+    term.focus();
+  };
+  ws.onmessage = (e) => term.write(new Uint8Array(e.data));
+  ws.onclose = (e) => {
+    const reason = e.reason || (e.code === 1000 ? "" : "connection failed, check the server log");
+    term.write(`\r\n\x1b[2m[disconnected${reason ? ": " + reason : ""}]\x1b[0m\r\n`);
+  };
+
+  term.onData((data) => send({ type: "input", data }));
+  window.addEventListener("resize", resize);
+
+  session = {
+    close() {
+      window.removeEventListener("resize", resize);
+      ws.close();
+    },
+  };
+}
+
+document.addEventListener("submit", (e) => {
+  if (e.target.id === "terminal-form") {
+    e.preventDefault();
+    connect(e.target);
+  }
+});
+
+document.addEventListener("click", (e) => {
+  if (e.target.id === "terminal-disconnect") {
+    disconnect();
+  }
+});
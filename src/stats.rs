@@ -0,0 +1,295 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use tokio::sync::{broadcast, RwLock};
+
+use crate::{
+    config::MetricsConfig, container::Container, logs::LogSource, store::Store, util::parse_bytes,
+};
+
+/// How often `docker stats` is sampled
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+/// Samples kept per container and project for the sparklines
+pub const HISTORY_LEN: usize = 60;
+/// How often samples past their retention are removed from the store
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// A line of `docker stats --no-stream --format '{{json .}}'`, sizes are
+/// human readable strings like `12.5MiB / 1.944GiB`
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StatsLine {
+    name: String,
+    #[serde(rename = "CPUPerc")]
+    cpu_perc: String,
+    mem_usage: String,
+    #[serde(rename = "NetIO")]
+    net_io: String,
+    #[serde(rename = "BlockIO")]
+    block_io: String,
+    #[serde(rename = "PIDs")]
+    pids: String,
+}
+
+/// Resource usage of a container, or the sum over a project's containers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatsSample {
+    pub time: SystemTime,
+    pub cpu_percent: f64,
+    pub mem_usage: u64,
+    /// zero for projects, their containers usually share the host memory
+    pub mem_limit: u64,
+    /// bytes received and sent since the container started
+    pub net_rx: u64,
+    pub net_tx: u64,
+    pub block_read: u64,
+    pub block_write: u64,
+    pub pids: u64,
+}
+
+/// Split `a / b` and parse both sides as byte sizes
+fn parse_pair(s: &str) -> Option<(u64, u64)> {
+    let (a, b) = s.split_once('/')?;
+    Some((parse_bytes(a)?, parse_bytes(b)?))
+}
+
+impl StatsLine {
+    fn sample(&self, time: SystemTime) -> Result<StatsSample> {
+        let (mem_usage, mem_limit) = parse_pair(&self.mem_usage)
+            .with_context(|| format!("invalid memory {}", self.mem_usage))?;
+        let (net_rx, net_tx) =
+            parse_pair(&self.net_io).with_context(|| format!("invalid net io {}", self.net_io))?;
+        let (block_read, block_write) = parse_pair(&self.block_io)
+            .with_context(|| format!("invalid block io {}", self.block_io))?;
+
+        Ok(StatsSample {
+            time,
+            cpu_percent: self
+                .cpu_perc
+                .trim()
+                .trim_end_matches('%')
+                .parse()
+                .unwrap_or(0.0),
+            mem_usage,
+            mem_limit,
+            net_rx,
+            net_tx,
+            block_read,
+            block_write,
+            pids: self.pids.trim().parse().unwrap_or(0),
+        })
+    }
+}
+
+/// Parse the lines of `docker stats`. Containers that are starting or
+/// stopping show `--` instead of their usage, they are skipped so the rest
+/// of the round is still recorded.
+fn parse_samples(output: &str, time: SystemTime) -> Vec<(String, StatsSample)> {
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let sample = serde_json::from_str::<StatsLine>(line)
+                .map_err(anyhow::Error::from)
+                .and_then(|stats| Ok((stats.name.clone(), stats.sample(time)?)));
+            match sample {
+                Ok(sample) => Some(sample),
+                Err(e) => {
+                    tracing::warn!("failed to parse stats {}: {:#}", line, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+impl StatsSample {
+    fn add(&mut self, other: &StatsSample) {
+        self.cpu_percent += other.cpu_percent;
+        self.mem_usage += other.mem_usage;
+        self.net_rx += other.net_rx;
+        self.net_tx += other.net_tx;
+        self.block_read += other.block_read;
+        self.block_write += other.block_write;
+        self.pids += other.pids;
+    }
+}
+
+/// Recent samples, oldest first
+pub type History = VecDeque<StatsSample>;
+
+/// Recent samples of every running container and compose project
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    /// keyed by container name
+    pub containers: BTreeMap<String, History>,
+    /// keyed by project name, summed over the project's containers
+    pub projects: BTreeMap<String, History>,
+}
+
+fn push(histories: &mut BTreeMap<String, History>, name: &str, sample: StatsSample) {
+    let history = histories.entry(name.to_string()).or_default();
+    if history.len() == HISTORY_LEN {
+        history.pop_front();
+    }
+    history.push_back(sample);
+}
+
+/// Network bytes per second between consecutive samples
+pub fn net_rates(history: &History) -> Vec<f64> {
+    history
+        .iter()
+        .zip(history.iter().skip(1))
+        .map(|(a, b)| {
+            let secs = b
+                .time
+                .duration_since(a.time)
+                .unwrap_or_default()
+                .as_secs_f64()
+                .max(1.0);
+            // counters reset when a container restarts
+            let bytes = (b.net_rx + b.net_tx).saturating_sub(a.net_rx + a.net_tx);
+            bytes as f64 / secs
+        })
+        .collect()
+}
+
+impl Stats {
+    /// One `docker stats` sample of the running containers
+    async fn sample() -> Result<Vec<(String, StatsSample)>> {
+        let output = tokio::process::Command::new("docker")
+            .arg("stats")
+            .arg("--no-stream")
+            .arg("--format")
+            .arg("{{json .}}")
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "docker stats: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let output = String::from_utf8(output.stdout)?;
+        Ok(parse_samples(&output, SystemTime::now()))
+    }
+
+    /// Add a sample round, containers and projects that are no longer running are dropped
+    fn record(&mut self, samples: Vec<(String, StatsSample)>, containers: &[Container]) {
+        let mut projects: BTreeMap<String, StatsSample> = BTreeMap::new();
+
+        for (name, sample) in samples.iter() {
+            let project = containers
+                .iter()
+                .find(|c| &c.names == name)
+                .and_then(|c| c.project());
+            if let Some(project) = project {
+                projects
+                    .entry(project)
+                    .and_modify(|total| total.add(sample))
+                    .or_insert(StatsSample {
+                        mem_limit: 0,
+                        ..*sample
+                    });
+            }
+        }
+
+        self.containers
+            .retain(|name, _| samples.iter().any(|(n, _)| n == name));
+        self.projects.retain(|name, _| projects.contains_key(name));
+
+        for (name, sample) in samples {
+            push(&mut self.containers, &name, sample);
+        }
+        for (name, sample) in projects {
+            push(&mut self.projects, &name, sample);
+        }
+    }
+
+    /// The samples of the last round, for the store
+    fn latest(&self) -> Vec<(LogSource, StatsSample)> {
+        let containers = self.containers.iter().filter_map(|(name, history)| {
+            Some((LogSource::Container(name.clone()), *history.back()?))
+        });
+        let projects = self.projects.iter().filter_map(|(name, history)| {
+            Some((LogSource::Project(name.clone()), *history.back()?))
+        });
+        containers.chain(projects).collect()
+    }
+
+    /// Sample every `SAMPLE_INTERVAL` for as long as mgdocker runs, each round
+    /// is saved to the store and `tx` is notified
+    pub async fn run(
+        stats: Arc<RwLock<Stats>>,
+        tx: broadcast::Sender<()>,
+        store: Store,
+        config: MetricsConfig,
+    ) {
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut last_prune: Option<Instant> = None;
+
+        loop {
+            interval.tick().await;
+
+            if last_prune.is_none_or(|time| time.elapsed() >= PRUNE_INTERVAL) {
+                let config = config.clone();
+                if let Err(e) = store.run(move |store| store.prune(&config)).await {
+                    tracing::warn!("failed to prune the metrics store: {:#}", e);
+                }
+                last_prune = Some(Instant::now());
+            }
+
+            let samples = match Self::sample().await {
+                Ok(samples) => samples,
+                Err(e) => {
+                    tracing::warn!("failed to sample container stats: {:#}", e);
+                    continue;
+                }
+            };
+            let containers = Container::get_all().unwrap_or_default();
+
+            let latest = {
+                let mut stats = stats.write().await;
+                stats.record(samples, &containers);
+                stats.latest()
+            };
+
+            if let Err(e) = store.run(move |store| store.insert(&latest)).await {
+                tracing::warn!("failed to store container stats: {:#}", e);
+            }
+
+            // there may be no one listening
+            tx.send(()).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_samples() {
+        // `docker stats` while the web container restarts
+        let output = r#"{"BlockIO":"1.2MB / 4.1kB","CPUPerc":"0.52%","Container":"3f9a","ID":"3f9a","MemPerc":"1.21%","MemUsage":"24.1MiB / 1.944GiB","Name":"db","NetIO":"1.5kB / 0B","PIDs":"7"}
+{"BlockIO":"--","CPUPerc":"--","Container":"8c2e","ID":"8c2e","MemPerc":"--","MemUsage":"-- / --","Name":"web","NetIO":"--","PIDs":"--"}
+not json
+"#;
+
+        let samples = super::parse_samples(output, SystemTime::UNIX_EPOCH);
+
+        assert_eq!(samples.len(), 1);
+        let (name, sample) = &samples[0];
+        assert_eq!(name, "db");
+        assert_eq!(sample.cpu_percent, 0.52);
+        assert_eq!(sample.pids, 7);
+        assert_eq!(sample.net_rx, 1500);
+    }
+}
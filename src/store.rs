@@ -0,0 +1,588 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+
+use crate::{config::MetricsConfig, logs::LogSource, stats::StatsSample};
+
+/// Seconds per bucket of each stored resolution, every sample goes into all
+/// of them and the coarser ones are averaged as samples come in
+const RESOLUTIONS: [i64; 3] = [10, 300, 3600];
+
+/// Range of the metrics charts
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MetricsRange {
+    #[default]
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl MetricsRange {
+    pub const ALL: [MetricsRange; 4] = [Self::Hour, Self::Day, Self::Week, Self::Month];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Hour => "1h",
+            Self::Day => "24h",
+            Self::Week => "7d",
+            Self::Month => "30d",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|range| range.to_str() == s)
+    }
+
+    pub fn duration(self) -> Duration {
+        let hours = match self {
+            Self::Hour => 1,
+            Self::Day => 24,
+            Self::Week => 24 * 7,
+            Self::Month => 24 * 30,
+        };
+        Duration::from_secs(hours * 3600)
+    }
+
+    /// Bucket size the range is read from, a few hundred points per chart
+    fn resolution(self) -> i64 {
+        match self {
+            Self::Hour => RESOLUTIONS[0],
+            Self::Day => RESOLUTIONS[1],
+            Self::Week | Self::Month => RESOLUTIONS[2],
+        }
+    }
+}
+
+/// A task mgdocker ran, drawn as a marker on the charts
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    /// unix seconds
+    pub time: i64,
+    /// project or container the task ran on
+    pub name: String,
+    pub task: String,
+}
+
+/// Something the retention policy removed, or failed to remove
+#[derive(Debug, Clone)]
+pub struct RetentionRecord {
+    /// unix seconds
+    pub time: i64,
+    /// see `retention::RetentionRule`
+    pub rule: String,
+    pub name: String,
+    pub size: Option<u64>,
+    /// what docker said when the removal failed
+    pub error: Option<String>,
+}
+
+/// Local sqlite store for the stats history, the task annotations, the
+/// removals of the retention policy and the images compose projects last ran.
+/// The calls block on sqlite and the connection lock, async code goes through
+/// `Store::run`.
+#[derive(Clone)]
+pub struct Store {
+    conn: Arc<Mutex<Connection>>,
+}
+
+pub fn unix_secs(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+impl Store {
+    /// The database at `path`, or one in memory until mgdocker exits
+    pub fn open(path: Option<&Path>) -> Result<Store> {
+        let conn = match path {
+            Some(path) => Connection::open(path)
+                .with_context(|| format!("failed to open metrics database {}", path.display()))?,
+            None => Connection::open_in_memory()?,
+        };
+
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS samples (
+                resolution INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                name TEXT NOT NULL,
+                time INTEGER NOT NULL,
+                count INTEGER NOT NULL,
+                cpu_percent REAL NOT NULL,
+                mem_usage INTEGER NOT NULL,
+                mem_limit INTEGER NOT NULL,
+                net_rx INTEGER NOT NULL,
+                net_tx INTEGER NOT NULL,
+                block_read INTEGER NOT NULL,
+                block_write INTEGER NOT NULL,
+                pids INTEGER NOT NULL,
+                PRIMARY KEY (resolution, kind, name, time)
+            );
+            CREATE TABLE IF NOT EXISTS annotations (
+                time INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                name TEXT NOT NULL,
+                task TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS annotations_name ON annotations (kind, name, time);
+            CREATE TABLE IF NOT EXISTS retention_history (
+                time INTEGER NOT NULL,
+                rule TEXT NOT NULL,
+                name TEXT NOT NULL,
+                size INTEGER,
+                error TEXT
+            );
+            CREATE TABLE IF NOT EXISTS project_images (
+                project TEXT NOT NULL,
+                image TEXT NOT NULL,
+                PRIMARY KEY (project, image)
+            );
+            ",
+        )?;
+
+        Ok(Store {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Run store calls on the blocking pool, so a slow disk or a held lock
+    /// doesn't stall the async tasks
+    pub async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Store) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || f(&store)).await?
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        // a panic while holding the lock can't leave a transaction half done
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add a sample round. Gauges are averaged per bucket, the io counters keep the latest value.
+    pub fn insert(&self, samples: &[(LogSource, StatsSample)]) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        {
+            let mut stmt = tx.prepare_cached(
+                "
+                INSERT INTO samples VALUES (?1, ?2, ?3, ?4, 1, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+                ON CONFLICT DO UPDATE SET
+                    cpu_percent = (cpu_percent * count + excluded.cpu_percent) / (count + 1),
+                    mem_usage = (mem_usage * count + excluded.mem_usage) / (count + 1),
+                    mem_limit = excluded.mem_limit,
+                    net_rx = excluded.net_rx,
+                    net_tx = excluded.net_tx,
+                    block_read = excluded.block_read,
+                    block_write = excluded.block_write,
+                    pids = (pids * count + excluded.pids) / (count + 1),
+                    count = count + 1
+                ",
+            )?;
+
+            for (source, sample) in samples {
+                let time = unix_secs(sample.time);
+                for resolution in RESOLUTIONS {
+                    stmt.execute(params![
+                        resolution,
+                        source.kind(),
+                        source.name(),
+                        time - time % resolution,
+                        sample.cpu_percent,
+                        sample.mem_usage as i64,
+                        sample.mem_limit as i64,
+                        sample.net_rx as i64,
+                        sample.net_tx as i64,
+                        sample.block_read as i64,
+                        sample.block_write as i64,
+                        sample.pids as i64,
+                    ])?;
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Samples of a container or project over the range, oldest first
+    pub fn series(&self, source: &LogSource, range: MetricsRange) -> Result<Vec<StatsSample>> {
+        let since = unix_secs(SystemTime::now()) - range.duration().as_secs() as i64;
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "
+            SELECT time, cpu_percent, mem_usage, mem_limit, net_rx, net_tx, block_read, block_write, pids
+            FROM samples
+            WHERE resolution = ?1 AND kind = ?2 AND name = ?3 AND time >= ?4
+            ORDER BY time
+            ",
+        )?;
+
+        let rows = stmt.query_map(
+            params![range.resolution(), source.kind(), source.name(), since],
+            |row| {
+                Ok(StatsSample {
+                    time: UNIX_EPOCH + Duration::from_secs(row.get::<_, i64>(0)? as u64),
+                    cpu_percent: row.get(1)?,
+                    mem_usage: row.get::<_, i64>(2)? as u64,
+                    mem_limit: row.get::<_, i64>(3)? as u64,
+                    net_rx: row.get::<_, i64>(4)? as u64,
+                    net_tx: row.get::<_, i64>(5)? as u64,
+                    block_read: row.get::<_, i64>(6)? as u64,
+                    block_write: row.get::<_, i64>(7)? as u64,
+                    pids: row.get::<_, i64>(8)? as u64,
+                })
+            },
+        )?;
+
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Record that a task ran on a project or container
+    pub fn annotate(&self, source: &LogSource, task: &str) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO annotations VALUES (?1, ?2, ?3, ?4)",
+            params![
+                unix_secs(SystemTime::now()),
+                source.kind(),
+                source.name(),
+                task
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Tasks that ran on any of the sources over the range, oldest first
+    pub fn annotations(
+        &self,
+        sources: &[LogSource],
+        range: MetricsRange,
+    ) -> Result<Vec<Annotation>> {
+        let since = unix_secs(SystemTime::now()) - range.duration().as_secs() as i64;
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT time, name, task FROM annotations WHERE kind = ?1 AND name = ?2 AND time >= ?3",
+        )?;
+
+        let mut annotations = vec![];
+        for source in sources {
+            let rows = stmt.query_map(params![source.kind(), source.name(), since], |row| {
+                Ok(Annotation {
+                    time: row.get(0)?,
+                    name: row.get(1)?,
+                    task: row.get(2)?,
+                })
+            })?;
+            for row in rows {
+                annotations.push(row?);
+            }
+        }
+
+        annotations.sort_by_key(|a| a.time);
+        Ok(annotations)
+    }
+
+    pub fn record_retention(&self, record: &RetentionRecord) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO retention_history VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                record.time,
+                record.rule,
+                record.name,
+                record.size.map(|size| size as i64),
+                record.error
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Replace the images of the given projects, by image id. Projects that
+    /// are not given, e.g. brought down, keep the images they last ran.
+    pub fn record_project_images(
+        &self,
+        projects: &BTreeMap<String, BTreeSet<String>>,
+    ) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        {
+            let mut delete = tx.prepare_cached("DELETE FROM project_images WHERE project = ?1")?;
+            let mut insert = tx.prepare_cached("INSERT INTO project_images VALUES (?1, ?2)")?;
+            for (project, images) in projects {
+                delete.execute(params![project])?;
+                for image in images {
+                    insert.execute(params![project, image])?;
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Ids of the images any compose project last ran
+    pub fn project_images(&self) -> Result<HashSet<String>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached("SELECT DISTINCT image FROM project_images")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Drop the retention history older than `days`
+    pub fn prune_retention_history(&self, days: u64) -> Result<()> {
+        let now = unix_secs(SystemTime::now());
+        self.conn().execute(
+            "DELETE FROM retention_history WHERE time < ?1",
+            params![now - days as i64 * 24 * 3600],
+        )?;
+        Ok(())
+    }
+
+    /// The latest removals of the retention policy, newest first
+    pub fn retention_history(&self, limit: usize) -> Result<Vec<RetentionRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "SELECT time, rule, name, size, error FROM retention_history ORDER BY time DESC, rowid DESC LIMIT ?1",
+        )?;
+
+        let rows = stmt.query_map(params![limit as i64], |row| {
+            Ok(RetentionRecord {
+                time: row.get(0)?,
+                rule: row.get(1)?,
+                name: row.get(2)?,
+                size: row.get::<_, Option<i64>>(3)?.map(|size| size as u64),
+                error: row.get(4)?,
+            })
+        })?;
+
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Drop samples and annotations past their retention. The 10 second
+    /// samples are kept for a day, the 5 minute ones for a week and the
+    /// hourly ones for `retention_days`.
+    pub fn prune(&self, config: &MetricsConfig) -> Result<()> {
+        let now = unix_secs(SystemTime::now());
+        let retention = config.retention_days as i64 * 24 * 3600;
+        let keep = [
+            (RESOLUTIONS[0], (24 * 3600).min(retention)),
+            (RESOLUTIONS[1], (7 * 24 * 3600).min(retention)),
+            (RESOLUTIONS[2], retention),
+        ];
+
+        let conn = self.conn();
+        for (resolution, secs) in keep {
+            conn.execute(
+                "DELETE FROM samples WHERE resolution = ?1 AND time < ?2",
+                params![resolution, now - secs],
+            )?;
+        }
+        conn.execute(
+            "DELETE FROM annotations WHERE time < ?1",
+            params![now - retention],
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 3600;
+
+    fn sample(time: i64, cpu_percent: f64, mem_usage: u64, net_rx: u64) -> StatsSample {
+        StatsSample {
+            time: UNIX_EPOCH + Duration::from_secs(time as u64),
+            cpu_percent,
+            mem_usage,
+            mem_limit: 1000,
+            net_rx,
+            net_tx: 0,
+            block_read: 0,
+            block_write: 0,
+            pids: 2,
+        }
+    }
+
+    fn source() -> LogSource {
+        LogSource::Container("web".to_string())
+    }
+
+    /// resolution, time and count of each stored bucket
+    fn buckets(store: &Store) -> Vec<(i64, i64, i64)> {
+        let conn = store.conn();
+        let mut stmt = conn
+            .prepare("SELECT resolution, time, count FROM samples ORDER BY resolution, time")
+            .unwrap();
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap();
+        rows.collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn insert() {
+        let store = Store::open(None).unwrap();
+        // the first three share the 5 minute bucket, the last one starts the next
+        let start = 1_000 * 3600;
+        for (time, cpu, mem, rx) in [
+            (start + 5, 10.0, 100, 1),
+            (start + 15, 20.0, 200, 2),
+            (start + 25, 30.0, 300, 3),
+            (start + 305, 40.0, 400, 4),
+        ] {
+            store
+                .insert(&[(source(), sample(time, cpu, mem, rx))])
+                .unwrap();
+        }
+
+        assert_eq!(
+            buckets(&store),
+            [
+                (10, start, 1),
+                (10, start + 10, 1),
+                (10, start + 20, 1),
+                (10, start + 300, 1),
+                (300, start, 3),
+                (300, start + 300, 1),
+                (3600, start, 4),
+            ]
+        );
+
+        let conn = store.conn();
+        let hourly = conn
+            .query_row(
+                "SELECT cpu_percent, mem_usage, net_rx FROM samples WHERE resolution = 3600",
+                [],
+                |row| {
+                    Ok((
+                        row.get::<_, f64>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, i64>(2)?,
+                    ))
+                },
+            )
+            .unwrap();
+        // gauges are averaged, counters keep the latest value
+        assert_eq!(hourly, (25.0, 250, 4));
+    }
+
+    #[test]
+    fn series() {
+        let store = Store::open(None).unwrap();
+        let now = unix_secs(SystemTime::now());
+        store
+            .insert(&[(source(), sample(now - 2 * DAY, 1.0, 1, 1))])
+            .unwrap();
+        store.insert(&[(source(), sample(now, 2.0, 2, 2))]).unwrap();
+
+        assert_eq!(
+            store.series(&source(), MetricsRange::Hour).unwrap().len(),
+            1
+        );
+        assert_eq!(
+            store.series(&source(), MetricsRange::Week).unwrap().len(),
+            2
+        );
+        assert!(store
+            .series(&LogSource::Container("db".to_string()), MetricsRange::Week)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn prune() {
+        let store = Store::open(None).unwrap();
+        let now = unix_secs(SystemTime::now());
+        for days in [0, 2, 10, 40] {
+            store
+                .insert(&[(source(), sample(now - days * DAY, 1.0, 1, 1))])
+                .unwrap();
+        }
+        store
+            .conn()
+            .execute(
+                "INSERT INTO annotations VALUES (?1, 'container', 'web', 'recreate')",
+                params![now - 40 * DAY],
+            )
+            .unwrap();
+        store.annotate(&source(), "recreate").unwrap();
+
+        store
+            .prune(&MetricsConfig {
+                database: None,
+                retention_days: 30,
+            })
+            .unwrap();
+
+        let count = |resolution: i64| {
+            buckets(&store)
+                .iter()
+                .filter(|(r, _, _)| *r == resolution)
+                .count()
+        };
+        // a day of 10 second, a week of 5 minute and 30 days of hourly samples
+        assert_eq!((count(10), count(300), count(3600)), (1, 2, 3));
+        let annotations = store.annotations(&[source()], MetricsRange::Month).unwrap();
+        assert_eq!(annotations.len(), 1);
+    }
+
+    #[test]
+    fn prune_retention_history() {
+        let store = Store::open(None).unwrap();
+        let now = unix_secs(SystemTime::now());
+        for (days, name) in [(10, "old"), (1, "new")] {
+            store
+                .record_retention(&RetentionRecord {
+                    time: now - days * DAY,
+                    rule: "dangling".to_string(),
+                    name: name.to_string(),
+                    size: Some(1),
+                    error: None,
+                })
+                .unwrap();
+        }
+
+        store.prune_retention_history(7).unwrap();
+
+        let history = store.retention_history(10).unwrap();
+        assert_eq!(
+            history.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(),
+            ["new"]
+        );
+    }
+
+    #[test]
+    fn record_project_images() {
+        let store = Store::open(None).unwrap();
+        let images = |images: &[&str]| images.iter().map(|i| i.to_string()).collect();
+
+        store
+            .record_project_images(&BTreeMap::from([
+                ("app".to_string(), images(&["a", "b"])),
+                ("blog".to_string(), images(&["c"])),
+            ]))
+            .unwrap();
+        // blog was brought down, it keeps the image it last ran
+        store
+            .record_project_images(&BTreeMap::from([("app".to_string(), images(&["d"]))]))
+            .unwrap();
+
+        assert_eq!(
+            store.project_images().unwrap(),
+            HashSet::from(["c".to_string(), "d".to_string()])
+        );
+    }
+}
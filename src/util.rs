@@ -1,3 +1,9 @@
+use std::{
+    ffi::OsStr,
+    fmt::Display,
+    process::{ExitStatus, Stdio},
+    str::FromStr,
+};
 use tokio::io::{AsyncBufReadExt, BufReader};
 
 use anyhow::{Context, Result};
@@ -5,6 +11,7 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
+use serde::{Deserialize, Deserializer};
 use tokio::sync::broadcast;
 use tokio_stream::{wrappers::LinesStream, StreamExt};
 
@@ -39,7 +46,7 @@ pub async fn execute_command(
     event_name: &str,
     mut cmd: tokio::process::Child,
     tx: &broadcast::Sender<SseEvent>,
-) -> Result<()> {
+) -> Result<ExitStatus> {
     let stdout = cmd
         .stdout
         .take()
@@ -58,10 +65,151 @@ pub async fn execute_command(
         let line = line?;
         let evt = SseEvent {
             event: event_name.into(),
-            data: format!("{}\n", line),
+            data: format!("{}\n", escape_html(&line)),
         };
         tx.send(evt).context("execute_command: stdout send error")?;
     }
 
+    Ok(cmd.wait().await?)
+}
+
+/// Run `docker <args>`, broadcasting the command and its output.
+/// Fails when the command exits with a non zero status.
+pub async fn docker<S: AsRef<OsStr>>(
+    event_name: &str,
+    args: &[S],
+    tx: &broadcast::Sender<SseEvent>,
+) -> Result<()> {
+    let printable = args
+        .iter()
+        .map(|arg| arg.as_ref().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    tx.send(SseEvent {
+        event: event_name.into(),
+        data: format!("docker {}\n", escape_html(&printable)),
+    })
+    .context("docker: stdout send error")?;
+
+    let cmd = tokio::process::Command::new("docker")
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let status = execute_command(event_name, cmd, tx).await?;
+
+    if !status.success() {
+        return Err(anyhow::anyhow!("docker {} failed: {}", printable, status));
+    }
+
+    Ok(())
+}
+
+/// A line of task output that doesn't come from a command
+pub fn message(event_name: &str, message: &str, tx: &broadcast::Sender<SseEvent>) -> Result<()> {
+    tx.send(SseEvent {
+        event: event_name.into(),
+        data: format!("{}\n", escape_html(message)),
+    })
+    .context("message: stdout send error")?;
     Ok(())
 }
+
+/// Tell the task's output why it was refused and fail it
+pub fn refuse(event_name: &str, reason: String, tx: &broadcast::Sender<SseEvent>) -> Result<()> {
+    message(event_name, &reason, tx)?;
+    Err(anyhow::anyhow!(reason))
+}
+
+/// Element id from names, for the live updates' out of band swaps which
+/// select it as `#id`
+pub fn dom_id(parts: &[&str]) -> String {
+    parts
+        .join("--")
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Escape text for the sse results, htmx swaps the event data in as html
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Deserialize a query parameter, treating an empty form field as `None`
+pub fn empty_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let s = Option::<String>::deserialize(deserializer)?;
+    match s.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(s) => s.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+/// Parse a size as printed by the docker cli, e.g. `12.5MiB`, `1.2kB` or `0B`
+pub fn parse_bytes(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let multiplier: u64 = match unit.trim() {
+        "" | "B" => 1,
+        "kB" | "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "TB" => 1_000_000_000_000,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        "TiB" => 1 << 40,
+        _ => return None,
+    };
+
+    Some((number * multiplier as f64).round() as u64)
+}
+
+/// Format a byte count the way the docker cli does, e.g. `12.5MB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "kB", "MB", "GB", "TB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{}B", bytes)
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape() {
+        assert_eq!(
+            escape_html(r#"<img src=x onerror="alert(1)"> & more"#),
+            "&lt;img src=x onerror=&quot;alert(1)&quot;&gt; &amp; more"
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::{collections::BTreeMap, process::Command};
+
+use anyhow::Result;
+use tokio::sync::broadcast;
+
+use crate::{
+    container::{Container, PROJECT_LABEL},
+    disk::DiskUsageDetail,
+    fields::de,
+    model::SseEvent,
+    util,
+};
+
+/// A volume of `docker system df --verbose`, which adds the size and the
+/// number of containers using it to `docker volume ls`
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Volume {
+    #[serde(alias = "Name")]
+    pub name: String,
+    #[serde(alias = "Driver", default)]
+    pub driver: String,
+    #[serde(alias = "Mountpoint", default)]
+    pub mountpoint: String,
+    #[serde(alias = "Labels", deserialize_with = "de::labels", default)]
+    pub labels: BTreeMap<String, String>,
+    /// containers using the volume, running or not
+    #[serde(alias = "Links", deserialize_with = "de::optional_count", default)]
+    pub links: Option<u64>,
+    #[serde(alias = "Size", deserialize_with = "de::optional_bytes", default)]
+    pub size: Option<u64>,
+}
+
+/// Check a volume name the way docker does, which also keeps anything that
+/// looks like an option out of `docker volume create`
+pub fn validate_name(name: &str) -> Result<()> {
+    let regex = regex::Regex::new(r"^[a-zA-Z0-9][a-zA-Z0-9_.-]+$")?;
+    if !regex.is_match(name) {
+        return Err(anyhow::anyhow!(
+            "invalid volume name {}, only letters, digits and _.- are allowed, at least two characters",
+            name
+        ));
+    }
+
+    Ok(())
+}
+
+/// `key=value` labels, one per line
+pub fn parse_labels(labels: &str) -> Result<Vec<String>> {
+    labels
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.split_once('=') {
+            Some((key, _)) if !key.trim().is_empty() => Ok(line.to_string()),
+            _ => Err(anyhow::anyhow!(
+                "invalid label {}, expected key=value",
+                line
+            )),
+        })
+        .collect()
+}
+
+impl Volume {
+    pub fn get_all() -> Result<Vec<Volume>> {
+        let mut volumes = DiskUsageDetail::get()?.volumes;
+        volumes.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(volumes)
+    }
+
+    /// compose project that created the volume
+    pub fn project(&self) -> Option<String> {
+        self.labels.get(PROJECT_LABEL).cloned()
+    }
+
+    pub fn is_unused(&self) -> bool {
+        self.links == Some(0)
+    }
+
+    /// The containers mounting the volume, `docker ps` lists the names of
+    /// the volumes along with the bind mounted paths
+    pub fn containers<'a>(&self, containers: &'a [Container]) -> Vec<&'a Container> {
+        containers
+            .iter()
+            .filter(|c| c.mounts.contains(&self.name))
+            .collect()
+    }
+
+    /// Names of the containers mounting the volume, running or not
+    pub fn containers_using(name: &str) -> Result<Vec<String>> {
+        let output = Command::new("docker")
+            .arg("ps")
+            .arg("--all")
+            .arg("--filter")
+            .arg(format!("volume={}", name))
+            .arg("--format")
+            .arg("{{.Names}}")
+            .output()?;
+
+        Ok(String::from_utf8(output.stdout)?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect())
+    }
+
+    /// `docker volume create`, with the local driver when none is given
+    pub async fn create(
+        event_name: &str,
+        name: String,
+        driver: Option<String>,
+        labels: Option<String>,
+        tx: &broadcast::Sender<SseEvent>,
+    ) -> Result<()> {
+        if let Err(e) = validate_name(&name) {
+            return util::refuse(event_name, e.to_string(), tx);
+        }
+        let labels = match parse_labels(labels.as_deref().unwrap_or_default()) {
+            Ok(labels) => labels,
+            Err(e) => return util::refuse(event_name, e.to_string(), tx),
+        };
+
+        let mut args = vec!["volume".to_string(), "create".to_string()];
+        if let Some(driver) = driver {
+            if let Err(e) = validate_name(&driver) {
+                return util::refuse(event_name, e.to_string(), tx);
+            }
+            args.extend(["--driver".to_string(), driver]);
+        }
+        for label in labels {
+            args.extend(["--label".to_string(), label]);
+        }
+        args.push(name);
+        util::docker(event_name, &args, tx).await
+    }
+
+    /// `docker volume rm`, refused while containers mount the volume
+    pub async fn remove(name: String, tx: &broadcast::Sender<SseEvent>) -> Result<()> {
+        let containers = Self::containers_using(&name)?;
+        if !containers.is_empty() {
+            return util::refuse(
+                &name,
+                format!(
+                    "the volume is used by {}, remove the containers first",
+                    containers.join(", ")
+                ),
+                tx,
+            );
+        }
+
+        util::docker(&name, &["volume", "rm", &name], tx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volume() {
+        // a volume of `docker system df --verbose --format json` from docker 25
+        let volume: Volume = serde_json::from_str(r#"{"Driver":"local","Labels":"com.docker.compose.project=app,com.docker.compose.volume=db","Links":"1","Mountpoint":"/var/lib/docker/volumes/app_db/_data","Name":"app_db","Scope":"local","Size":"41.2MB"}"#).unwrap();
+
+        assert_eq!(volume.mountpoint, "/var/lib/docker/volumes/app_db/_data");
+        assert_eq!(volume.project(), Some("app".to_string()));
+        assert_eq!(volume.links, Some(1));
+        assert!(!volume.is_unused());
+    }
+
+    #[test]
+    fn validate_name() {
+        assert!(super::validate_name("app_db").is_ok());
+        assert!(super::validate_name("data.v2-1").is_ok());
+        assert!(super::validate_name("--help").is_err());
+        assert!(super::validate_name("a").is_err());
+        assert!(super::validate_name("a/b").is_err());
+    }
+
+    #[test]
+    fn parse_labels() {
+        assert_eq!(
+            super::parse_labels("team=data\n\n backup=daily \n").unwrap(),
+            vec!["team=data", "backup=daily"]
+        );
+        assert!(super::parse_labels("no-value").is_err());
+        assert!(super::parse_labels("=x").is_err());
+    }
+}